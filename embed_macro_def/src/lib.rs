@@ -0,0 +1,187 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use flate2::{write::GzEncoder, Compression};
+use proc_macro::{Literal, TokenStream, TokenTree};
+
+/// The ETag implementation of the server, shared so embedded and served files get the same ETags
+#[path = "../../src/http/http_etag.rs"]
+mod http_etag;
+
+use http_etag::content_etag;
+
+/// Extensions of the files which are precompressed with the `compress` option
+const COMPRESSIBLE: &[&str] = &[
+    "html", "htm", "css", "js", "mjs", "json", "map", "svg", "txt", "xml", "xhtml", "csv", "md",
+    "wasm",
+];
+
+/// Embeds the files of a directory into the binary
+/// <br>
+/// The path is relative to the directory of the `Cargo.toml` of the crate. Every file is embedded with
+/// `include_bytes!`, together with its modification time and a strong ETag computed from its content.
+/// With the `compress` option, gzip and brotli variants of text files are computed at compile time as well.
+/// The result is an `EmbeddedDir`, served with `StaticFiles::embedded`.
+/// <br>
+/// The expansion refers to `EmbeddedDir`, `EmbeddedFile` and `ContentType` through `crate::` paths, so the macro
+/// can only be used inside the server crate itself.
+/// <br>
+/// Changing an embedded file rebuilds the crate, since it is included with `include_bytes!`. Adding or removing
+/// files does not: watch the directory from the build script of the crate with
+/// `println!("cargo:rerun-if-changed=static");`.
+///
+/// # Example
+/// ``` rust
+/// static ASSETS: EmbeddedDir = embed_dir!("static", compress);
+///
+/// Server::new("127.0.0.1", 8000)
+///     .mount("/", StaticFiles::embedded(&ASSETS))
+///     .start();
+/// ```
+#[proc_macro]
+pub fn embed_dir(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(output) => output,
+        Err(message) => format!("compile_error!({:?})", message).parse().unwrap(),
+    }
+}
+
+fn expand(input: TokenStream) -> Result<TokenStream, String> {
+    let mut tokens = input.into_iter();
+    let directory = match tokens.next() {
+        Some(TokenTree::Literal(literal)) => {
+            let literal = literal.to_string();
+            literal
+                .strip_prefix('"')
+                .and_then(|literal| literal.strip_suffix('"'))
+                .ok_or("embed_dir! expects a string literal")?
+                .to_string()
+        }
+        _ => return Err("embed_dir! expects the path of a directory".to_string()),
+    };
+    let compress = match (tokens.next(), tokens.next()) {
+        (None, _) => false,
+        (Some(TokenTree::Punct(punct)), Some(TokenTree::Ident(ident)))
+            if punct.as_char() == ',' && ident.to_string() == "compress" =>
+        {
+            true
+        }
+        _ => {
+            return Err(
+                "embed_dir! expects `\"directory\"` or `\"directory\", compress`".to_string(),
+            )
+        }
+    };
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").map_err(|e| e.to_string())?;
+    let root = Path::new(&manifest_dir)
+        .join(&directory)
+        .canonicalize()
+        .map_err(|e| format!("Unable to embed {}: {}", directory, e))?;
+    let mut files = Vec::new();
+    collect_files(&root, &root, &mut files)
+        .map_err(|e| format!("Unable to embed {}: {}", directory, e))?;
+    let mut files = files
+        .into_iter()
+        .map(|file| {
+            let relative = file
+                .strip_prefix(&root)
+                .map_err(|e| e.to_string())?
+                .to_string_lossy()
+                .replace('\\', "/");
+            Ok((relative, file))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    // Sorted by the relative path, `EmbeddedDir` looks files up with a binary search
+    files.sort();
+
+    let mut entries = String::new();
+    for (relative, file) in files {
+        let data = fs::read(&file).map_err(|e| format!("Unable to embed {:?}: {}", file, e))?;
+        // The last extension of the file name, like `ContentType::parse_file_name` uses
+        let extension = relative
+            .rsplit('/')
+            .next()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, extension)| extension.to_ascii_lowercase())
+            .unwrap_or_default();
+        let modified = fs::metadata(&file)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let compressible = compress && COMPRESSIBLE.contains(&extension.as_str());
+        let (gzip, brotli) = match compressible {
+            true => (
+                variant(&data, gzip(&data).map_err(|e| e.to_string())?),
+                variant(&data, brotli(&data).map_err(|e| e.to_string())?),
+            ),
+            false => ("None".to_string(), "None".to_string()),
+        };
+        entries.push_str(&format!(
+            "crate::static_files::embedded::EmbeddedFile {{ path: {:?}, data: include_bytes!({:?}), content_type: crate::http::http_content_types::ContentType::from_extension({:?}), etag: {:?}, modified: {}, gzip: {}, brotli: {} }},",
+            relative,
+            file.to_string_lossy(),
+            extension,
+            content_etag(&data),
+            modified,
+            gzip,
+            brotli,
+        ));
+    }
+    format!(
+        "crate::static_files::embedded::EmbeddedDir {{ directory: {:?}, files: &[{}] }}",
+        root.to_string_lossy(),
+        entries
+    )
+    .parse()
+    .map_err(|e| format!("{:?}", e))
+}
+
+/// Collects the files of a directory, recursively
+/// <br>
+/// Symbolic links to files are followed as long as their target is inside the root directory, links to
+/// directories are skipped to avoid cycles. Hidden files are collected and only served if `StaticFiles::hidden_files` allows it.
+fn collect_files(root: &Path, directory: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        match path.canonicalize() {
+            Ok(target) if target.starts_with(root) => {}
+            _ => continue,
+        }
+        if path.is_dir() && !path.is_symlink() {
+            collect_files(root, &path, files)?;
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Formats a compressed variant as an `Option<&[u8]>` expression, `None` if it is not smaller than the data
+fn variant(data: &[u8], compressed: Vec<u8>) -> String {
+    match compressed.len() < data.len() {
+        true => format!("Some({})", Literal::byte_string(&compressed)),
+        false => "None".to_string(),
+    }
+}
+
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+fn brotli(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    {
+        let mut encoder = brotli::CompressorWriter::new(&mut output, 4096, 11, 22);
+        encoder.write_all(data)?;
+    }
+    Ok(output)
+}
@@ -0,0 +1,314 @@
+use std::fmt::{Debug, Display};
+
+use serde_json::error::Category;
+
+use crate::{
+    handler::IntoResponse,
+    http::{
+        http_codes::{get_reason_phrase, StatusCode},
+        http_content_types::ContentType,
+        http_headers::parse_quality_list,
+    },
+    utils::html::escape_html,
+    Request, Response,
+};
+
+/// The error type of the framework
+/// <br>
+/// Carries the status code of the response, a message which is safe to show to the client and
+/// an optional internal cause which is only logged. Handlers can return `Result<T, E>` for any
+/// `E: Into<Error>`, the error is rendered by the `ErrorRenderer` of the server.
+///
+/// # Arguments
+/// * `status` - The status code of the response : StatusCode
+/// * `message` - The public message : &str
+///
+/// # Example
+/// ``` rust
+/// fn index(_: Request) -> Result<Response, Error> {
+///     let data = fs::read_to_string("./static/index.html")?;
+///     Ok(Response::new(StatusCode::Ok, ContentType::TextHtmlCharsetUtf8, Vec::new(), Vec::new(), data))
+/// }
+///
+/// fn user(Path(id): Path<u64>) -> Result<String, Error> {
+///     Err(Error::new(StatusCode::NotFound, "No such user"))
+/// }
+/// ```
+pub struct Error {
+    status: StatusCode,
+    message: String,
+    cause: Option<Box<dyn std::error::Error + Send + Sync>>,
+}
+
+#[allow(unused)]
+impl Error {
+    pub fn new(status: StatusCode, message: &str) -> Error {
+        Error {
+            status,
+            message: message.to_string(),
+            cause: None,
+        }
+    }
+
+    /// Creates an error whose message is the reason phrase of the status
+    /// # Arguments
+    /// * `status` - The status code : StatusCode
+    pub fn from_status(status: StatusCode) -> Error {
+        Error::new(status, get_reason_phrase(status).as_str())
+    }
+
+    /// Creates a `500 Internal Server Error` with an internal cause
+    /// # Arguments
+    /// * `cause` - The internal cause, only logged : impl std::error::Error
+    pub fn internal(cause: impl std::error::Error + Send + Sync + 'static) -> Error {
+        Error::from_status(StatusCode::InternalServerError).with_cause(cause)
+    }
+
+    /// Sets the internal cause of the error
+    /// # Arguments
+    /// * `cause` - The internal cause, only logged : impl std::error::Error
+    /// # Returns
+    /// * `Error` - The error
+    pub fn with_cause(mut self, cause: impl std::error::Error + Send + Sync + 'static) -> Error {
+        self.cause = Some(Box::new(cause));
+        self
+    }
+
+    /// Gets the status code of the error
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Gets the public message of the error
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Gets the internal cause of the error
+    pub fn cause(&self) -> Option<&(dyn std::error::Error + Send + Sync)> {
+        self.cause.as_deref()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.cause {
+            Some(cause) => write!(f, "{} {}: {}", self.status, self.message, cause),
+            None => write!(f, "{} {}", self.status, self.message),
+        }
+    }
+}
+
+impl Debug for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.cause
+            .as_deref()
+            .map(|cause| cause as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl From<StatusCode> for Error {
+    fn from(status: StatusCode) -> Self {
+        Error::from_status(status)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        let status = match err.kind() {
+            std::io::ErrorKind::NotFound => StatusCode::NotFound,
+            std::io::ErrorKind::PermissionDenied => StatusCode::Forbidden,
+            _ => StatusCode::InternalServerError,
+        };
+        Error::from_status(status).with_cause(err)
+    }
+}
+
+/// Invalid JSON sent by the client is a `400 Bad Request`. I/O errors and serialization failures, which have no
+/// position in a JSON text, are bugs of the server and become a `500 Internal Server Error` without exposing the message.
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        match err.classify() {
+            Category::Io => Error::internal(err),
+            _ if err.line() == 0 => Error::internal(err),
+            _ => Error::new(StatusCode::BadRequest, err.to_string().as_str()).with_cause(err),
+        }
+    }
+}
+
+/// The error is kept in the extensions of the response and rendered by the server,
+/// once the `Accept` header of the request is known.
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let mut response = Response::get_empty();
+        response.set_status(self.status);
+        response.extensions.insert(self);
+        response
+    }
+}
+
+/// Renders errors into responses
+/// <br>
+/// Any `Fn(&Error, &Request) -> Response` closure is a renderer as well.
+pub trait ErrorRenderer: Send + Sync {
+    /// Renders an error
+    /// # Arguments
+    /// * `error` - The error : &Error
+    /// * `request` - The method, path and headers of the failed request : &Request
+    /// # Returns
+    /// * `Response` - The response sent to the client
+    fn render(&self, error: &Error, request: &Request) -> Response;
+}
+
+impl<F> ErrorRenderer for F
+where
+    F: Fn(&Error, &Request) -> Response + Send + Sync,
+{
+    fn render(&self, error: &Error, request: &Request) -> Response {
+        self(error, request)
+    }
+}
+
+/// Formats an error can be rendered in
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ErrorFormat {
+    Html,
+    Json,
+    Text,
+}
+
+impl ErrorFormat {
+    /// Chooses the format from the `Accept` header, plain text if nothing matches
+    /// # Arguments
+    /// * `request` - The request : &Request
+    /// # Returns
+    /// * `ErrorFormat` - The format : ErrorFormat
+    pub fn negotiate(request: &Request) -> ErrorFormat {
+        let accept = match request.get_header("Accept") {
+            Some(header) => header.value().to_string(),
+            None => return ErrorFormat::Text,
+        };
+        for (media_type, quality) in parse_quality_list(&accept) {
+            if quality <= 0.0 {
+                continue;
+            }
+            match media_type.as_str() {
+                "application/problem+json" | "application/json" => return ErrorFormat::Json,
+                "text/html" | "application/xhtml+xml" => return ErrorFormat::Html,
+                "text/plain" | "text/*" | "*/*" => return ErrorFormat::Text,
+                _ => {}
+            }
+        }
+        ErrorFormat::Text
+    }
+}
+
+/// The default error renderer
+/// <br>
+/// Responds with an HTML page, an RFC 7807 `application/problem+json` document or plain text,
+/// depending on the `Accept` header of the request.
+pub struct DefaultErrorRenderer;
+
+impl ErrorRenderer for DefaultErrorRenderer {
+    fn render(&self, error: &Error, request: &Request) -> Response {
+        let title = get_reason_phrase(error.status());
+        let (content_type, data) = match ErrorFormat::negotiate(request) {
+            ErrorFormat::Json => (
+                ContentType::ApplicationProblemJson,
+                serde_json::json!({
+                    "type": "about:blank",
+                    "title": title,
+                    "status": error.status() as u16,
+                    "detail": error.message(),
+                    "instance": request.path,
+                })
+                .to_string(),
+            ),
+            ErrorFormat::Html => (
+                ContentType::TextHtmlCharsetUtf8,
+                format!(
+                    "<!DOCTYPE html><html><head><title>{0} {1}</title></head><body><h1>{0} {1}</h1><p>{2}</p></body></html>",
+                    error.status() as u16,
+                    escape_html(&title),
+                    escape_html(error.message()),
+                ),
+            ),
+            ErrorFormat::Text => (
+                ContentType::TextPlainCharsetUtf8,
+                error.message().to_string(),
+            ),
+        };
+        Response::new(error.status(), content_type, Vec::new(), Vec::new(), data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::http::http_methods::HttpMethod;
+
+    #[test]
+    fn maps_client_json_errors_to_bad_request() {
+        let syntax = serde_json::from_str::<u32>("{").unwrap_err();
+        assert_eq!(Error::from(syntax).status(), StatusCode::BadRequest);
+        let data = serde_json::from_str::<u32>("\"a\"").unwrap_err();
+        assert_eq!(Error::from(data).status(), StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn maps_serialization_and_io_errors_to_internal_error() {
+        let map = HashMap::from([((1, 2), 3)]);
+        let error = Error::from(serde_json::to_string(&map).unwrap_err());
+        assert_eq!(error.status(), StatusCode::InternalServerError);
+        assert_eq!(error.message(), "Internal Server Error");
+        assert!(error.cause().is_some());
+
+        let io = serde_json::from_reader::<_, u32>(FailingReader).unwrap_err();
+        assert_eq!(Error::from(io).status(), StatusCode::InternalServerError);
+    }
+
+    struct FailingReader;
+
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("disk failed"))
+        }
+    }
+
+    #[test]
+    fn maps_io_errors_by_kind() {
+        let error = Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert_eq!(error.status(), StatusCode::NotFound);
+        let error = Error::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert_eq!(error.status(), StatusCode::Forbidden);
+    }
+
+    #[test]
+    fn renders_format_of_accept_header() {
+        let error = Error::new(StatusCode::NotFound, "No <such> user");
+        let render = |accept: &str| {
+            let request = Request::test(HttpMethod::GET, "/users/4", &[("Accept", accept)]);
+            DefaultErrorRenderer.render(&error, &request)
+        };
+
+        let response = render("application/json;q=0.5, text/html;q=0");
+        assert_eq!(response.content_type, ContentType::ApplicationProblemJson);
+        let problem: serde_json::Value = serde_json::from_slice(&response.data).unwrap();
+        assert_eq!(problem["status"], 404);
+        assert_eq!(problem["instance"], "/users/4");
+
+        let response = render("text/html");
+        assert_eq!(response.status, StatusCode::NotFound);
+        assert!(String::from_utf8_lossy(&response.data).contains("No &lt;such&gt; user"));
+
+        assert_eq!(render("image/png").data, b"No <such> user");
+    }
+}
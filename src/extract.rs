@@ -0,0 +1,388 @@
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    error::Error,
+    http::{http_codes::StatusCode, http_cookies::Cookie, http_headers::Header},
+    Request,
+};
+
+/// Types which can be extracted from a request, used as handler arguments
+/// <br>
+/// If the extraction fails the returned error is rendered instead of calling the handler.
+///
+/// # Example
+/// ``` rust
+/// fn get_user(Path(id): Path<u64>, Query(filter): Query<Filter>, State(db): State<Database>) -> impl IntoResponse {
+///     Json(db.find_user(id, filter))
+/// }
+///
+/// Route::new("/users/:id", get_user, HttpMethod::GET);
+/// ```
+pub trait FromRequest: Sized {
+    fn from_request(request: &Request) -> Result<Self, Error>;
+}
+
+impl FromRequest for Request {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        Ok(request.clone())
+    }
+}
+
+impl<T: FromRequest> FromRequest for Option<T> {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        Ok(T::from_request(request).ok())
+    }
+}
+
+impl FromRequest for String {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        Ok(request.data.clone())
+    }
+}
+
+impl FromRequest for Vec<u8> {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        Ok(request.raw_data.clone())
+    }
+}
+
+/// A single path variable
+pub trait PathVariable: Sized {
+    fn parse_variable(value: &str) -> Option<Self>;
+}
+
+macro_rules! impl_path_variable {
+    ($($ty:ty),*) => {
+        $(
+            impl PathVariable for $ty {
+                fn parse_variable(value: &str) -> Option<Self> {
+                    value.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_path_variable!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char, String
+);
+
+/// Types which can be built from the path variables of a route, in order of appearance
+pub trait FromPathVariables: Sized {
+    fn from_path_variables(variables: &[String]) -> Option<Self>;
+}
+
+impl<T: PathVariable> FromPathVariables for T {
+    fn from_path_variables(variables: &[String]) -> Option<Self> {
+        match variables {
+            [value] => T::parse_variable(value),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_from_path_variables {
+    ($($ty:ident),*) => {
+        impl<$($ty: PathVariable),*> FromPathVariables for ($($ty,)*) {
+            #[allow(non_snake_case)]
+            fn from_path_variables(variables: &[String]) -> Option<Self> {
+                match variables {
+                    [$($ty),*] => Some(($($ty::parse_variable($ty)?,)*)),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+impl_from_path_variables!(A, B);
+impl_from_path_variables!(A, B, C);
+impl_from_path_variables!(A, B, C, D);
+impl_from_path_variables!(A, B, C, D, E);
+
+/// Extracts the path variables of the route, e.g. `/users/:id`
+/// <br>
+/// A single value is extracted as `Path<T>`, several values as a tuple `Path<(A, B)>`. Rejects with `400 Bad Request`.
+pub struct Path<T>(pub T);
+
+impl<T: FromPathVariables> FromRequest for Path<T> {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        T::from_path_variables(&request.path_variables)
+            .map(Path)
+            .ok_or_else(|| Error::new(StatusCode::BadRequest, "Invalid path variables"))
+    }
+}
+
+/// Deserializes the query string. Rejects with `400 Bad Request`.
+pub struct Query<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest for Query<T> {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        serde_urlencoded::from_str(&request.query)
+            .map(Query)
+            .map_err(|e| Error::new(StatusCode::BadRequest, &e.to_string()))
+    }
+}
+
+/// Extracts all headers of the request
+pub struct Headers(pub Vec<Header>);
+
+impl FromRequest for Headers {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        Ok(Headers(request.headers.clone()))
+    }
+}
+
+/// Extracts all cookies of the request
+pub struct Cookies(pub Vec<Cookie>);
+
+impl FromRequest for Cookies {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        Ok(Cookies(request.cookies.clone()))
+    }
+}
+
+/// Extracts the body of the request as text, exactly as sent if it is valid UTF-8.
+/// Binary bodies are extracted with a `Vec<u8>` argument.
+pub struct Body(pub String);
+
+impl FromRequest for Body {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        Ok(Body(request.data.clone()))
+    }
+}
+
+/// Returns `true` if the `Content-Type` of the request has the given media type
+fn has_content_type(request: &Request, media_type: &str) -> bool {
+    request.get_header("Content-Type").is_some_and(|header| {
+        header
+            .value()
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .eq_ignore_ascii_case(media_type)
+    })
+}
+
+/// Deserializes an `application/x-www-form-urlencoded` body.
+/// Rejects with `415 Unsupported Media Type` or `400 Bad Request`.
+pub struct Form<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest for Form<T> {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        if !has_content_type(request, "application/x-www-form-urlencoded") {
+            return Err(Error::new(
+                StatusCode::UnsupportedMediaType,
+                "Expected application/x-www-form-urlencoded",
+            ));
+        }
+        serde_urlencoded::from_str(&request.data)
+            .map(Form)
+            .map_err(|e| Error::new(StatusCode::BadRequest, &e.to_string()))
+    }
+}
+
+/// Deserializes an `application/json` body, or serializes a JSON response.
+/// Rejects with `415 Unsupported Media Type` or `400 Bad Request`.
+pub struct Json<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest for Json<T> {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        if !has_content_type(request, "application/json") {
+            return Err(Error::new(
+                StatusCode::UnsupportedMediaType,
+                "Expected application/json",
+            ));
+        }
+        serde_json::from_str(&request.data)
+            .map(Json)
+            .map_err(|e| Error::new(StatusCode::BadRequest, &e.to_string()))
+    }
+}
+
+/// Extracts the application state registered with `Server::with_state`.
+/// Rejects with `500 Internal Server Error` if no state of the type was registered.
+pub struct State<T>(pub Arc<T>);
+
+impl<T: Send + Sync + 'static> FromRequest for State<T> {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        request.state::<T>().map(State).ok_or_else(|| {
+            Error::new(
+                StatusCode::InternalServerError,
+                "Application state is not registered",
+            )
+        })
+    }
+}
+
+/// Extracts a per-request value inserted into `Request::extensions` by a middleware.
+/// Rejects with `500 Internal Server Error` if the value is missing.
+pub struct Extension<T>(pub T);
+
+impl<T: Clone + Send + Sync + 'static> FromRequest for Extension<T> {
+    fn from_request(request: &Request) -> Result<Self, Error> {
+        request
+            .extensions
+            .get::<T>()
+            .cloned()
+            .map(Extension)
+            .ok_or_else(|| {
+                Error::new(
+                    StatusCode::InternalServerError,
+                    "Request extension is missing",
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{http::http_methods::HttpMethod, transmitters::extensions::Extensions};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Filter {
+        name: String,
+        page: u32,
+    }
+
+    fn post(content_type: &str, body: &str) -> Request {
+        Request::parse(format!(
+            "POST /items HTTP/1.1\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+            content_type,
+            body.len(),
+            body
+        ))
+    }
+
+    fn status<T>(result: Result<T, Error>) -> StatusCode {
+        match result {
+            Ok(_) => StatusCode::Ok,
+            Err(error) => error.status(),
+        }
+    }
+
+    #[test]
+    fn extracts_path_variables() {
+        let mut request = Request::test(HttpMethod::GET, "/users/4/posts/x", &[]);
+        request.set_path_variables(vec!["4".to_string(), "x".to_string()]);
+        let Path((id, slug)) = Path::<(u64, String)>::from_request(&request).unwrap();
+        assert_eq!((id, slug.as_str()), (4, "x"));
+        assert_eq!(
+            status(Path::<u64>::from_request(&request)),
+            StatusCode::BadRequest
+        );
+        request.set_path_variables(vec!["four".to_string()]);
+        assert_eq!(
+            status(Path::<u64>::from_request(&request)),
+            StatusCode::BadRequest
+        );
+    }
+
+    #[test]
+    fn extracts_query() {
+        let request = Request::test(HttpMethod::GET, "/items?name=a%20b&page=2", &[]);
+        let Query(filter) = Query::<Filter>::from_request(&request).unwrap();
+        assert_eq!(
+            filter,
+            Filter {
+                name: "a b".to_string(),
+                page: 2
+            }
+        );
+        let request = Request::test(HttpMethod::GET, "/items?page=x", &[]);
+        assert_eq!(
+            status(Query::<Filter>::from_request(&request)),
+            StatusCode::BadRequest
+        );
+    }
+
+    #[test]
+    fn extracts_body_as_sent() {
+        let body = "line 1\r\n\r\n  line 3  \r\n";
+        let request = post("text/plain", body);
+        assert_eq!(Body::from_request(&request).unwrap().0, body);
+        assert_eq!(String::from_request(&request).unwrap(), body);
+        assert_eq!(Vec::<u8>::from_request(&request).unwrap(), body.as_bytes());
+    }
+
+    #[test]
+    fn extracts_form() {
+        let request = post("application/x-www-form-urlencoded", "name=a+b&page=3");
+        let Form(filter) = Form::<Filter>::from_request(&request).unwrap();
+        assert_eq!(
+            filter,
+            Filter {
+                name: "a b".to_string(),
+                page: 3
+            }
+        );
+        let request = post("application/json", "name=a&page=3");
+        assert_eq!(
+            status(Form::<Filter>::from_request(&request)),
+            StatusCode::UnsupportedMediaType
+        );
+        let request = post("application/x-www-form-urlencoded", "name=a");
+        assert_eq!(
+            status(Form::<Filter>::from_request(&request)),
+            StatusCode::BadRequest
+        );
+    }
+
+    #[test]
+    fn extracts_json() {
+        let request = post(
+            "application/json; charset=utf-8",
+            "{\n\n\"name\": \"a\",\r\n\"page\": 1}",
+        );
+        let Json(filter) = Json::<Filter>::from_request(&request).unwrap();
+        assert_eq!(
+            filter,
+            Filter {
+                name: "a".to_string(),
+                page: 1
+            }
+        );
+        let request = post("text/plain", "{}");
+        assert_eq!(
+            status(Json::<Filter>::from_request(&request)),
+            StatusCode::UnsupportedMediaType
+        );
+        let request = post("application/json", "{\"name\": 1}");
+        assert_eq!(
+            status(Json::<Filter>::from_request(&request)),
+            StatusCode::BadRequest
+        );
+    }
+
+    #[test]
+    fn extracts_state_and_extensions() {
+        let mut request = Request::test(HttpMethod::GET, "/", &[]);
+        assert_eq!(
+            status(State::<HashMap<String, u32>>::from_request(&request)),
+            StatusCode::InternalServerError
+        );
+        assert_eq!(
+            status(Extension::<u32>::from_request(&request)),
+            StatusCode::InternalServerError
+        );
+        assert!(Option::<Extension<u32>>::from_request(&request)
+            .unwrap()
+            .is_none());
+
+        let mut state = Extensions::new();
+        state.insert(Arc::new(HashMap::from([("a".to_string(), 1u32)])));
+        request.app_state = Arc::new(state);
+        request.extensions.insert(7u32);
+        let State(map) = State::<HashMap<String, u32>>::from_request(&request).unwrap();
+        assert_eq!(map["a"], 1);
+        assert_eq!(Extension::<u32>::from_request(&request).unwrap().0, 7);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{
+    http::{http_codes::StatusCode, http_content_types::ContentType},
+    Request,
+};
+
+/// Predicate deciding whether a route accepts a request
+/// <br>
+/// When a guard rejects a request the server tries the next route with the same path and method.
+/// If no route accepts the request, the `rejection` status of the first failing guard is returned.
+/// <br>
+/// Any `Fn(&Request) -> bool` closure is a guard rejecting with `400 Bad Request`.
+///
+/// # Example
+/// ``` rust
+/// Route::new("/users", create_user, HttpMethod::POST)
+///     .guard(content_type(ContentType::ApplicationJson))
+///     .guard(|request: &Request| request.data.len() < 1024);
+/// ```
+pub trait Guard: Send + Sync {
+    /// Returns `true` if the request is accepted
+    fn check(&self, request: &Request) -> bool;
+
+    /// The status returned when no route accepted the request
+    fn rejection(&self) -> StatusCode {
+        StatusCode::BadRequest
+    }
+}
+
+impl<F> Guard for F
+where
+    F: Fn(&Request) -> bool + Send + Sync,
+{
+    fn check(&self, request: &Request) -> bool {
+        self(request)
+    }
+}
+
+/// Guard accepting requests which contain a header
+pub struct HeaderGuard {
+    key: String,
+}
+
+impl Guard for HeaderGuard {
+    fn check(&self, request: &Request) -> bool {
+        request.get_header(&self.key).is_some()
+    }
+}
+
+/// Guard accepting requests with a given `Content-Type`, parameters such as `charset` are ignored
+pub struct ContentTypeGuard {
+    content_type: ContentType,
+}
+
+impl Guard for ContentTypeGuard {
+    fn check(&self, request: &Request) -> bool {
+        let expected = self.content_type.to_string();
+        let expected = expected.split(';').next().unwrap_or_default().trim();
+        match request.get_header("Content-Type") {
+            Some(header) => header
+                .value()
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .eq_ignore_ascii_case(expected),
+            None => false,
+        }
+    }
+
+    fn rejection(&self) -> StatusCode {
+        StatusCode::UnsupportedMediaType
+    }
+}
+
+/// Guard accepting requests sent to a given host, the port is ignored
+pub struct HostGuard {
+    host: String,
+}
+
+impl Guard for HostGuard {
+    fn check(&self, request: &Request) -> bool {
+        match request.get_header("Host") {
+            Some(header) => strip_port(header.value().trim()).eq_ignore_ascii_case(&self.host),
+            None => false,
+        }
+    }
+
+    fn rejection(&self) -> StatusCode {
+        StatusCode::NotFound
+    }
+}
+
+/// Removes the port from a `Host` header value, keeping IPv6 literals such as `[::1]` intact
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port))
+            if !port.is_empty()
+                && port.bytes().all(|b| b.is_ascii_digit())
+                && (!name.starts_with('[') || name.ends_with(']')) =>
+        {
+            name
+        }
+        _ => host,
+    }
+}
+
+/// Creates a guard accepting requests which contain a header
+/// # Arguments
+/// * `key` - The key of the header : &str
+/// # Returns
+/// * `HeaderGuard` - The guard, rejects with `400 Bad Request`
+#[allow(unused)]
+pub fn header_present(key: &str) -> HeaderGuard {
+    HeaderGuard {
+        key: key.to_string(),
+    }
+}
+
+/// Creates a guard accepting requests with a given `Content-Type`
+/// # Arguments
+/// * `content_type` - The expected content type : ContentType
+/// # Returns
+/// * `ContentTypeGuard` - The guard, rejects with `415 Unsupported Media Type`
+#[allow(unused)]
+pub fn content_type(content_type: ContentType) -> ContentTypeGuard {
+    ContentTypeGuard { content_type }
+}
+
+/// Creates a guard accepting requests sent to a given host
+/// # Arguments
+/// * `host` - The expected host without port, IPv6 addresses in brackets, e.g. `[::1]` : &str
+/// # Returns
+/// * `HostGuard` - The guard, rejects with `404 Not Found`
+#[allow(unused)]
+pub fn host(host: &str) -> HostGuard {
+    HostGuard {
+        host: host.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::http_methods::HttpMethod;
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        Request::test(HttpMethod::POST, "/", headers)
+    }
+
+    #[test]
+    fn strips_ports_but_keeps_ipv6_literals() {
+        assert_eq!(strip_port("example.com:8080"), "example.com");
+        assert_eq!(strip_port("example.com"), "example.com");
+        assert_eq!(strip_port("[::1]:8080"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+        assert_eq!(strip_port("[::1"), "[::1");
+        assert_eq!(strip_port("example.com:"), "example.com:");
+    }
+
+    #[test]
+    fn matches_host_without_port() {
+        let guard = host("[::1]");
+        assert!(guard.check(&request(&[("Host", "[::1]:8080")])));
+        assert!(guard.check(&request(&[("Host", "[::1]")])));
+        assert!(!guard.check(&request(&[("Host", "[::2]:8080")])));
+        assert!(!guard.check(&request(&[])));
+        assert!(host("Example.com").check(&request(&[("Host", "example.com:80")])));
+        assert_eq!(guard.rejection(), StatusCode::NotFound);
+    }
+
+    #[test]
+    fn matches_content_type_ignoring_parameters() {
+        let guard = content_type(ContentType::ApplicationJson);
+        assert!(guard.check(&request(&[(
+            "Content-Type",
+            "application/json; charset=utf-8"
+        )])));
+        assert!(!guard.check(&request(&[("Content-Type", "text/plain")])));
+        assert!(!guard.check(&request(&[])));
+        assert_eq!(guard.rejection(), StatusCode::UnsupportedMediaType);
+    }
+
+    #[test]
+    fn requires_header() {
+        let guard = header_present("X-Token");
+        assert!(guard.check(&request(&[("X-Token", "abc")])));
+        assert!(!guard.check(&request(&[])));
+    }
+}
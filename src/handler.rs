@@ -0,0 +1,171 @@
+use serde::Serialize;
+
+use crate::{
+    error::Error,
+    extract::{FromRequest, Json},
+    http::{http_codes::StatusCode, http_content_types::ContentType},
+    Request, Response,
+};
+
+/// Types which can be returned from a handler
+/// # Example
+/// ``` rust
+/// fn health() -> impl IntoResponse {
+///     (StatusCode::Ok, "up".to_string())
+/// }
+/// ```
+pub trait IntoResponse {
+    fn into_response(self) -> Response;
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+impl IntoResponse for () {
+    fn into_response(self) -> Response {
+        Response::get_empty()
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Response {
+        let mut response = Response::get_empty();
+        response.set_data(self);
+        response
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> Response {
+        self.to_string().into_response()
+    }
+}
+
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> Response {
+        let mut response = Response::get_empty();
+        response.set_status(self);
+        response
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for (StatusCode, T) {
+    fn into_response(self) -> Response {
+        let mut response = self.1.into_response();
+        response.set_status(self.0);
+        response
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for (StatusCode, ContentType, T) {
+    fn into_response(self) -> Response {
+        let mut response = self.2.into_response();
+        response.set_status(self.0).set_content_type(self.1);
+        response
+    }
+}
+
+impl<T: IntoResponse, E: Into<Error>> IntoResponse for Result<T, E> {
+    fn into_response(self) -> Response {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(err) => err.into().into_response(),
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        match serde_json::to_string(&self.0) {
+            Ok(data) => Response::new(
+                StatusCode::Ok,
+                ContentType::ApplicationJson,
+                Vec::new(),
+                Vec::new(),
+                data,
+            ),
+            Err(e) => Error::internal(e).into_response(),
+        }
+    }
+}
+
+/// Functions which can handle a request
+/// <br>
+/// Implemented for every function taking up to eight arguments which implement `FromRequest`
+/// and returning a type which implements `IntoResponse`. The arguments are extracted in order,
+/// the error of the first failing extraction is rendered instead of calling the function.
+pub trait Handler<Args>: Send + Sync + 'static {
+    fn call(&self, request: Request) -> Response;
+}
+
+macro_rules! impl_handler {
+    ($($ty:ident),*) => {
+        impl<F, R, $($ty,)*> Handler<($($ty,)*)> for F
+        where
+            F: Fn($($ty),*) -> R + Send + Sync + 'static,
+            R: IntoResponse,
+            $($ty: FromRequest,)*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            fn call(&self, request: Request) -> Response {
+                $(
+                    let $ty = match $ty::from_request(&request) {
+                        Ok(value) => value,
+                        Err(error) => return error.into_response(),
+                    };
+                )*
+                self($($ty),*).into_response()
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(A);
+impl_handler!(A, B);
+impl_handler!(A, B, C);
+impl_handler!(A, B, C, D);
+impl_handler!(A, B, C, D, E);
+impl_handler!(A, B, C, D, E, G);
+impl_handler!(A, B, C, D, E, G, H);
+impl_handler!(A, B, C, D, E, G, H, I);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{extract::Path, http::http_methods::HttpMethod};
+
+    fn rename(Path(id): Path<u64>, Json(name): Json<String>) -> String {
+        format!("{}={}", id, name)
+    }
+
+    fn call<Args>(handler: impl Handler<Args>, request: Request) -> Response {
+        handler.call(request)
+    }
+
+    #[test]
+    fn calls_handler_with_extracted_arguments() {
+        let mut request = Request::parse(
+            "PUT /users/4 HTTP/1.1\r\nContent-Type: application/json\r\n\r\n\"ann\"".to_string(),
+        );
+        request.set_path_variables(vec!["4".to_string()]);
+        let response = call(rename, request);
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.data, b"4=ann");
+    }
+
+    #[test]
+    fn renders_first_failing_extraction() {
+        let request = Request::test(HttpMethod::PUT, "/users/x", &[]);
+        assert_eq!(call(rename, request).status, StatusCode::BadRequest);
+        let mut request = Request::test(HttpMethod::PUT, "/users/4", &[]);
+        request.set_path_variables(vec!["4".to_string()]);
+        assert_eq!(
+            call(rename, request).status,
+            StatusCode::UnsupportedMediaType
+        );
+    }
+}
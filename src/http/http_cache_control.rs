@@ -0,0 +1,62 @@
+use std::fmt::Display;
+
+/// Lifetime of immutable responses, one year
+const IMMUTABLE_MAX_AGE: u64 = 365 * 24 * 60 * 60;
+
+/// The value of a `Cache-Control` response header
+///
+/// # Example
+/// ``` rust
+/// use http::http_cache_control::CacheControl;
+/// assert_eq!(CacheControl::max_age(3600).to_string(), "public, max-age=3600");
+/// assert_eq!(CacheControl::no_cache().directive("must-revalidate").to_string(), "no-cache, must-revalidate");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheControl {
+    directives: Vec<String>,
+}
+
+#[allow(unused)]
+impl CacheControl {
+    /// Allows any cache to store the response and reuse it for `seconds` without revalidation
+    pub fn max_age(seconds: u64) -> CacheControl {
+        CacheControl::new(&["public".to_string(), format!("max-age={}", seconds)])
+    }
+
+    /// Caches the response for a year without ever revalidating it, for content which never changes under the same URL
+    pub fn immutable() -> CacheControl {
+        CacheControl::max_age(IMMUTABLE_MAX_AGE).directive("immutable")
+    }
+
+    /// Requires caches to revalidate the response with the server before every reuse
+    pub fn no_cache() -> CacheControl {
+        CacheControl::new(&["no-cache".to_string()])
+    }
+
+    /// Forbids caches to store the response
+    pub fn no_store() -> CacheControl {
+        CacheControl::new(&["no-store".to_string()])
+    }
+
+    /// Adds a directive, e.g. `must-revalidate` or `stale-while-revalidate=60`
+    /// # Arguments
+    /// * `directive` - The directive : &str
+    /// # Returns
+    /// * `CacheControl` - The header value
+    pub fn directive(mut self, directive: &str) -> CacheControl {
+        self.directives.push(directive.to_string());
+        self
+    }
+
+    fn new(directives: &[String]) -> CacheControl {
+        CacheControl {
+            directives: directives.to_vec(),
+        }
+    }
+}
+
+impl Display for CacheControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.directives.join(", "))
+    }
+}
@@ -139,7 +139,7 @@ pub enum StatusCode {
 
 impl Display for StatusCode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self)
+        write!(f, "{}", *self as u16)
     }
 }
 
@@ -155,3 +155,16 @@ pub fn get_status_line(status: StatusCode) -> String {
         .unwrap()
         .to_string()
 }
+
+/// Returns the reason phrase for a given status code
+/// # Arguments
+/// * `status` - The status code : StatusCode
+/// # Returns
+/// * `String` - The reason phrase, e.g. `Not Found` : String
+pub fn get_reason_phrase(status: StatusCode) -> String {
+    get_status_line(status)
+        .splitn(3, ' ')
+        .nth(2)
+        .unwrap_or_default()
+        .to_string()
+}
@@ -1,7 +1,5 @@
 use std::fmt::Display;
 
-use regex::Regex;
-
 use crate::{server::get_log_level, utils::logger::Logger};
 
 const CONTENT_TYPES: &[(u16, &str)] = &[
@@ -94,6 +92,91 @@ const CONTENT_TYPES: &[(u16, &str)] = &[
     (77, "video/3gpp2"),
     (78, "audio/3gpp2"),
     (79, "application/x-7z-compressed"),
+    (80, "application/problem+json"),
+];
+
+/// The content types of file extensions, lowercase without the leading dot
+const FILE_EXTENSIONS: &[(&str, ContentType)] = &[
+    ("html", ContentType::TextHtmlCharsetUtf8),
+    ("txt", ContentType::TextPlainCharsetUtf8),
+    ("css", ContentType::TextCssCharsetUtf8),
+    ("js", ContentType::TextJavascriptCharsetUtf8),
+    ("jpeg", ContentType::ImageJpeg),
+    ("jpg", ContentType::ImageJpeg),
+    ("png", ContentType::ImagePng),
+    ("gif", ContentType::ImageGif),
+    ("svg", ContentType::ImageSvgXml),
+    ("webp", ContentType::ImageWebp),
+    ("bmp", ContentType::ImageBmp),
+    ("tiff", ContentType::ImageTiff),
+    ("json", ContentType::ApplicationJson),
+    ("xml", ContentType::ApplicationXml),
+    ("pdf", ContentType::ApplicationPdf),
+    ("zip", ContentType::ApplicationZip),
+    ("bin", ContentType::ApplicationOctetStream),
+    ("abw", ContentType::ApplicationXAbiword),
+    ("apng", ContentType::ImageApng),
+    ("arc", ContentType::ApplicationXFreearc),
+    ("avi", ContentType::VideoXMsvideo),
+    ("azw", ContentType::ApplicationVndAmazonEbook),
+    ("bz", ContentType::ApplicationXBzip),
+    ("bz2", ContentType::ApplicationXBzip2),
+    ("cda", ContentType::ApplicationXCdf),
+    ("csh", ContentType::ApplicationXCsh),
+    (
+        "docx",
+        ContentType::ApplicationVndOpenxmlformatsOfficedocumentWordprocessingmlDocument,
+    ),
+    ("eot", ContentType::ApplicationVndMsFontobject),
+    ("epub", ContentType::ApplicationEpubZip),
+    ("gz", ContentType::ApplicationGzip),
+    ("ico", ContentType::ApplicationVndMicrosoftIcon),
+    ("jar", ContentType::ApplicationJavaArchive),
+    ("midi", ContentType::AudioMidi),
+    ("mjs", ContentType::TextJavascriptDuplicate),
+    ("mp3", ContentType::AudioMpeg),
+    ("mp4", ContentType::VideoMp4),
+    ("mpeg", ContentType::VideoMpeg),
+    ("mpkg", ContentType::ApplicationVndAppleInstallerXml),
+    (
+        "odp",
+        ContentType::ApplicationVndOasisOpendocumentPresentation,
+    ),
+    (
+        "ods",
+        ContentType::ApplicationVndOasisOpendocumentSpreadsheet,
+    ),
+    ("odt", ContentType::ApplicationVndOasisOpendocumentText),
+    ("oga", ContentType::AudioOgg),
+    ("otf", ContentType::FontOtf),
+    ("ogx", ContentType::ApplicationOgg),
+    ("php", ContentType::ApplicationXHttpdPhp),
+    (
+        "pptx",
+        ContentType::ApplicationVndOpenxmlformatsOfficedocumentPresentationmlPresentation,
+    ),
+    ("rar", ContentType::ApplicationVndRar),
+    ("rtf", ContentType::ApplicationRtf),
+    ("sh", ContentType::ApplicationXSh),
+    ("tar", ContentType::ApplicationXTar),
+    ("tif", ContentType::ImageTiffDuplicate),
+    ("ts", ContentType::VideoMp2t),
+    ("ttf", ContentType::FontTtf),
+    ("wav", ContentType::AudioWav),
+    ("weba", ContentType::AudioWebm),
+    ("webm", ContentType::VideoWebm),
+    ("woff", ContentType::FontWoff),
+    ("woff2", ContentType::FontWoff2),
+    ("xhtml", ContentType::ApplicationXhtmlXml),
+    ("xls", ContentType::ApplicationVndMsExcel),
+    (
+        "xlsx",
+        ContentType::ApplicationVndOpenxmlformatsOfficedocumentSpreadsheetmlSheet,
+    ),
+    ("xul", ContentType::ApplicationVndMozillaXulXml),
+    ("3gp", ContentType::Video3gpp),
+    ("3g2", ContentType::Video3gpp2),
+    ("7z", ContentType::ApplicationX7zCompressed),
 ];
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -186,6 +269,7 @@ pub enum ContentType {
     Video3gpp2 = 77,
     Audio3gpp2 = 78,
     ApplicationX7zCompressed = 79,
+    ApplicationProblemJson = 80,
 }
 
 /// Get the content type from the code
@@ -209,8 +293,11 @@ pub fn get_type(value: ContentType) -> String {
 
 impl ContentType {
     /// Parse the file name to get the content type
+    /// <br>
+    /// Only the last extension of the file name is used, e.g. `app.min.js` is JavaScript. Files without
+    /// a known extension are served as `application/octet-stream`.
     /// # Arguments
-    /// * `value` - The file name : &str
+    /// * `value` - The file name or path : &str
     /// # Returns
     /// * `ContentType` - The content type : ContentType
     pub fn parse_file_name(value: &str) -> ContentType {
@@ -219,80 +306,29 @@ impl ContentType {
             level: get_log_level(),
         };
         logger.debug(&["parse_file_name", value]);
-        let regex = Regex::new(r"(\..*)$").unwrap();
-        let f_type = regex.captures(value).unwrap().get(1).unwrap().as_str();
+        let file_name = value.rsplit('/').next().unwrap_or_default();
+        match file_name.rsplit_once('.') {
+            Some((_, extension)) => ContentType::from_extension(extension),
+            None => ContentType::ApplicationOctetStream,
+        }
+    }
 
-        match f_type {
-            ".html" => ContentType::TextHtmlCharsetUtf8,
-            ".txt" => ContentType::TextPlainCharsetUtf8,
-            ".css" => ContentType::TextCssCharsetUtf8,
-            ".js" => ContentType::TextJavascriptCharsetUtf8,
-            ".jpeg" | ".jpg" => ContentType::ImageJpeg,
-            ".png" => ContentType::ImagePng,
-            ".gif" => ContentType::ImageGif,
-            ".svg" => ContentType::ImageSvgXml,
-            ".webp" => ContentType::ImageWebp,
-            ".bmp" => ContentType::ImageBmp,
-            ".tiff" => ContentType::ImageTiff,
-            ".json" => ContentType::ApplicationJson,
-            ".xml" => ContentType::ApplicationXml,
-            ".pdf" => ContentType::ApplicationPdf,
-            ".zip" => ContentType::ApplicationZip,
-            ".bin" => ContentType::ApplicationOctetStream,
-            ".abw" => ContentType::ApplicationXAbiword,
-            ".apng" => ContentType::ImageApng,
-            ".arc" => ContentType::ApplicationXFreearc,
-            ".avi" => ContentType::VideoXMsvideo,
-            ".azw" => ContentType::ApplicationVndAmazonEbook,
-            ".bz" => ContentType::ApplicationXBzip,
-            ".bz2" => ContentType::ApplicationXBzip2,
-            ".cda" => ContentType::ApplicationXCdf,
-            ".csh" => ContentType::ApplicationXCsh,
-            ".docx" => {
-                ContentType::ApplicationVndOpenxmlformatsOfficedocumentWordprocessingmlDocument
-            }
-            ".eot" => ContentType::ApplicationVndMsFontobject,
-            ".epub" => ContentType::ApplicationEpubZip,
-            ".gz" => ContentType::ApplicationGzip,
-            ".ico" => ContentType::ApplicationVndMicrosoftIcon,
-            ".jar" => ContentType::ApplicationJavaArchive,
-            ".midi" => ContentType::AudioMidi,
-            ".mjs" => ContentType::TextJavascriptDuplicate,
-            ".mp3" => ContentType::AudioMpeg,
-            ".mp4" => ContentType::VideoMp4,
-            ".mpeg" => ContentType::VideoMpeg,
-            ".mpkg" => ContentType::ApplicationVndAppleInstallerXml,
-            ".odp" => ContentType::ApplicationVndOasisOpendocumentPresentation,
-            ".ods" => ContentType::ApplicationVndOasisOpendocumentSpreadsheet,
-            ".odt" => ContentType::ApplicationVndOasisOpendocumentText,
-            ".oga" => ContentType::AudioOgg,
-            ".otf" => ContentType::FontOtf,
-            ".ogx" => ContentType::ApplicationOgg,
-            ".php" => ContentType::ApplicationXHttpdPhp,
-            ".pptx" => {
-                ContentType::ApplicationVndOpenxmlformatsOfficedocumentPresentationmlPresentation
+    /// Gets the content type of a file extension
+    /// <br>
+    /// Usable in constants, `embed_dir!` resolves the content types of embedded files with it at compile time.
+    /// # Arguments
+    /// * `extension` - The extension without the leading dot, compared case-insensitively : &str
+    /// # Returns
+    /// * `ContentType` - The content type, `application/octet-stream` for unknown extensions : ContentType
+    pub const fn from_extension(extension: &str) -> ContentType {
+        let mut index = 0;
+        while index < FILE_EXTENSIONS.len() {
+            if extension.eq_ignore_ascii_case(FILE_EXTENSIONS[index].0) {
+                return FILE_EXTENSIONS[index].1;
             }
-            ".rar" => ContentType::ApplicationVndRar,
-            ".rtf" => ContentType::ApplicationRtf,
-            ".sh" => ContentType::ApplicationXSh,
-            ".tar" => ContentType::ApplicationXTar,
-            ".tif" => ContentType::ImageTiffDuplicate,
-            ".ts" => ContentType::VideoMp2t,
-            ".ttf" => ContentType::FontTtf,
-            ".wav" => ContentType::AudioWav,
-            ".weba" => ContentType::AudioWebm,
-            ".webm" => ContentType::VideoWebm,
-            ".woff" => ContentType::FontWoff,
-            ".woff2" => ContentType::FontWoff2,
-            ".xhtml" => ContentType::ApplicationXhtmlXml,
-            ".xls" => ContentType::ApplicationVndMsExcel,
-            ".xlsx" => ContentType::ApplicationVndOpenxmlformatsOfficedocumentSpreadsheetmlSheet,
-            ".xul" => ContentType::ApplicationVndMozillaXulXml,
-            ".3gp" => ContentType::Video3gpp,
-            ".3g2" => ContentType::Video3gpp2,
-            ".7z" => ContentType::ApplicationX7zCompressed,
-            _ => ContentType::TextPlainCharsetUtf8,
+            index += 1;
         }
+        ContentType::ApplicationOctetStream
     }
 }
 
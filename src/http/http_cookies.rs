@@ -1,17 +1,48 @@
 use std::fmt::Display;
 
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[allow(unused)]
+/// Values of the `SameSite` cookie attribute
+/// # Example
+/// ``` rust
+/// use http::http_cookies::SameSite;
+/// let same_site = SameSite::Lax;
+/// assert_eq!(same_site.to_string(), "Lax");
+/// ```
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl Display for SameSite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SameSite::Strict => write!(f, "Strict"),
+            SameSite::Lax => write!(f, "Lax"),
+            SameSite::None => write!(f, "None"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Cookie {
     pub key: String,
     pub value: String,
+    pub path: Option<String>,
+    pub max_age: Option<i64>,
+    pub http_only: bool,
+    pub secure: bool,
+    pub same_site: Option<SameSite>,
 }
 #[allow(unused)]
 /// HTTP cookies
 ///
-/// Cookie is a tuple of key and value
+/// Cookie is a tuple of key and value with optional `Set-Cookie` attributes.
+/// The attributes are only used when the cookie is sent with a response.
 ///
 /// # Example
-/// ``` rust    
+/// ``` rust
 /// use http::http_cookies::Cookie;
 /// let cookie = Cookie::new("key".to_string(), "value".to_string());
 /// assert_eq!(cookie.key(), "key");
@@ -19,7 +50,15 @@ pub struct Cookie {
 /// ```
 impl Cookie {
     pub fn new(key: String, value: String) -> Cookie {
-        Cookie { key, value }
+        Cookie {
+            key,
+            value,
+            path: None,
+            max_age: None,
+            http_only: false,
+            secure: false,
+            same_site: None,
+        }
     }
 
     pub fn key(&self) -> &str {
@@ -29,6 +68,88 @@ impl Cookie {
     pub fn value(&self) -> &str {
         &self.value
     }
+
+    /// Sets the `Path` attribute
+    /// # Arguments
+    /// * `path` - The path the cookie is scoped to : &str
+    /// # Returns
+    /// * `Cookie` - The cookie
+    pub fn set_path(&mut self, path: &str) -> &mut Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    /// Sets the `Max-Age` attribute. A value of `0` tells the client to remove the cookie.
+    /// # Arguments
+    /// * `max_age` - The lifetime of the cookie in seconds : i64
+    /// # Returns
+    /// * `Cookie` - The cookie
+    pub fn set_max_age(&mut self, max_age: i64) -> &mut Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets the `HttpOnly` attribute
+    /// # Arguments
+    /// * `http_only` - Whether the cookie is hidden from scripts : bool
+    /// # Returns
+    /// * `Cookie` - The cookie
+    pub fn set_http_only(&mut self, http_only: bool) -> &mut Self {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Sets the `Secure` attribute
+    /// # Arguments
+    /// * `secure` - Whether the cookie is only sent over HTTPS : bool
+    /// # Returns
+    /// * `Cookie` - The cookie
+    pub fn set_secure(&mut self, secure: bool) -> &mut Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets the `SameSite` attribute
+    /// # Arguments
+    /// * `same_site` - The same site policy : SameSite
+    /// # Returns
+    /// * `Cookie` - The cookie
+    pub fn set_same_site(&mut self, same_site: SameSite) -> &mut Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// Formats the cookie as the value of a `Set-Cookie` header
+    /// # Returns
+    /// * `String` - The cookie with its attributes : String
+    /// # Example
+    /// ``` rust
+    /// let mut cookie = Cookie::new("sid".to_string(), "abc".to_string());
+    /// cookie.set_path("/").set_http_only(true);
+    /// assert_eq!(cookie.to_set_cookie(), "sid=abc; Path=/; HttpOnly");
+    /// ```
+    pub fn to_set_cookie(&self) -> String {
+        let mut result = self.to_string();
+        if let Some(path) = &self.path {
+            result.push_str("; Path=");
+            result.push_str(path);
+        }
+        if let Some(max_age) = self.max_age {
+            result.push_str("; Max-Age=");
+            result.push_str(max_age.to_string().as_str());
+        }
+        if let Some(same_site) = self.same_site {
+            result.push_str("; SameSite=");
+            result.push_str(same_site.to_string().as_str());
+        }
+        if self.secure {
+            result.push_str("; Secure");
+        }
+        if self.http_only {
+            result.push_str("; HttpOnly");
+        }
+        result
+    }
 }
 
 impl Display for Cookie {
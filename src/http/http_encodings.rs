@@ -0,0 +1,322 @@
+use std::{
+    fmt::Display,
+    io::{self, Read, Write},
+};
+
+use flate2::{
+    read::{DeflateDecoder, GzDecoder, ZlibDecoder},
+    write::{DeflateEncoder, GzEncoder},
+    Compression,
+};
+
+use crate::{
+    error::Error,
+    http::{
+        http_codes::StatusCode,
+        http_content_types::{get_type, ContentType},
+        http_headers::parse_quality_list,
+    },
+    Request,
+};
+
+/// Content codings supported by the server, in order of preference
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ContentEncoding {
+    Brotli,
+    Gzip,
+    Deflate,
+}
+
+#[allow(unused)]
+impl ContentEncoding {
+    /// All supported codings, in order of preference
+    pub const ALL: [ContentEncoding; 3] = [
+        ContentEncoding::Brotli,
+        ContentEncoding::Gzip,
+        ContentEncoding::Deflate,
+    ];
+
+    /// Returns the name of the coding used in `Accept-Encoding` and `Content-Encoding`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+        }
+    }
+
+    /// Returns the file extension of files precompressed with the coding, `None` for `deflate`
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            ContentEncoding::Brotli => Some("br"),
+            ContentEncoding::Gzip => Some("gz"),
+            ContentEncoding::Deflate => None,
+        }
+    }
+
+    /// Parses the name of a coding, case-insensitively
+    /// # Arguments
+    /// * `name` - The name : &str
+    /// # Returns
+    /// * `Option<ContentEncoding>` - The coding, `None` if it is not supported
+    pub fn from_name(name: &str) -> Option<ContentEncoding> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "br" => Some(ContentEncoding::Brotli),
+            "gzip" | "x-gzip" => Some(ContentEncoding::Gzip),
+            "deflate" => Some(ContentEncoding::Deflate),
+            _ => None,
+        }
+    }
+
+    /// Compresses data
+    /// # Arguments
+    /// * `data` - The data : &[u8]
+    /// # Returns
+    /// * `io::Result<Vec<u8>>` - The compressed data
+    pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            ContentEncoding::Brotli => {
+                let mut output = Vec::new();
+                {
+                    let mut encoder = brotli::CompressorWriter::new(&mut output, 4096, 5, 22);
+                    encoder.write_all(data)?;
+                }
+                Ok(output)
+            }
+            ContentEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            ContentEncoding::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    /// Decompresses data, stopping as soon as the decompressed data exceeds the limit
+    /// <br>
+    /// `deflate` data is accepted both with the zlib wrapper required by HTTP and as raw deflate, which some clients send.
+    /// # Arguments
+    /// * `data` - The compressed data : &[u8]
+    /// * `limit` - The maximum size of the decompressed data in bytes : usize
+    /// # Returns
+    /// * `Result<Vec<u8>, Error>` - The decompressed data, `413 Payload Too Large` if it exceeds the limit or
+    ///   `400 Bad Request` if the data is corrupted
+    pub fn decompress(&self, data: &[u8], limit: usize) -> Result<Vec<u8>, Error> {
+        let decoder: Box<dyn Read + '_> = match self {
+            ContentEncoding::Brotli => Box::new(brotli::Decompressor::new(data, 4096)),
+            ContentEncoding::Gzip => Box::new(GzDecoder::new(data)),
+            ContentEncoding::Deflate if is_zlib(data) => Box::new(ZlibDecoder::new(data)),
+            ContentEncoding::Deflate => Box::new(DeflateDecoder::new(data)),
+        };
+        let mut output = Vec::new();
+        decoder
+            .take(limit as u64 + 1)
+            .read_to_end(&mut output)
+            .map_err(|e| {
+                Error::new(StatusCode::BadRequest, "Invalid compressed request body").with_cause(e)
+            })?;
+        if output.len() > limit {
+            return Err(Error::new(
+                StatusCode::PayloadTooLarge,
+                "Decompressed request body is too large",
+            ));
+        }
+        Ok(output)
+    }
+}
+
+/// Returns `true` if the data starts with a zlib header
+fn is_zlib(data: &[u8]) -> bool {
+    match data {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+/// Decodes data compressed with the codings of a `Content-Encoding` header, applied in the listed order
+/// # Arguments
+/// * `value` - The header value, e.g. `gzip` : &str
+/// * `data` - The compressed data : Vec<u8>
+/// * `limit` - The maximum size of the decoded data in bytes : usize
+/// # Returns
+/// * `Result<Vec<u8>, Error>` - The decoded data, `415 Unsupported Media Type` for unsupported codings,
+///   `413 Payload Too Large` if the data exceeds the limit or `400 Bad Request` if it is corrupted
+pub fn decode_content(value: &str, data: Vec<u8>, limit: usize) -> Result<Vec<u8>, Error> {
+    let mut codings = Vec::new();
+    for name in value.split(',').map(|name| name.trim()) {
+        if name.is_empty() || name.eq_ignore_ascii_case("identity") {
+            continue;
+        }
+        match ContentEncoding::from_name(name) {
+            Some(encoding) => codings.push(encoding),
+            None => {
+                return Err(Error::new(
+                    StatusCode::UnsupportedMediaType,
+                    &format!("Unsupported content encoding: {}", name),
+                ))
+            }
+        }
+    }
+    codings
+        .iter()
+        .rev()
+        .try_fold(data, |data, encoding| encoding.decompress(&data, limit))
+}
+
+impl Display for ContentEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Chooses the coding of a response from the `Accept-Encoding` header of the request
+/// <br>
+/// The coding with the highest quality value is chosen, codings with equal quality in the order of `supported`.
+/// Codings not listed get the quality of `*`, if present. Codings with a quality of `0` are never chosen.
+/// # Arguments
+/// * `request` - The request : &Request
+/// * `supported` - The codings available for the response, in order of preference : &[ContentEncoding]
+/// # Returns
+/// * `Option<ContentEncoding>` - The coding, `None` to send the content as is
+pub fn negotiate_encoding(
+    request: &Request,
+    supported: &[ContentEncoding],
+) -> Option<ContentEncoding> {
+    let accept = request.get_header("Accept-Encoding")?;
+    let items = parse_quality_list(accept.value());
+    let quality = |name: &str| {
+        items
+            .iter()
+            .find(|(item, _)| item == name || (name == "gzip" && item == "x-gzip"))
+            .or_else(|| items.iter().find(|(item, _)| item == "*"))
+            .map(|(_, quality)| *quality)
+            .unwrap_or_default()
+    };
+    let mut best: Option<(ContentEncoding, f32)> = None;
+    for encoding in supported {
+        let q = quality(encoding.name());
+        if q > 0.0 && best.is_none_or(|(_, best)| q > best) {
+            best = Some((*encoding, q));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+/// Returns `true` if content of the type benefits from compression: text, JSON, JavaScript, CSS, XML and SVG
+/// # Arguments
+/// * `content_type` - The content type : ContentType
+pub fn is_compressible(content_type: ContentType) -> bool {
+    let media_type = get_type(content_type);
+    let essence = media_type.split(';').next().unwrap_or_default().trim();
+    essence.starts_with("text/")
+        || essence.ends_with("json")
+        || essence.ends_with("javascript")
+        || essence.ends_with("xml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::http_methods::HttpMethod;
+
+    fn accepting(value: &str) -> Request {
+        Request::test(HttpMethod::GET, "/", &[("Accept-Encoding", value)])
+    }
+
+    fn negotiate(value: &str) -> Option<ContentEncoding> {
+        negotiate_encoding(&accepting(value), &ContentEncoding::ALL)
+    }
+
+    #[test]
+    fn negotiates_by_quality_then_preference() {
+        use ContentEncoding::*;
+        assert_eq!(negotiate("gzip, deflate, br"), Some(Brotli));
+        assert_eq!(negotiate("deflate, gzip"), Some(Gzip));
+        assert_eq!(negotiate("br;q=0.5, gzip;q=0.8"), Some(Gzip));
+        assert_eq!(negotiate("x-gzip"), Some(Gzip));
+        assert_eq!(negotiate("*;q=0.5, br;q=0"), Some(Gzip));
+        assert_eq!(negotiate("*"), Some(Brotli));
+        assert_eq!(negotiate("identity"), None);
+        assert_eq!(negotiate("gzip;q=0, *;q=0"), None);
+        assert_eq!(negotiate_encoding(&accepting("br"), &[Gzip]), None);
+        assert_eq!(
+            negotiate_encoding(
+                &Request::test(HttpMethod::GET, "/", &[]),
+                &ContentEncoding::ALL
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn compresses_and_decompresses_every_coding() {
+        let data = "hello world ".repeat(100);
+        for encoding in ContentEncoding::ALL {
+            let compressed = encoding.compress(data.as_bytes()).unwrap();
+            assert!(compressed.len() < data.len(), "{}", encoding);
+            assert_eq!(
+                encoding.decompress(&compressed, 4096).unwrap(),
+                data.as_bytes()
+            );
+            assert_eq!(ContentEncoding::from_name(encoding.name()), Some(encoding));
+        }
+    }
+
+    #[test]
+    fn accepts_zlib_and_raw_deflate() {
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(b"wrapped").unwrap();
+        let zlib = zlib.finish().unwrap();
+        assert!(is_zlib(&zlib));
+        let raw = ContentEncoding::Deflate.compress(b"raw").unwrap();
+        assert_eq!(
+            ContentEncoding::Deflate.decompress(&zlib, 64).unwrap(),
+            b"wrapped"
+        );
+        assert_eq!(
+            ContentEncoding::Deflate.decompress(&raw, 64).unwrap(),
+            b"raw"
+        );
+    }
+
+    #[test]
+    fn decodes_content_encoding_chains() {
+        let gzip = ContentEncoding::Gzip.compress(b"body").unwrap();
+        let both = ContentEncoding::Brotli.compress(&gzip).unwrap();
+        assert_eq!(decode_content("gzip, br", both, 64).unwrap(), b"body");
+        assert_eq!(
+            decode_content("identity", b"plain".to_vec(), 64).unwrap(),
+            b"plain"
+        );
+
+        let status = |result: Result<Vec<u8>, Error>| match result {
+            Ok(_) => panic!("Expected an error"),
+            Err(error) => error.status(),
+        };
+        assert_eq!(
+            status(decode_content("compress", b"x".to_vec(), 64)),
+            StatusCode::UnsupportedMediaType
+        );
+        let large = ContentEncoding::Gzip.compress(&[0; 1000]).unwrap();
+        assert_eq!(
+            status(decode_content("gzip", large, 999)),
+            StatusCode::PayloadTooLarge
+        );
+        assert_eq!(
+            status(decode_content("gzip", b"not gzip".to_vec(), 64)),
+            StatusCode::BadRequest
+        );
+    }
+
+    #[test]
+    fn compresses_only_textual_content_types() {
+        assert!(is_compressible(ContentType::TextHtmlCharsetUtf8));
+        assert!(is_compressible(ContentType::ApplicationJson));
+        assert!(is_compressible(ContentType::ImageSvgXml));
+        assert!(!is_compressible(ContentType::ImagePng));
+    }
+}
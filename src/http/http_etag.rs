@@ -0,0 +1,17 @@
+//! Strong ETags computed from content
+//! <br>
+//! This file is also compiled into `embed_macro_def`, so ETags of embedded files computed at compile time match the
+//! ones computed at runtime. It must only depend on `std` and `sha2`.
+
+use sha2::{Digest, Sha256};
+
+/// Computes a strong ETag from the content of a response
+/// # Arguments
+/// * `data` - The content : &[u8]
+/// # Returns
+/// * `String` - The quoted ETag, the first 128 bits of the SHA-256 hash of the content : String
+pub fn content_etag(data: &[u8]) -> String {
+    let hash = Sha256::digest(data);
+    let hex: String = hash[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("\"{}\"", hex)
+}
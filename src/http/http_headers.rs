@@ -35,3 +35,37 @@ impl Display for Header {
         write!(f, "{}: {}", self.key, self.value)
     }
 }
+
+/// Parses a header value listing items with optional quality values, e.g. `Accept` or `Accept-Encoding`
+/// <br>
+/// Items are returned in descending order of quality, items with equal quality keep their order.
+/// Parameters other than `q` are dropped and items with a quality of `0` are kept, so they can be used to exclude values.
+/// # Arguments
+/// * `value` - The header value : &str
+/// # Returns
+/// * `Vec<(String, f32)>` - The lowercased items and their quality : Vec<(String, f32)>
+/// # Example
+/// ``` rust
+/// let items = parse_quality_list("text/html;q=0.8, application/json");
+/// assert_eq!(items[0].0, "application/json");
+/// ```
+pub fn parse_quality_list(value: &str) -> Vec<(String, f32)> {
+    let mut items: Vec<(String, f32)> = value
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let name = parts.next()?.trim().to_ascii_lowercase();
+            if name.is_empty() {
+                return None;
+            }
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .next()
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((name, quality))
+        })
+        .collect();
+    items.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    items
+}
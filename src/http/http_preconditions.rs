@@ -0,0 +1,301 @@
+use std::{
+    fs::Metadata,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    error::Error,
+    handler::IntoResponse,
+    http::{http_codes::StatusCode, http_headers::Header, http_methods::HttpMethod},
+    Request, Response,
+};
+
+pub use super::http_etag::content_etag;
+
+/// Formats a time as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+/// # Arguments
+/// * `time` - The time : SystemTime
+/// # Returns
+/// * `String` - The HTTP date : String
+pub fn format_http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Parses an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+/// # Arguments
+/// * `value` - The HTTP date : &str
+/// # Returns
+/// * `Option<SystemTime>` - The time, `None` if the date is invalid
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let date = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    let seconds = u64::try_from(date.timestamp()).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Truncates a time to whole seconds, the resolution of HTTP dates
+fn truncate_to_seconds(time: SystemTime) -> SystemTime {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+/// Computes the ETag of a file from its size and modification time
+/// # Arguments
+/// * `metadata` - The metadata of the file : &Metadata
+/// # Returns
+/// * `String` - The quoted ETag, e.g. `"18f3a2c1b00-1a2b"` : String
+pub fn file_etag(metadata: &Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", modified, metadata.len())
+}
+
+/// Returns `true` if the list of ETags of a header matches the ETag
+/// # Arguments
+/// * `list` - The header value, `*` or a comma separated list of ETags : &str
+/// * `etag` - The ETag of the current representation : &str
+/// * `weak` - Whether weak ETags are compared by their opaque tag only : bool
+fn etag_list_matches(list: &str, etag: &str, weak: bool) -> bool {
+    if list.trim() == "*" {
+        return true;
+    }
+    list.split(',').map(|tag| tag.trim()).any(|tag| {
+        if weak {
+            tag.trim_start_matches("W/") == etag.trim_start_matches("W/")
+        } else {
+            !tag.starts_with("W/") && !etag.starts_with("W/") && tag == etag
+        }
+    })
+}
+
+/// The outcome of the evaluation of the conditional headers of a request
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Precondition {
+    /// The request can be processed
+    Passed,
+    /// The client's copy is up to date, answer `304 Not Modified`
+    NotModified,
+    /// A precondition failed, answer `412 Precondition Failed`
+    Failed,
+}
+
+/// Evaluates `If-Match`, `If-Unmodified-Since`, `If-None-Match` and `If-Modified-Since` in the order of RFC 9110
+/// <br>
+/// `If-None-Match` and `If-Modified-Since` result in `NotModified` for `GET` and `HEAD` requests and in `Failed` for other methods.
+/// `If-Modified-Since` and `If-Unmodified-Since` are ignored if the corresponding ETag header is present.
+/// # Arguments
+/// * `request` - The request : &Request
+/// * `etag` - The ETag of the current representation, `None` if it has none : Option<&str>
+/// * `last_modified` - The modification time of the current representation : Option<SystemTime>
+/// # Returns
+/// * `Precondition` - The outcome : Precondition
+pub fn evaluate_preconditions(
+    request: &Request,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> Precondition {
+    let header = |key: &str| request.get_header(key).map(|h| h.value().to_string());
+    let safe = request.method == HttpMethod::GET || request.method == HttpMethod::HEAD;
+    let last_modified = last_modified.map(truncate_to_seconds);
+
+    if let Some(if_match) = header("If-Match") {
+        if !etag.is_some_and(|etag| etag_list_matches(&if_match, etag, false)) {
+            return Precondition::Failed;
+        }
+    } else if let Some(date) = header("If-Unmodified-Since").and_then(|v| parse_http_date(&v)) {
+        if last_modified.is_some_and(|modified| modified > date) {
+            return Precondition::Failed;
+        }
+    }
+
+    if let Some(if_none_match) = header("If-None-Match") {
+        if etag.is_some_and(|etag| etag_list_matches(&if_none_match, etag, true)) {
+            return match safe {
+                true => Precondition::NotModified,
+                false => Precondition::Failed,
+            };
+        }
+    } else if let Some(date) = header("If-Modified-Since").and_then(|v| parse_http_date(&v)) {
+        if safe && last_modified.is_some_and(|modified| modified <= date) {
+            return Precondition::NotModified;
+        }
+    }
+    Precondition::Passed
+}
+
+/// Checks the preconditions of a request before changing a resource, e.g. in a `PUT` or `DELETE` handler
+/// # Arguments
+/// * `request` - The request : &Request
+/// * `etag` - The ETag of the current representation, `None` if it has none : Option<&str>
+/// * `last_modified` - The modification time of the current representation : Option<SystemTime>
+/// # Returns
+/// * `Result<(), Error>` - `412 Precondition Failed` or, for `GET` and `HEAD`, `304 Not Modified` if the request must not be processed
+/// # Example
+/// ``` rust
+/// fn update_user(request: Request, Path(id): Path<u64>, State(db): State<Database>) -> Result<StatusCode, Error> {
+///     let user = db.find_user(id)?;
+///     check_preconditions(&request, Some(&content_etag(&user.to_json())), None)?;
+///     db.update_user(id, &request.data)?;
+///     Ok(StatusCode::NoContent)
+/// }
+/// ```
+pub fn check_preconditions(
+    request: &Request,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> Result<(), Error> {
+    match evaluate_preconditions(request, etag, last_modified) {
+        Precondition::Passed => Ok(()),
+        Precondition::NotModified => Err(Error::from_status(StatusCode::NotModified)),
+        Precondition::Failed => Err(Error::from_status(StatusCode::PreconditionFailed)),
+    }
+}
+
+/// Turns a response into a `304 Not Modified` response, keeping its headers and cookies
+/// # Arguments
+/// * `response` - The response : Response
+/// # Returns
+/// * `Response` - The response without body
+pub fn not_modified(mut response: Response) -> Response {
+    response
+        .set_status(StatusCode::NotModified)
+        .set_data(Vec::new());
+    response
+}
+
+/// Makes a successful response of a dynamic handler conditional
+/// <br>
+/// Adds a strong `ETag` computed from the body unless the response already has one, then evaluates the
+/// conditional headers of the request against the `ETag` and `Last-Modified` headers of the response.
+/// Responses other than `200 OK` and streamed bodies are returned unchanged.
+/// # Arguments
+/// * `request` - The request : &Request
+/// * `response` - The response of the handler : Response
+/// # Returns
+/// * `Response` - The response, `304 Not Modified` or `412 Precondition Failed`
+/// # Example
+/// ``` rust
+/// fn report(request: Request, State(db): State<Database>) -> Response {
+///     conditional(&request, Json(db.report()).into_response())
+/// }
+/// ```
+pub fn conditional(request: &Request, mut response: Response) -> Response {
+    if response.status != StatusCode::Ok || response.body.is_some() {
+        return response;
+    }
+    let etag = match response.get_header("ETag") {
+        Some(header) => header.value().to_string(),
+        None => {
+            let etag = content_etag(&response.data);
+            response.add_header(Header::new("ETag".to_string(), etag.clone()));
+            etag
+        }
+    };
+    let last_modified = response
+        .get_header("Last-Modified")
+        .and_then(|header| parse_http_date(header.value()));
+    match evaluate_preconditions(request, Some(&etag), last_modified) {
+        Precondition::Passed => response,
+        Precondition::NotModified => not_modified(response),
+        Precondition::Failed => Error::from_status(StatusCode::PreconditionFailed).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETAG: &str = "\"v2\"";
+
+    fn evaluate(method: HttpMethod, headers: &[(&str, &str)]) -> Precondition {
+        let modified = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        evaluate_preconditions(
+            &Request::test(method, "/", headers),
+            Some(ETAG),
+            Some(modified),
+        )
+    }
+
+    #[test]
+    fn formats_and_parses_http_dates() {
+        let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(format_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+
+    #[test]
+    fn answers_not_modified_for_matching_validators() {
+        use Precondition::*;
+        assert_eq!(evaluate(HttpMethod::GET, &[]), Passed);
+        assert_eq!(
+            evaluate(HttpMethod::GET, &[("If-None-Match", "\"v1\", W/\"v2\"")]),
+            NotModified
+        );
+        assert_eq!(
+            evaluate(HttpMethod::HEAD, &[("If-None-Match", "*")]),
+            NotModified
+        );
+        assert_eq!(
+            evaluate(HttpMethod::GET, &[("If-None-Match", "\"v1\"")]),
+            Passed
+        );
+        let date = ("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(evaluate(HttpMethod::GET, &[date]), NotModified);
+        let earlier = ("If-Modified-Since", "Sun, 06 Nov 1994 08:49:36 GMT");
+        assert_eq!(evaluate(HttpMethod::GET, &[earlier]), Passed);
+        // If-None-Match takes precedence over If-Modified-Since
+        assert_eq!(
+            evaluate(HttpMethod::GET, &[("If-None-Match", "\"v1\""), date]),
+            Passed
+        );
+    }
+
+    #[test]
+    fn fails_unsafe_requests_with_outdated_validators() {
+        use Precondition::*;
+        assert_eq!(evaluate(HttpMethod::PUT, &[("If-Match", ETAG)]), Passed);
+        assert_eq!(evaluate(HttpMethod::PUT, &[("If-Match", "\"v1\"")]), Failed);
+        // Weak ETags never match If-Match
+        assert_eq!(
+            evaluate(HttpMethod::PUT, &[("If-Match", "W/\"v2\"")]),
+            Failed
+        );
+        assert_eq!(evaluate(HttpMethod::PUT, &[("If-None-Match", "*")]), Failed);
+        let date = ("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:36 GMT");
+        assert_eq!(evaluate(HttpMethod::DELETE, &[date]), Failed);
+        let date = ("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(evaluate(HttpMethod::DELETE, &[date]), Passed);
+    }
+
+    #[test]
+    fn makes_dynamic_responses_conditional() {
+        let response = || "report".into_response();
+        let etag = content_etag(b"report");
+        let fresh = conditional(&Request::test(HttpMethod::GET, "/", &[]), response());
+        assert_eq!(fresh.get_header("ETag").unwrap().value(), etag);
+        assert_eq!(fresh.data, b"report");
+
+        let request = Request::test(HttpMethod::GET, "/", &[("If-None-Match", &etag)]);
+        let cached = conditional(&request, response());
+        assert_eq!(cached.status, StatusCode::NotModified);
+        assert!(cached.data.is_empty());
+        assert_eq!(cached.get_header("ETag").unwrap().value(), etag);
+
+        let request = Request::test(HttpMethod::PUT, "/", &[("If-Match", "\"old\"")]);
+        assert_eq!(
+            conditional(&request, response()).status,
+            StatusCode::PreconditionFailed
+        );
+    }
+}
@@ -0,0 +1,410 @@
+use std::{
+    fs::File,
+    io::{self, Cursor, Read},
+};
+
+use crate::{
+    error::Error,
+    handler::IntoResponse,
+    http::{
+        http_codes::StatusCode, http_content_types::get_type, http_headers::Header,
+        http_methods::HttpMethod, http_preconditions::parse_http_date,
+    },
+    transmitters::body::Body,
+    Request, Response,
+};
+
+/// Maximum number of ranges served in a single response, requests asking for more get the whole content
+const MAX_RANGES: usize = 16;
+
+/// A range of bytes, both ends inclusive
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Returns the number of bytes in the range
+    pub fn length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Formats the `Content-Range` value of the range, e.g. `bytes 0-499/1234`
+    /// # Arguments
+    /// * `total` - The length of the whole content : u64
+    pub fn content_range(&self, total: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total)
+    }
+}
+
+/// The outcome of parsing the `Range` header of a request
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RangeRequest {
+    /// No usable `Range` header, the whole content is sent
+    Full,
+    /// The satisfiable ranges, sorted and merged
+    Ranges(Vec<ByteRange>),
+    /// None of the ranges overlaps the content, answer `416 Range Not Satisfiable`
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header value, e.g. `bytes=0-499, 1000-, -500`
+/// <br>
+/// Malformed headers, units other than `bytes` and requests for more than 16 ranges are ignored, as allowed by RFC 9110.
+/// Overlapping and adjacent ranges are merged.
+/// # Arguments
+/// * `value` - The header value : &str
+/// * `total` - The length of the content : u64
+/// # Returns
+/// * `RangeRequest` - The ranges to send : RangeRequest
+pub fn parse_range(value: &str, total: u64) -> RangeRequest {
+    let specs = match value.trim().strip_prefix("bytes=") {
+        Some(specs) => specs,
+        None => return RangeRequest::Full,
+    };
+    let last = total.saturating_sub(1);
+    let mut ranges = Vec::new();
+    for spec in specs.split(',').map(|spec| spec.trim()) {
+        let (start, end) = match spec.split_once('-') {
+            Some(bounds) => bounds,
+            None => return RangeRequest::Full,
+        };
+        let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => Some((start, end.min(last))),
+            (Ok(start), Err(_)) if end.is_empty() => Some((start, last)),
+            (Err(_), Ok(suffix)) if start.is_empty() && suffix > 0 => {
+                Some((total.saturating_sub(suffix), last))
+            }
+            (Err(_), Ok(_)) if start.is_empty() => None,
+            _ => return RangeRequest::Full,
+        };
+        if let Some((start, end)) = range.filter(|(start, _)| *start < total) {
+            ranges.push(ByteRange { start, end });
+        }
+    }
+    if ranges.len() > MAX_RANGES {
+        return RangeRequest::Full;
+    }
+    if ranges.is_empty() {
+        return RangeRequest::Unsatisfiable;
+    }
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end + 1 => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    RangeRequest::Ranges(merged)
+}
+
+/// Returns `true` if the `If-Range` header of the request is missing or matches the current representation
+/// <br>
+/// An ETag must match strongly, a date must equal the modification time.
+/// # Arguments
+/// * `request` - The request : &Request
+/// * `etag` - The ETag of the current representation : Option<&str>
+/// * `last_modified` - The `Last-Modified` header value of the current representation : Option<&str>
+pub fn if_range_matches(
+    request: &Request,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> bool {
+    let value = match request.get_header("If-Range") {
+        Some(header) => header.value().trim().to_string(),
+        None => return true,
+    };
+    if value.starts_with('"') {
+        return etag.is_some_and(|etag| !etag.starts_with("W/") && etag == value);
+    }
+    let date = parse_http_date(&value);
+    date.is_some() && last_modified.and_then(parse_http_date) == date
+}
+
+/// Generates a random multipart boundary
+fn generate_boundary() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("Unable to generate multipart boundary");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Formats the header of a part of a `multipart/byteranges` body
+fn part_header(boundary: &str, content_type: &str, range: ByteRange, total: u64) -> Vec<u8> {
+    format!(
+        "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+        boundary,
+        content_type,
+        range.content_range(total)
+    )
+    .into_bytes()
+}
+
+/// Builds the `multipart/byteranges` body of a file body
+fn file_multipart(
+    file: &File,
+    offset: u64,
+    ranges: &[ByteRange],
+    boundary: &str,
+    content_type: &str,
+    total: u64,
+) -> io::Result<Body> {
+    let mut reader: Box<dyn Read + Send> = Box::new(io::empty());
+    let mut length = 0;
+    for range in ranges {
+        let header = part_header(boundary, content_type, *range, total);
+        length += header.len() as u64 + range.length();
+        let part = Body::file_range(file.try_clone()?, offset + range.start, range.length());
+        reader = Box::new(reader.chain(Cursor::new(header)).chain(part.into_reader()));
+    }
+    let trailer = format!("\r\n--{}--\r\n", boundary).into_bytes();
+    length += trailer.len() as u64;
+    Ok(Body::sized_reader(
+        reader.chain(Cursor::new(trailer)),
+        length,
+    ))
+}
+
+/// Answers the `Range` header of a request from a complete `200 OK` response
+/// <br>
+/// Adds `Accept-Ranges: bytes` and, if the request asks for ranges and its `If-Range` header matches the `ETag` or
+/// `Last-Modified` header of the response, responds with `206 Partial Content`. A single range is sent with a
+/// `Content-Range` header, several ranges as a `multipart/byteranges` body. Ranges outside of the content are answered
+/// with `416 Range Not Satisfiable`.
+/// <br>
+/// Works on the data of the response and on file bodies, which are never read into memory. Other streamed bodies can not be ranged.
+/// # Arguments
+/// * `request` - The request : &Request
+/// * `response` - The complete response : Response
+/// # Returns
+/// * `Response` - The response
+/// # Example
+/// ``` rust
+/// fn video(request: Request) -> Result<Response, Error> {
+///     let mut response = Response::get_empty();
+///     response
+///         .set_content_type(ContentType::VideoMp4)
+///         .set_body(Body::file(File::open("./media/intro.mp4")?)?);
+///     Ok(apply_ranges(&request, response))
+/// }
+/// ```
+pub fn apply_ranges(request: &Request, mut response: Response) -> Response {
+    if response.status != StatusCode::Ok {
+        return response;
+    }
+    let total = match &response.body {
+        None => response.data.len() as u64,
+        Some(Body::File { length, .. }) => *length,
+        Some(_) => return response,
+    };
+    response.add_header(Header::new(
+        "Accept-Ranges".to_string(),
+        "bytes".to_string(),
+    ));
+    if request.method != HttpMethod::GET && request.method != HttpMethod::HEAD {
+        return response;
+    }
+    let range = match request.get_header("Range") {
+        Some(header) => header.value().to_string(),
+        None => return response,
+    };
+    let etag = response.get_header("ETag").map(|h| h.value().to_string());
+    let last_modified = response
+        .get_header("Last-Modified")
+        .map(|h| h.value().to_string());
+    if !if_range_matches(request, etag.as_deref(), last_modified.as_deref()) {
+        return response;
+    }
+
+    let ranges = match parse_range(&range, total) {
+        RangeRequest::Full => return response,
+        RangeRequest::Unsatisfiable => {
+            let mut error = Error::from_status(StatusCode::RangeNotSatisfiable).into_response();
+            error.add_header(Header::new(
+                "Content-Range".to_string(),
+                format!("bytes */{}", total),
+            ));
+            return error;
+        }
+        RangeRequest::Ranges(ranges) => ranges,
+    };
+    response.set_status(StatusCode::PartialContent);
+
+    if let [range] = ranges[..] {
+        response.add_header(Header::new(
+            "Content-Range".to_string(),
+            range.content_range(total),
+        ));
+        match response.body.take() {
+            Some(Body::File { file, offset, .. }) => {
+                response.set_body(Body::file_range(file, offset + range.start, range.length()))
+            }
+            _ => {
+                let data = response.data[range.start as usize..=range.end as usize].to_vec();
+                response.set_data(data)
+            }
+        };
+        return response;
+    }
+
+    let boundary = generate_boundary();
+    let content_type = get_type(response.content_type);
+    response.add_header(Header::new(
+        "Content-Type".to_string(),
+        format!("multipart/byteranges; boundary={}", boundary),
+    ));
+    match response.body.take() {
+        Some(Body::File { file, offset, .. }) => {
+            match file_multipart(&file, offset, &ranges, &boundary, &content_type, total) {
+                Ok(body) => response.set_body(body),
+                Err(e) => return Error::internal(e).into_response(),
+            };
+        }
+        _ => {
+            let mut data = Vec::new();
+            for range in ranges {
+                data.extend(part_header(&boundary, &content_type, range, total));
+                data.extend_from_slice(&response.data[range.start as usize..=range.end as usize]);
+            }
+            data.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+            response.set_data(data);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write};
+
+    use super::*;
+    use crate::{http::http_content_types::ContentType, sessions::session::generate_id};
+
+    const DATA: &[u8] = b"0123456789abcdefghij";
+
+    fn range(start: u64, end: u64) -> ByteRange {
+        ByteRange { start, end }
+    }
+
+    fn plain() -> Response {
+        let mut response = Response::new(
+            StatusCode::Ok,
+            ContentType::TextPlainCharsetUtf8,
+            vec![Header::new("ETag".to_string(), "\"v1\"".to_string())],
+            Vec::new(),
+            DATA,
+        );
+        response.add_header(Header::new(
+            "Last-Modified".to_string(),
+            "Sun, 06 Nov 1994 08:49:37 GMT".to_string(),
+        ));
+        response
+    }
+
+    fn ranged(headers: &[(&str, &str)], response: Response) -> Response {
+        apply_ranges(&Request::test(HttpMethod::GET, "/", headers), response)
+    }
+
+    #[test]
+    fn parses_and_merges_ranges() {
+        use RangeRequest::*;
+        assert_eq!(parse_range("bytes=0-4", 20), Ranges(vec![range(0, 4)]));
+        assert_eq!(parse_range("bytes=15-", 20), Ranges(vec![range(15, 19)]));
+        assert_eq!(parse_range("bytes=-5", 20), Ranges(vec![range(15, 19)]));
+        assert_eq!(parse_range("bytes=-50", 20), Ranges(vec![range(0, 19)]));
+        assert_eq!(parse_range("bytes=10-99", 20), Ranges(vec![range(10, 19)]));
+        assert_eq!(
+            parse_range("bytes=8-9, 0-2, 3-4, 12-14", 20),
+            Ranges(vec![range(0, 4), range(8, 9), range(12, 14)])
+        );
+        assert_eq!(parse_range("bytes=20-30", 20), Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 20), Unsatisfiable);
+        assert_eq!(parse_range("bytes=5-2", 20), Full);
+        assert_eq!(parse_range("items=0-4", 20), Full);
+        assert_eq!(parse_range("bytes=a-b", 20), Full);
+        let many = (0..17)
+            .map(|i| format!("{0}-{0}", i * 2))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(parse_range(&format!("bytes={}", many), 40), Full);
+    }
+
+    #[test]
+    fn answers_single_range_with_partial_content() {
+        let response = ranged(&[("Range", "bytes=2-5")], plain());
+        assert_eq!(response.status, StatusCode::PartialContent);
+        assert_eq!(response.data, b"2345");
+        assert_eq!(
+            response.get_header("Content-Range").unwrap().value(),
+            "bytes 2-5/20"
+        );
+        assert_eq!(
+            response.get_header("Accept-Ranges").unwrap().value(),
+            "bytes"
+        );
+
+        let response = ranged(&[("Range", "bytes=30-")], plain());
+        assert_eq!(response.status, StatusCode::RangeNotSatisfiable);
+        assert_eq!(
+            response.get_header("Content-Range").unwrap().value(),
+            "bytes */20"
+        );
+    }
+
+    #[test]
+    fn ignores_ranges_when_if_range_does_not_match() {
+        for if_range in ["\"v1\"", "Sun, 06 Nov 1994 08:49:37 GMT"] {
+            let response = ranged(&[("Range", "bytes=0-1"), ("If-Range", if_range)], plain());
+            assert_eq!(response.status, StatusCode::PartialContent);
+        }
+        for if_range in ["\"v0\"", "W/\"v1\"", "Sun, 06 Nov 1994 08:49:38 GMT"] {
+            let response = ranged(&[("Range", "bytes=0-1"), ("If-Range", if_range)], plain());
+            assert_eq!(response.status, StatusCode::Ok);
+            assert_eq!(response.data, DATA);
+        }
+    }
+
+    #[test]
+    fn answers_several_ranges_with_multipart_body() {
+        let response = ranged(&[("Range", "bytes=0-1, 18-")], plain());
+        assert_eq!(response.status, StatusCode::PartialContent);
+        let content_type = response
+            .get_header("Content-Type")
+            .unwrap()
+            .value()
+            .to_string();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap();
+        let expected = format!(
+            "\r\n--{0}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 0-1/20\r\n\r\n01\
+             \r\n--{0}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 18-19/20\r\n\r\nij\
+             \r\n--{0}--\r\n",
+            boundary
+        );
+        assert_eq!(String::from_utf8_lossy(&response.data), expected);
+    }
+
+    #[test]
+    fn ranges_file_bodies_with_same_result_as_data() {
+        let path = std::env::temp_dir().join(format!("ranges-{}", generate_id()));
+        fs::File::create(&path).unwrap().write_all(DATA).unwrap();
+        let file_response = || {
+            let mut response = plain();
+            response.set_body(Body::file(fs::File::open(&path).unwrap()).unwrap());
+            response
+        };
+
+        let single = ranged(&[("Range", "bytes=-3")], file_response());
+        assert_eq!(single.body.unwrap().read_to_end().unwrap(), b"hij");
+        let multiple = ranged(&[("Range", "bytes=0-1, 18-")], file_response());
+        let length = multiple.body.as_ref().unwrap().length().unwrap();
+        let body = multiple.body.unwrap().read_to_end().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(body.len() as u64, length);
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains("bytes 0-1/20\r\n\r\n01\r\n"));
+        assert!(body.contains("bytes 18-19/20\r\n\r\nij\r\n"));
+    }
+}
@@ -1,5 +1,10 @@
+pub mod http_cache_control;
 pub mod http_codes;
 pub mod http_content_types;
 pub mod http_cookies;
+pub mod http_encodings;
+pub mod http_etag;
 pub mod http_headers;
 pub mod http_methods;
+pub mod http_preconditions;
+pub mod http_ranges;
@@ -1,24 +1,35 @@
+pub mod error;
+pub mod extract;
+pub mod guard;
+pub mod handler;
 pub mod http;
+pub mod middleware;
 pub mod route;
 mod server;
+pub mod sessions;
+pub mod static_files;
 pub mod thread_pool;
 pub mod transmitters;
 pub mod utils;
 
+use error::Error;
 use http::http_codes::get_status_line;
 use http::http_codes::StatusCode;
 use http::http_content_types::*;
 use http::http_methods::*;
 
+use middleware::{cors::Cors, Middleware, Next};
 use route::Route;
 
 use route_macro_def::add_routes;
-use server::PreRequest;
-use server::PreRequestHandler;
-use server::PreResponse;
-use server::PreResponseHandler;
+use server::Server;
+use sessions::{
+    session_manager::{SessionConfig, SessionManager},
+    session_store::MemorySessionStore,
+};
+use static_files::StaticFiles;
 
-use std::{fs, thread};
+use std::{fs, thread, time::Duration};
 use transmitters::request::*;
 use transmitters::response::*;
 use utils::logger::*;
@@ -32,39 +43,14 @@ const LOGGER: Logger = Logger {
     level: LOG_LEVEL,
 };
 
-fn appliances(data: Request) -> Response {
-    LOGGER.debug(&["Responding to ", data.path.as_str()]);
-    let resource_type = data.path.as_str();
-    let data = match fs::read_to_string("./static/".to_owned() + resource_type) {
-        Ok(data) => data,
-        Err(e) => {
-            LOGGER.error(e.to_string().as_str(), &["Error reading file"]);
-            return Response::new(
-                StatusCode::NotFound,
-                ContentType::TextHtmlCharsetUtf8,
-                Vec::new(),
-                Vec::new(),
-                "".to_string(),
-            );
-        }
-    };
-    Response::new(
-        StatusCode::Ok,
-        ContentType::parse_file_name(resource_type),
-        Vec::new(),
-        Vec::new(),
-        data,
-    )
-}
-
-fn index(_: Request) -> Response {
-    Response::new(
+fn index(_: Request) -> Result<Response, Error> {
+    Ok(Response::new(
         StatusCode::Ok,
         ContentType::TextHtmlCharsetUtf8,
         Vec::new(),
         Vec::new(),
-        fs::read_to_string("./static/index.html").unwrap(),
-    )
+        fs::read_to_string("./static/index.html")?,
+    ))
 }
 
 fn sleep(_: Request) -> Response {
@@ -88,19 +74,15 @@ fn options(_: Request) -> Response {
     )
 }
 
-/// Custom pre-response handler
-impl PreRequest for PreRequestHandler {
-    fn call(&self, _: String, request: Request) -> Request {
-        LOGGER.info(&["Pre-request called for ", request.path.as_str()]);
-        request
-    }
-}
+/// Custom logging middleware
+struct RequestLogger;
 
-/// Custom pre-response handler
-impl PreResponse for PreResponseHandler {
-    fn call(&self, _: String, response: Response) -> Response {
+impl Middleware for RequestLogger {
+    fn handle(&self, request: Request, next: Next) -> Response {
+        LOGGER.info(&["Request received for ", request.path.as_str()]);
+        let response = next.run(request);
         LOGGER.info(&[
-            "Pre-response called for ",
+            "Responding with ",
             get_status_line(response.status).as_str(),
         ]);
         response
@@ -108,19 +90,21 @@ impl PreResponse for PreResponseHandler {
 }
 
 fn main() {
-    server::start(
-        add_routes!(
+    Server::new("127.0.0.1", 8000)
+        .threads(10)
+        .log_level(LOG_LEVEL)
+        .with(RequestLogger)
+        .with(Cors::new().any_origin())
+        .with(SessionManager::new(
+            SessionConfig::default(),
+            MemorySessionStore::new(Duration::from_secs(30 * 60)),
+        ))
+        .routes(add_routes!(
             Route::new("/", index, HttpMethod::GET),
-            Route::new("", appliances, HttpMethod::GET),
             Route::new("/sleep", sleep, HttpMethod::OPTIONS),
             Route::new("/sleep", sleep, HttpMethod::GET),
             Route::new("/options", options, HttpMethod::OPTIONS)
-        ),
-        "127.0.0.1",
-        8000,
-        10,
-        LOG_LEVEL,
-        Some(PreRequestHandler),
-        Some(PreResponseHandler),
-    );
+        ))
+        .mount("/", StaticFiles::new("./static"))
+        .start();
 }
@@ -0,0 +1,342 @@
+use std::{collections::HashMap, fs, io};
+
+use argon2::{password_hash::PasswordHash, Argon2, PasswordVerifier};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{
+    error::Error,
+    handler::IntoResponse,
+    http::{http_codes::StatusCode, http_headers::Header},
+    middleware::{Middleware, Next},
+    server::get_log_level,
+    utils::logger::Logger,
+    Request, Response,
+};
+
+/// The user authenticated by `BasicAuth`
+/// <br>
+/// Inserted into `Request::extensions`, handlers get it with `Extension<Principal>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+}
+
+/// Gets the credentials of the `Authorization` header for an authentication scheme
+/// # Arguments
+/// * `request` - The request : &Request
+/// * `scheme` - The scheme, e.g. `Bearer`, compared case-insensitively : &str
+/// # Returns
+/// * `Option<String>` - The credentials after the scheme, `None` if the header is missing or uses another scheme
+pub fn authorization(request: &Request, scheme: &str) -> Option<String> {
+    let header = request.get_header("Authorization")?;
+    let (name, credentials) = header.value().trim().split_once(' ')?;
+    match name.eq_ignore_ascii_case(scheme) {
+        true => Some(credentials.trim().to_string()),
+        false => None,
+    }
+}
+
+/// Builds the `401 Unauthorized` or `400 Bad Request` response with a `WWW-Authenticate` challenge
+pub(super) fn challenge(
+    status: StatusCode,
+    scheme: &str,
+    realm: &str,
+    error: Option<&str>,
+) -> Response {
+    let mut value = format!("{} realm=\"{}\"", scheme, realm.replace('"', "\\\""));
+    if scheme == "Basic" {
+        value.push_str(", charset=\"UTF-8\"");
+    }
+    if let Some(error) = error {
+        value.push_str(&format!(", error=\"{}\"", error));
+    }
+    let mut response = Error::from_status(status).into_response();
+    response.add_header(Header::new("WWW-Authenticate".to_string(), value));
+    response
+}
+
+/// Users and password hashes of an htpasswd-style file
+/// <br>
+/// Every line holds `user:hash`, empty lines and lines starting with `#` are ignored.
+/// Supported are bcrypt (`$2y$`, `$2b$`, `$2a$`, as created by `htpasswd -B`) and argon2 (`$argon2id$`, `$argon2i$`, `$argon2d$`) hashes.
+/// <br>
+/// Unknown users are checked against the first hash of the file, so they take as long to reject as wrong passwords
+/// and user names can not be guessed from the response time.
+///
+/// # Example
+/// ``` rust
+/// let users = Htpasswd::load("./users.htpasswd")?;
+/// assert!(users.verify("admin", "secret"));
+/// ```
+pub struct Htpasswd {
+    users: HashMap<String, String>,
+    dummy: Option<String>,
+}
+
+#[allow(unused)]
+impl Htpasswd {
+    /// Loads an htpasswd file
+    /// # Arguments
+    /// * `path` - The path of the file : &str
+    /// # Returns
+    /// * `io::Result<Htpasswd>` - The users, or the error reading the file. Malformed lines and unsupported
+    ///   hash formats are rejected with `InvalidData`
+    pub fn load(path: &str) -> io::Result<Htpasswd> {
+        Htpasswd::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses the content of an htpasswd file
+    /// # Arguments
+    /// * `content` - The content : &str
+    /// # Returns
+    /// * `io::Result<Htpasswd>` - The users, `InvalidData` for malformed lines and unsupported hash formats
+    pub fn parse(content: &str) -> io::Result<Htpasswd> {
+        let mut users = HashMap::new();
+        let mut dummy = None;
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Line {} of htpasswd file: {}", number + 1, message),
+                )
+            };
+            let (user, hash) = line.split_once(':').ok_or_else(|| invalid("missing `:`"))?;
+            if !is_bcrypt(hash) && !hash.starts_with("$argon2") {
+                return Err(invalid("unsupported hash, use bcrypt or argon2"));
+            }
+            dummy.get_or_insert_with(|| hash.to_string());
+            users.insert(user.to_string(), hash.to_string());
+        }
+        Ok(Htpasswd { users, dummy })
+    }
+
+    /// Verifies the password of a user
+    /// # Arguments
+    /// * `user` - The user name : &str
+    /// * `password` - The password : &str
+    /// # Returns
+    /// * `bool` - `true` if the user exists and the password matches its hash
+    pub fn verify(&self, user: &str, password: &str) -> bool {
+        match self.users.get(user) {
+            Some(hash) => verify_hash(password, hash),
+            None => {
+                // Spends the time of a real check, the result is ignored
+                if let Some(dummy) = &self.dummy {
+                    verify_hash(password, dummy);
+                }
+                false
+            }
+        }
+    }
+}
+
+/// Verifies a password against a bcrypt or argon2 hash
+fn verify_hash(password: &str, hash: &str) -> bool {
+    if is_bcrypt(hash) {
+        return bcrypt::verify(password, hash).unwrap_or(false);
+    }
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+fn is_bcrypt(hash: &str) -> bool {
+    ["$2y$", "$2b$", "$2a$"]
+        .iter()
+        .any(|prefix| hash.starts_with(prefix))
+}
+
+type Verifier = dyn Fn(&str, &str) -> bool + Send + Sync;
+type Validator<P> = dyn Fn(&str) -> Option<P> + Send + Sync;
+
+/// HTTP Basic authentication
+/// <br>
+/// Requests without valid credentials get `401 Unauthorized` with a `WWW-Authenticate: Basic` challenge, so browsers
+/// ask for a user name and password. The authenticated user is inserted into the request extensions as `Principal`.
+/// <br>
+/// Basic credentials are sent in clear text, only use it over HTTPS.
+///
+/// # Arguments
+/// * `realm` - The protection space shown to the user : &str
+/// * `verifier` - Checks a user name and password : impl Fn(&str, &str) -> bool
+///
+/// # Example
+/// ``` rust
+/// Server::new("127.0.0.1", 8000)
+///     .routes(add_routes!(
+///         Route::new("/admin", admin, HttpMethod::GET).with(BasicAuth::htpasswd("Admin", "./users.htpasswd")?),
+///         Route::new("/metrics", metrics, HttpMethod::GET)
+///             .with(BasicAuth::new("Metrics", |user, password| user == "prometheus" && password == METRICS_PASSWORD))
+///     ))
+///     .start();
+///
+/// fn admin(Extension(principal): Extension<Principal>) -> String {
+///     format!("Hello {}", principal.name)
+/// }
+/// ```
+pub struct BasicAuth {
+    realm: String,
+    verifier: Box<Verifier>,
+}
+
+#[allow(unused)]
+impl BasicAuth {
+    pub fn new(
+        realm: &str,
+        verifier: impl Fn(&str, &str) -> bool + Send + Sync + 'static,
+    ) -> BasicAuth {
+        BasicAuth {
+            realm: realm.to_string(),
+            verifier: Box::new(verifier),
+        }
+    }
+
+    /// Creates Basic authentication checking the users of an htpasswd file
+    /// # Arguments
+    /// * `realm` - The protection space shown to the user : &str
+    /// * `path` - The path of the htpasswd file : &str
+    /// # Returns
+    /// * `io::Result<BasicAuth>` - The middleware, or the error loading the file
+    pub fn htpasswd(realm: &str, path: &str) -> io::Result<BasicAuth> {
+        let users = Htpasswd::load(path)?;
+        Ok(BasicAuth::new(realm, move |user, password| {
+            users.verify(user, password)
+        }))
+    }
+
+    /// Decodes the user name and password of the `Authorization` header
+    fn credentials(request: &Request) -> Option<(String, String)> {
+        let encoded = authorization(request, "Basic")?;
+        let decoded = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+        let (user, password) = decoded.split_once(':')?;
+        Some((user.to_string(), password.to_string()))
+    }
+}
+
+impl Middleware for BasicAuth {
+    fn handle(&self, mut request: Request, next: Next) -> Response {
+        let logger: Logger = Logger {
+            c_name: "AUTH",
+            level: get_log_level(),
+        };
+        match BasicAuth::credentials(&request) {
+            Some((user, password)) if (self.verifier)(&user, &password) => {
+                request.extensions.insert(Principal { name: user });
+                next.run(request)
+            }
+            Some(_) => {
+                // The user name is not logged, clients often send a password by mistake
+                logger.info(&["Rejected credentials for", request.path.as_str()]);
+                challenge(StatusCode::Unauthorized, "Basic", &self.realm, None)
+            }
+            None => challenge(StatusCode::Unauthorized, "Basic", &self.realm, None),
+        }
+    }
+}
+
+/// HTTP Bearer token authentication
+/// <br>
+/// The token of the `Authorization: Bearer` header is passed to the validator, which returns the principal the token
+/// belongs to. The principal is inserted into the request extensions, handlers get it with `Extension<P>`.
+/// Requests without a token get `401 Unauthorized`, requests with an invalid token `401 Unauthorized` with
+/// `error="invalid_token"` and malformed headers `400 Bad Request` with `error="invalid_request"`.
+///
+/// # Arguments
+/// * `realm` - The protection space : &str
+/// * `validator` - Returns the principal of a valid token : impl Fn(&str) -> Option<P>
+///
+/// # Example
+/// ``` rust
+/// #[derive(Clone)]
+/// struct ApiClient { id: u64 }
+///
+/// Server::new("127.0.0.1", 8000)
+///     .with(BearerAuth::new("API", |token| API_KEYS.get(token).map(|id| ApiClient { id: *id })))
+///     .start();
+///
+/// fn usage(Extension(client): Extension<ApiClient>) -> String {
+///     format!("Client {}", client.id)
+/// }
+/// ```
+pub struct BearerAuth<P> {
+    realm: String,
+    validator: Box<Validator<P>>,
+}
+
+#[allow(unused)]
+impl<P: Clone + Send + Sync + 'static> BearerAuth<P> {
+    pub fn new(
+        realm: &str,
+        validator: impl Fn(&str) -> Option<P> + Send + Sync + 'static,
+    ) -> BearerAuth<P> {
+        BearerAuth {
+            realm: realm.to_string(),
+            validator: Box::new(validator),
+        }
+    }
+}
+
+/// Returns `true` if a token only contains the characters allowed by RFC 6750
+pub(super) fn is_token68(token: &str) -> bool {
+    let trimmed = token.trim_end_matches('=');
+    !trimmed.is_empty()
+        && trimmed
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-._~+/".contains(c))
+}
+
+impl<P: Clone + Send + Sync + 'static> Middleware for BearerAuth<P> {
+    fn handle(&self, mut request: Request, next: Next) -> Response {
+        let logger: Logger = Logger {
+            c_name: "AUTH",
+            level: get_log_level(),
+        };
+        let token = match authorization(&request, "Bearer") {
+            Some(token) if is_token68(&token) => token,
+            Some(_) => {
+                return challenge(
+                    StatusCode::BadRequest,
+                    "Bearer",
+                    &self.realm,
+                    Some("invalid_request"),
+                )
+            }
+            None => return challenge(StatusCode::Unauthorized, "Bearer", &self.realm, None),
+        };
+        match (self.validator)(&token) {
+            Some(principal) => {
+                request.extensions.insert(principal);
+                next.run(request)
+            }
+            None => {
+                logger.info(&["Rejected bearer token for", request.path.as_str()]);
+                challenge(
+                    StatusCode::Unauthorized,
+                    "Bearer",
+                    &self.realm,
+                    Some("invalid_token"),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_unknown_user_with_password_of_dummy_hash() {
+        let hash = bcrypt::hash("secret", 4).unwrap();
+        let users = Htpasswd::parse(&format!("admin:{}", hash)).unwrap();
+        assert!(users.verify("admin", "secret"));
+        assert!(!users.verify("guest", "secret"));
+        assert!(!users.verify("admin", "wrong"));
+    }
+}
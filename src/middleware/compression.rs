@@ -0,0 +1,209 @@
+use crate::{
+    http::{
+        http_codes::StatusCode,
+        http_encodings::{is_compressible, negotiate_encoding, ContentEncoding},
+        http_headers::Header,
+    },
+    middleware::{Middleware, Next},
+    server::get_log_level,
+    utils::logger::Logger,
+    Request, Response,
+};
+
+/// Compresses responses with the best coding accepted by the client
+/// <br>
+/// Only `200 OK` responses of a compressible content type (text, JSON, JavaScript, CSS, XML and SVG) from `min_size`
+/// bytes on are compressed. Responses which already have a `Content-Encoding` and streamed bodies are sent as is.
+/// Compressible responses get `Vary: Accept-Encoding`, the `ETag` of compressed responses is made weak since the
+/// bytes differ from the uncompressed representation.
+///
+/// # Example
+/// ``` rust
+/// Server::new("127.0.0.1", 8000)
+///     .with(Compression::new().min_size(512).encodings(&[ContentEncoding::Gzip]))
+///     .start();
+/// ```
+pub struct Compression {
+    encodings: Vec<ContentEncoding>,
+    min_size: usize,
+}
+
+#[allow(unused)]
+impl Compression {
+    pub fn new() -> Compression {
+        Compression {
+            encodings: ContentEncoding::ALL.to_vec(),
+            min_size: 1024,
+        }
+    }
+
+    /// Sets the codings to use, in order of preference. Defaults to `br`, `gzip` and `deflate`
+    /// # Arguments
+    /// * `encodings` - The codings : &[ContentEncoding]
+    /// # Returns
+    /// * `Compression` - The middleware
+    pub fn encodings(mut self, encodings: &[ContentEncoding]) -> Compression {
+        self.encodings = encodings.to_vec();
+        self
+    }
+
+    /// Sets the minimum size of compressed responses. Defaults to 1 KiB
+    /// # Arguments
+    /// * `bytes` - The size in bytes : usize
+    /// # Returns
+    /// * `Compression` - The middleware
+    pub fn min_size(mut self, bytes: usize) -> Compression {
+        self.min_size = bytes;
+        self
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::new()
+    }
+}
+
+impl Middleware for Compression {
+    fn handle(&self, request: Request, next: Next) -> Response {
+        let logger: Logger = Logger {
+            c_name: "COMPRESSION",
+            level: get_log_level(),
+        };
+        let encoding = negotiate_encoding(&request, &self.encodings);
+        let mut response = next.run(request);
+
+        if response.status != StatusCode::Ok
+            || response.body.is_some()
+            || response.get_header("Content-Encoding").is_some()
+            || response.get_header("Content-Type").is_some()
+            || !is_compressible(response.content_type)
+        {
+            return response;
+        }
+        response.add_vary("Accept-Encoding");
+        let encoding = match encoding {
+            Some(encoding) if response.data.len() >= self.min_size => encoding,
+            _ => return response,
+        };
+        let data = match encoding.compress(&response.data) {
+            Ok(data) => data,
+            Err(e) => {
+                logger.error(e.to_string().as_str(), &["Unable to compress response"]);
+                return response;
+            }
+        };
+        if let Some(etag) = response
+            .headers
+            .iter_mut()
+            .find(|header| header.key.eq_ignore_ascii_case("ETag"))
+        {
+            if !etag.value.starts_with("W/") {
+                etag.value = format!("W/{}", etag.value);
+            }
+        }
+        response
+            .add_header(Header::new(
+                "Content-Encoding".to_string(),
+                encoding.name().to_string(),
+            ))
+            .set_data(data);
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::http::{http_content_types::ContentType, http_methods::HttpMethod};
+
+    fn compress(
+        compression: Compression,
+        accept: &str,
+        endpoint: &dyn Fn(Request) -> Response,
+    ) -> Response {
+        let middlewares: [Arc<dyn Middleware>; 1] = [Arc::new(compression)];
+        let request = Request::test(HttpMethod::GET, "/", &[("Accept-Encoding", accept)]);
+        Next::new(&middlewares, endpoint).run(request)
+    }
+
+    fn text(_: Request) -> Response {
+        let mut response = Response::get_empty();
+        response
+            .set_content_type(ContentType::TextPlainCharsetUtf8)
+            .set_data("a".repeat(2048))
+            .add_header(Header::new("ETag".to_string(), "\"v1\"".to_string()));
+        response
+    }
+
+    #[test]
+    fn compresses_with_negotiated_coding() {
+        let response = compress(Compression::new(), "gzip;q=0.5, br", &text);
+        assert_eq!(
+            response.get_header("Content-Encoding").unwrap().value(),
+            "br"
+        );
+        assert_eq!(
+            response.get_header("Vary").unwrap().value(),
+            "Accept-Encoding"
+        );
+        assert_eq!(response.get_header("ETag").unwrap().value(), "W/\"v1\"");
+        let data = ContentEncoding::Brotli
+            .decompress(&response.data, 4096)
+            .unwrap();
+        assert_eq!(data, "a".repeat(2048).as_bytes());
+
+        let gzip_only = Compression::new().encodings(&[ContentEncoding::Gzip]);
+        let response = compress(gzip_only, "br, gzip", &text);
+        assert_eq!(
+            response.get_header("Content-Encoding").unwrap().value(),
+            "gzip"
+        );
+    }
+
+    #[test]
+    fn varies_but_skips_small_or_unaccepted_responses() {
+        for (compression, accept) in [
+            (Compression::new().min_size(4096), "gzip"),
+            (Compression::new(), "identity"),
+        ] {
+            let response = compress(compression, accept, &text);
+            assert!(response.get_header("Content-Encoding").is_none());
+            assert_eq!(
+                response.get_header("Vary").unwrap().value(),
+                "Accept-Encoding"
+            );
+            assert_eq!(response.get_header("ETag").unwrap().value(), "\"v1\"");
+            assert_eq!(response.data.len(), 2048);
+        }
+    }
+
+    #[test]
+    fn leaves_binary_encoded_and_error_responses_alone() {
+        let png = |request| {
+            let mut response = text(request);
+            response.set_content_type(ContentType::ImagePng);
+            response
+        };
+        let encoded = |request| {
+            let mut response = text(request);
+            response.add_header(Header::new(
+                "Content-Encoding".to_string(),
+                "br".to_string(),
+            ));
+            response
+        };
+        let missing = |request| {
+            let mut response = text(request);
+            response.set_status(StatusCode::NotFound);
+            response
+        };
+        for endpoint in [&png as &dyn Fn(Request) -> Response, &encoded, &missing] {
+            let response = compress(Compression::new(), "gzip", endpoint);
+            assert!(response.get_header("Vary").is_none());
+            assert_eq!(response.data.len(), 2048);
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    http::{http_codes::StatusCode, http_headers::Header, http_methods::HttpMethod},
+    middleware::{Middleware, Next},
+    server::get_log_level,
+    utils::logger::Logger,
+    Request, Response,
+};
+
+/// The origins allowed to access the server
+#[derive(Clone)]
+pub enum AllowedOrigins {
+    /// Every origin
+    Any,
+    /// The listed origins, e.g. `https://example.com`, compared case-insensitively
+    List(Vec<String>),
+    /// The origins accepted by the predicate
+    Predicate(Arc<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+impl AllowedOrigins {
+    /// Returns `true` if the origin is allowed
+    pub fn allows(&self, origin: &str) -> bool {
+        match self {
+            AllowedOrigins::Any => true,
+            AllowedOrigins::List(origins) => origins
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(origin)),
+            AllowedOrigins::Predicate(predicate) => predicate(origin),
+        }
+    }
+}
+
+/// Cross-Origin Resource Sharing
+/// <br>
+/// Requests with an `Origin` header from an allowed origin get the `Access-Control-*` headers, other requests are passed
+/// on unchanged. Preflight requests (`OPTIONS` with `Access-Control-Request-Method`) are answered by the middleware with
+/// `204 No Content`, or `403 Forbidden` for origins which are not allowed.
+/// <br>
+/// Without credentials, any origin is answered with `Access-Control-Allow-Origin: *`. With credentials the origin of the
+/// request is echoed, since browsers reject the wildcard for requests with cookies. Responses depending on the origin get
+/// `Vary: Origin`.
+/// <br>
+/// Credentials require the allowed origins to be listed or matched by a predicate. Combining `any_origin` with
+/// `allow_credentials(true)` would let every website read the responses of its visitors, the builder panics on it.
+/// <br>
+/// By default no origin is allowed, the methods `GET`, `HEAD`, `POST`, `PUT`, `PATCH` and `DELETE` are allowed and the
+/// headers requested by a preflight request are allowed as well.
+///
+/// # Example
+/// ``` rust
+/// Server::new("127.0.0.1", 8000)
+///     .with(
+///         Cors::new()
+///             .allow_origin("https://app.example.com")
+///             .allow_origin_fn(|origin| origin.ends_with(".example.com"))
+///             .allow_credentials(true)
+///             .allow_headers(&["Content-Type", "Authorization"])
+///             .expose_headers(&["ETag"])
+///             .max_age(Duration::from_secs(600)),
+///     )
+///     .start();
+/// ```
+pub struct Cors {
+    origins: AllowedOrigins,
+    credentials: bool,
+    methods: Vec<HttpMethod>,
+    allowed_headers: Option<Vec<String>>,
+    exposed_headers: Vec<String>,
+    max_age: Option<Duration>,
+}
+
+#[allow(unused)]
+impl Cors {
+    pub fn new() -> Cors {
+        Cors {
+            origins: AllowedOrigins::List(Vec::new()),
+            credentials: false,
+            methods: vec![
+                HttpMethod::GET,
+                HttpMethod::HEAD,
+                HttpMethod::POST,
+                HttpMethod::PUT,
+                HttpMethod::PATCH,
+                HttpMethod::DELETE,
+            ],
+            allowed_headers: None,
+            exposed_headers: Vec::new(),
+            max_age: None,
+        }
+    }
+
+    /// Allows every origin
+    /// # Returns
+    /// * `Cors` - The middleware
+    /// # Panics
+    /// If credentials are allowed
+    pub fn any_origin(mut self) -> Cors {
+        if self.credentials {
+            panic!("CORS credentials can not be allowed for any origin, list the origins instead");
+        }
+        self.origins = AllowedOrigins::Any;
+        self
+    }
+
+    /// Allows an origin, e.g. `https://example.com`, in addition to the listed ones
+    /// # Arguments
+    /// * `origin` - The origin : &str
+    /// # Returns
+    /// * `Cors` - The middleware
+    pub fn allow_origin(mut self, origin: &str) -> Cors {
+        let origin = origin.trim_end_matches('/').to_string();
+        self.origins = match self.origins {
+            AllowedOrigins::List(mut origins) => {
+                origins.push(origin);
+                AllowedOrigins::List(origins)
+            }
+            AllowedOrigins::Any => AllowedOrigins::Any,
+            AllowedOrigins::Predicate(predicate) => {
+                AllowedOrigins::Predicate(Arc::new(move |candidate: &str| {
+                    candidate.eq_ignore_ascii_case(&origin) || predicate(candidate)
+                }))
+            }
+        };
+        self
+    }
+
+    /// Allows the origins accepted by a predicate, in addition to the ones already allowed
+    /// # Arguments
+    /// * `predicate` - The predicate receiving the origin : impl Fn(&str) -> bool
+    /// # Returns
+    /// * `Cors` - The middleware
+    pub fn allow_origin_fn(
+        mut self,
+        predicate: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Cors {
+        if matches!(self.origins, AllowedOrigins::Any) {
+            return self;
+        }
+        let previous = self.origins;
+        self.origins = AllowedOrigins::Predicate(Arc::new(move |origin: &str| {
+            previous.allows(origin) || predicate(origin)
+        }));
+        self
+    }
+
+    /// Sets whether cookies and `Authorization` headers may be sent with cross-origin requests. Defaults to `false`
+    /// <br>
+    /// Only allowed together with listed origins or a predicate.
+    /// # Arguments
+    /// * `allowed` - Whether credentials are allowed : bool
+    /// # Returns
+    /// * `Cors` - The middleware
+    /// # Panics
+    /// If every origin is allowed with `any_origin`
+    pub fn allow_credentials(mut self, allowed: bool) -> Cors {
+        if allowed && matches!(self.origins, AllowedOrigins::Any) {
+            panic!("CORS credentials can not be allowed for any origin, list the origins instead");
+        }
+        self.credentials = allowed;
+        self
+    }
+
+    /// Sets the methods allowed for cross-origin requests
+    /// # Arguments
+    /// * `methods` - The methods : &[HttpMethod]
+    /// # Returns
+    /// * `Cors` - The middleware
+    pub fn allow_methods(mut self, methods: &[HttpMethod]) -> Cors {
+        self.methods = methods.to_vec();
+        self
+    }
+
+    /// Sets the request headers allowed for cross-origin requests. By default the headers requested by the preflight request are allowed
+    /// # Arguments
+    /// * `headers` - The header names : &[&str]
+    /// # Returns
+    /// * `Cors` - The middleware
+    pub fn allow_headers(mut self, headers: &[&str]) -> Cors {
+        self.allowed_headers = Some(headers.iter().map(|header| header.to_string()).collect());
+        self
+    }
+
+    /// Sets the response headers scripts of other origins may read, besides the CORS-safelisted ones
+    /// # Arguments
+    /// * `headers` - The header names : &[&str]
+    /// # Returns
+    /// * `Cors` - The middleware
+    pub fn expose_headers(mut self, headers: &[&str]) -> Cors {
+        self.exposed_headers = headers.iter().map(|header| header.to_string()).collect();
+        self
+    }
+
+    /// Sets how long browsers may cache the result of a preflight request
+    /// # Arguments
+    /// * `max_age` - The duration : Duration
+    /// # Returns
+    /// * `Cors` - The middleware
+    pub fn max_age(mut self, max_age: Duration) -> Cors {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Adds `Access-Control-Allow-Origin` and `Access-Control-Allow-Credentials` for an allowed origin
+    fn add_origin_headers(&self, response: &mut Response, origin: &str) {
+        let value = match self.origins {
+            AllowedOrigins::Any => "*".to_string(),
+            _ => origin.to_string(),
+        };
+        response.add_header(Header::new(
+            "Access-Control-Allow-Origin".to_string(),
+            value,
+        ));
+        if self.credentials {
+            response.add_header(Header::new(
+                "Access-Control-Allow-Credentials".to_string(),
+                "true".to_string(),
+            ));
+        }
+    }
+
+    /// Answers a preflight request
+    fn preflight(&self, request: &Request, origin: &str) -> Response {
+        let logger: Logger = Logger {
+            c_name: "CORS",
+            level: get_log_level(),
+        };
+        let mut response = Response::get_empty();
+        self.add_vary(&mut response);
+        response
+            .add_vary("Access-Control-Request-Method")
+            .add_vary("Access-Control-Request-Headers");
+        if !self.origins.allows(origin) {
+            logger.debug(&["Rejected preflight request from", origin]);
+            response.set_status(StatusCode::Forbidden);
+            return response;
+        }
+        response.set_status(StatusCode::NoContent);
+        self.add_origin_headers(&mut response, origin);
+        let methods: Vec<String> = self
+            .methods
+            .iter()
+            .map(|method| method.to_string())
+            .collect();
+        response.add_header(Header::new(
+            "Access-Control-Allow-Methods".to_string(),
+            methods.join(", "),
+        ));
+        let headers = match &self.allowed_headers {
+            Some(headers) => headers.join(", "),
+            None => request
+                .get_header("Access-Control-Request-Headers")
+                .map(|header| header.value().to_string())
+                .unwrap_or_default(),
+        };
+        if !headers.is_empty() {
+            response.add_header(Header::new(
+                "Access-Control-Allow-Headers".to_string(),
+                headers,
+            ));
+        }
+        if let Some(max_age) = self.max_age {
+            response.add_header(Header::new(
+                "Access-Control-Max-Age".to_string(),
+                max_age.as_secs().to_string(),
+            ));
+        }
+        response
+    }
+
+    /// Adds `Vary: Origin` unless every origin gets the same answer
+    fn add_vary(&self, response: &mut Response) {
+        if !matches!(self.origins, AllowedOrigins::Any) || self.credentials {
+            response.add_vary("Origin");
+        }
+    }
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Cors::new()
+    }
+}
+
+impl Middleware for Cors {
+    fn handle(&self, request: Request, next: Next) -> Response {
+        let origin = match request.get_header("Origin") {
+            Some(header) => header.value().to_string(),
+            None => {
+                let mut response = next.run(request);
+                self.add_vary(&mut response);
+                return response;
+            }
+        };
+        if request.method == HttpMethod::OPTIONS
+            && request
+                .get_header("Access-Control-Request-Method")
+                .is_some()
+        {
+            return self.preflight(&request, &origin);
+        }
+        let mut response = next.run(request);
+        self.add_vary(&mut response);
+        if self.origins.allows(&origin) {
+            self.add_origin_headers(&mut response, &origin);
+            if !self.exposed_headers.is_empty() {
+                response.add_header(Header::new(
+                    "Access-Control-Expose-Headers".to_string(),
+                    self.exposed_headers.join(", "),
+                ));
+            }
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "CORS credentials")]
+    fn rejects_credentials_after_any_origin() {
+        let _ = Cors::new().any_origin().allow_credentials(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "CORS credentials")]
+    fn rejects_any_origin_after_credentials() {
+        let _ = Cors::new().allow_credentials(true).any_origin();
+    }
+
+    #[test]
+    fn allows_credentials_for_listed_origins() {
+        let cors = Cors::new()
+            .allow_origin("https://app.example.com")
+            .allow_credentials(true);
+        assert!(cors.origins.allows("https://app.example.com"));
+        assert!(!cors.origins.allows("https://evil.example"));
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+
+use crate::{
+    error::Error,
+    handler::IntoResponse,
+    http::{
+        http_codes::StatusCode,
+        http_cookies::{Cookie, SameSite},
+        http_methods::HttpMethod,
+    },
+    middleware::{Middleware, Next},
+    server::get_log_level,
+    sessions::session::generate_id,
+    utils::logger::Logger,
+    Request, Response,
+};
+
+/// Where the expected CSRF token is kept
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum CsrfMode {
+    /// The token is kept in a cookie readable by scripts, requests must repeat it in a header or form field
+    DoubleSubmitCookie,
+    /// The token is kept in the session, requires the `SessionManager` middleware to run before
+    SynchronizerToken,
+}
+
+/// The CSRF token of a request, to embed in forms or pages
+/// <br>
+/// Inserted into `Request::extensions` by `Csrf` for every request.
+///
+/// # Example
+/// ``` rust
+/// fn form(Extension(CsrfToken(token)): Extension<CsrfToken>) -> Response {
+///     Response::new(
+///         StatusCode::Ok,
+///         ContentType::TextHtmlCharsetUtf8,
+///         Vec::new(),
+///         Vec::new(),
+///         format!("<form method=\"post\"><input type=\"hidden\" name=\"csrf_token\" value=\"{}\"></form>", token),
+///     )
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsrfToken(pub String);
+
+/// Protects against cross-site request forgery
+/// <br>
+/// `POST`, `PUT`, `PATCH` and `DELETE` requests must send the token of the client in the `X-CSRF-Token` header or in
+/// the `csrf_token` field of a URL-encoded form. As an extra layer, the `Origin` header, or the `Referer` header if
+/// there is no `Origin`, must name the host of the request or a trusted origin. Requests failing a check get
+/// `403 Forbidden`. Routes can be exempted, e.g. webhooks authenticated by other means.
+/// <br>
+/// By default the double-submit cookie `csrf_token` holds the token. With `CsrfMode::SynchronizerToken` the token is
+/// stored in the session instead, which must be loaded by `SessionManager` before this middleware runs.
+///
+/// # Example
+/// ``` rust
+/// Server::new("127.0.0.1", 8000)
+///     .with(SessionManager::new(SessionConfig::default(), MemorySessionStore::new(Duration::from_secs(30 * 60))))
+///     .with(
+///         Csrf::new()
+///             .mode(CsrfMode::SynchronizerToken)
+///             .trusted_origin("https://admin.example.com")
+///             .exempt("/webhooks/*"),
+///     )
+///     .start();
+/// ```
+pub struct Csrf {
+    mode: CsrfMode,
+    cookie_name: String,
+    secure_cookie: bool,
+    header_name: String,
+    field_name: String,
+    session_key: String,
+    check_origin: bool,
+    trusted_origins: Vec<String>,
+    exempt: Vec<String>,
+}
+
+#[allow(unused)]
+impl Csrf {
+    pub fn new() -> Csrf {
+        Csrf {
+            mode: CsrfMode::DoubleSubmitCookie,
+            cookie_name: "csrf_token".to_string(),
+            secure_cookie: false,
+            header_name: "X-CSRF-Token".to_string(),
+            field_name: "csrf_token".to_string(),
+            session_key: "csrf_token".to_string(),
+            check_origin: true,
+            trusted_origins: Vec::new(),
+            exempt: Vec::new(),
+        }
+    }
+
+    /// Sets where the expected token is kept. Defaults to `CsrfMode::DoubleSubmitCookie`
+    /// # Arguments
+    /// * `mode` - The mode : CsrfMode
+    /// # Returns
+    /// * `Csrf` - The middleware
+    pub fn mode(mut self, mode: CsrfMode) -> Csrf {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the name of the double-submit cookie and whether it is only sent over HTTPS. Defaults to `csrf_token`, not secure
+    /// # Arguments
+    /// * `name` - The cookie name : &str
+    /// * `secure` - Whether the cookie is only sent over HTTPS : bool
+    /// # Returns
+    /// * `Csrf` - The middleware
+    pub fn cookie(mut self, name: &str, secure: bool) -> Csrf {
+        self.cookie_name = name.to_string();
+        self.secure_cookie = secure;
+        self
+    }
+
+    /// Sets the request header carrying the token. Defaults to `X-CSRF-Token`
+    /// # Arguments
+    /// * `name` - The header name : &str
+    /// # Returns
+    /// * `Csrf` - The middleware
+    pub fn header_name(mut self, name: &str) -> Csrf {
+        self.header_name = name.to_string();
+        self
+    }
+
+    /// Sets the form field carrying the token. Defaults to `csrf_token`
+    /// # Arguments
+    /// * `name` - The field name : &str
+    /// # Returns
+    /// * `Csrf` - The middleware
+    pub fn field_name(mut self, name: &str) -> Csrf {
+        self.field_name = name.to_string();
+        self
+    }
+
+    /// Sets the session key holding the token with `CsrfMode::SynchronizerToken`. Defaults to `csrf_token`
+    /// # Arguments
+    /// * `key` - The session key : &str
+    /// # Returns
+    /// * `Csrf` - The middleware
+    pub fn session_key(mut self, key: &str) -> Csrf {
+        self.session_key = key.to_string();
+        self
+    }
+
+    /// Sets whether the `Origin` and `Referer` headers are checked. Defaults to `true`
+    /// # Arguments
+    /// * `enabled` - Whether to check the origin : bool
+    /// # Returns
+    /// * `Csrf` - The middleware
+    pub fn check_origin(mut self, enabled: bool) -> Csrf {
+        self.check_origin = enabled;
+        self
+    }
+
+    /// Trusts an origin besides the host of the request, e.g. `https://admin.example.com`
+    /// # Arguments
+    /// * `origin` - The origin : &str
+    /// # Returns
+    /// * `Csrf` - The middleware
+    pub fn trusted_origin(mut self, origin: &str) -> Csrf {
+        self.trusted_origins
+            .push(origin.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Exempts request paths from the checks, either an exact path or a prefix ending with `*`, e.g. `/webhooks/*`
+    /// # Arguments
+    /// * `path` - The path : &str
+    /// # Returns
+    /// * `Csrf` - The middleware
+    pub fn exempt(mut self, path: &str) -> Csrf {
+        self.exempt.push(path.to_string());
+        self
+    }
+
+    fn is_exempt(&self, path: &str) -> bool {
+        self.exempt
+            .iter()
+            .any(|exempt| match exempt.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == exempt,
+            })
+    }
+
+    /// Gets the expected token of the client, `None` if the client has none yet
+    fn expected_token(&self, request: &Request) -> Option<String> {
+        match self.mode {
+            CsrfMode::DoubleSubmitCookie => request
+                .get_cookie(&self.cookie_name)
+                .map(|cookie| cookie.value().to_string())
+                .filter(|token| is_valid_token(token)),
+            CsrfMode::SynchronizerToken => {
+                request.session.as_ref()?.get::<String>(&self.session_key)
+            }
+        }
+    }
+
+    /// Gets the token sent with the request, from the header or the form field
+    fn submitted_token(&self, request: &Request) -> Option<String> {
+        if let Some(header) = request.get_header(&self.header_name) {
+            return Some(header.value().trim().to_string());
+        }
+        let is_form = request.get_header("Content-Type").is_some_and(|header| {
+            header
+                .value()
+                .trim_start()
+                .starts_with("application/x-www-form-urlencoded")
+        });
+        if !is_form {
+            return None;
+        }
+        let mut fields: HashMap<String, String> =
+            serde_urlencoded::from_str(&request.data).unwrap_or_default();
+        fields.remove(&self.field_name)
+    }
+
+    /// Checks the `Origin` header, or the `Referer` header if there is no `Origin`
+    /// # Returns
+    /// * `Result<(), &str>` - The reason of the rejection
+    fn verify_origin(&self, request: &Request) -> Result<(), &'static str> {
+        let origin = match (request.get_header("Origin"), request.get_header("Referer")) {
+            (Some(origin), _) => origin.value().trim().to_string(),
+            (None, Some(referer)) => match origin_of(referer.value().trim()) {
+                Some(origin) => origin,
+                None => return Err("Invalid Referer"),
+            },
+            (None, None) => return Ok(()),
+        };
+        let host = request
+            .get_header("Host")
+            .map(|header| header.value().trim().to_string())
+            .unwrap_or_default();
+        let same_host = origin.split_once("://").is_some_and(|(_, authority)| {
+            !host.is_empty() && authority.eq_ignore_ascii_case(&host)
+        });
+        let trusted = self
+            .trusted_origins
+            .iter()
+            .any(|trusted| trusted.eq_ignore_ascii_case(&origin));
+        match same_host || trusted {
+            true => Ok(()),
+            false => Err("Cross-origin request"),
+        }
+    }
+
+    /// Checks an unsafe request
+    /// # Returns
+    /// * `Result<(), &str>` - The reason of the rejection
+    fn verify(&self, request: &Request, expected: Option<&str>) -> Result<(), &'static str> {
+        if self.check_origin {
+            self.verify_origin(request)?;
+        }
+        let expected = expected.ok_or("Missing CSRF token")?;
+        let submitted = self.submitted_token(request).ok_or("Missing CSRF token")?;
+        match constant_time_eq(expected.as_bytes(), submitted.as_bytes()) {
+            true => Ok(()),
+            false => Err("Invalid CSRF token"),
+        }
+    }
+
+    fn cookie_for(&self, token: &str) -> Cookie {
+        let mut cookie = Cookie::new(self.cookie_name.clone(), token.to_string());
+        cookie
+            .set_path("/")
+            .set_secure(self.secure_cookie)
+            .set_same_site(SameSite::Strict);
+        cookie
+    }
+}
+
+impl Default for Csrf {
+    fn default() -> Self {
+        Csrf::new()
+    }
+}
+
+/// Returns `true` if a token has the format of generated tokens, so cookies set by other means are replaced
+fn is_valid_token(token: &str) -> bool {
+    token.len() == 64 && token.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Returns the origin (`scheme://host:port`) of a URL
+fn origin_of(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    match authority.is_empty() {
+        true => None,
+        false => Some(format!("{}://{}", scheme, authority)),
+    }
+}
+
+/// Compares two byte strings in a time independent of where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+impl Middleware for Csrf {
+    fn handle(&self, mut request: Request, next: Next) -> Response {
+        let logger: Logger = Logger {
+            c_name: "CSRF",
+            level: get_log_level(),
+        };
+        if self.mode == CsrfMode::SynchronizerToken && request.session.is_none() {
+            logger.error(
+                "Missing session",
+                &["The synchronizer token mode requires the SessionManager middleware"],
+            );
+            return Error::from_status(StatusCode::InternalServerError).into_response();
+        }
+        let expected = self.expected_token(&request);
+
+        let unsafe_method = matches!(
+            request.method,
+            HttpMethod::POST | HttpMethod::PUT | HttpMethod::PATCH | HttpMethod::DELETE
+        );
+        if unsafe_method && !self.is_exempt(&request.path) {
+            if let Err(reason) = self.verify(&request, expected.as_deref()) {
+                logger.info(&["Rejected request to", request.path.as_str(), reason]);
+                return Error::new(StatusCode::Forbidden, reason).into_response();
+            }
+        }
+
+        let token = expected.clone().unwrap_or_else(generate_id);
+        if expected.is_none() && self.mode == CsrfMode::SynchronizerToken {
+            if let Some(session) = &request.session {
+                session.insert(&self.session_key, token.clone());
+            }
+        }
+        request.extensions.insert(CsrfToken(token.clone()));
+        let mut response = next.run(request);
+        if expected.is_none() && self.mode == CsrfMode::DoubleSubmitCookie {
+            response.add_cookie(self.cookie_for(&token));
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::http_content_types::ContentType;
+
+    const TOKEN: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn post(headers: &[(&str, &str)]) -> Request {
+        let cookie = format!("csrf_token={}", TOKEN);
+        let mut headers = headers.to_vec();
+        headers.push(("Cookie", cookie.as_str()));
+        Request::test(HttpMethod::POST, "/transfer", &headers)
+    }
+
+    fn handle(request: Request) -> Response {
+        let endpoint = |_: Request| {
+            Response::new(
+                StatusCode::Ok,
+                ContentType::TextPlainCharsetUtf8,
+                Vec::new(),
+                Vec::new(),
+                "ok",
+            )
+        };
+        Csrf::new().handle(request, Next::new(&[], &endpoint))
+    }
+
+    #[test]
+    fn rejects_cross_origin_post() {
+        let request = post(&[
+            ("Host", "bank.example"),
+            ("Origin", "https://evil.example"),
+            ("X-CSRF-Token", TOKEN),
+        ]);
+        assert_eq!(handle(request).status, StatusCode::Forbidden);
+    }
+
+    #[test]
+    fn rejects_mismatching_token() {
+        let request = post(&[
+            ("Host", "bank.example"),
+            ("Origin", "https://bank.example"),
+            ("X-CSRF-Token", "forged"),
+        ]);
+        assert_eq!(handle(request).status, StatusCode::Forbidden);
+    }
+
+    #[test]
+    fn accepts_matching_token() {
+        let request = post(&[
+            ("Host", "bank.example"),
+            ("Origin", "https://bank.example"),
+            ("X-CSRF-Token", TOKEN),
+        ]);
+        assert_eq!(handle(request).status, StatusCode::Ok);
+    }
+}
@@ -0,0 +1,595 @@
+use std::{
+    fmt, fs, io,
+    marker::PhantomData,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use jsonwebtoken::{
+    errors::ErrorKind,
+    jwk::{AlgorithmParameters, EllipticCurve, JwkSet, KeyAlgorithm},
+    Algorithm, DecodingKey, EncodingKey, Validation,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    http::http_codes::StatusCode,
+    middleware::{
+        auth::{authorization, challenge, is_token68},
+        Middleware, Next,
+    },
+    server::get_log_level,
+    utils::logger::Logger,
+    Request, Response,
+};
+
+/// The supported signature algorithms
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum JwtAlgorithm {
+    /// HMAC with SHA-256, a secret shared by issuer and verifier
+    HS256,
+    /// RSASSA-PKCS1-v1_5 with SHA-256
+    RS256,
+    /// ECDSA on the P-256 curve with SHA-256
+    ES256,
+}
+
+impl JwtAlgorithm {
+    fn algorithm(self) -> Algorithm {
+        match self {
+            JwtAlgorithm::HS256 => Algorithm::HS256,
+            JwtAlgorithm::RS256 => Algorithm::RS256,
+            JwtAlgorithm::ES256 => Algorithm::ES256,
+        }
+    }
+}
+
+/// Why a token was rejected or could not be issued
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum JwtError {
+    /// The token is not a well-formed JWT
+    Malformed,
+    /// No key matches the `kid` and `alg` of the token
+    UnknownKey,
+    InvalidSignature,
+    /// The `exp` claim is in the past
+    Expired,
+    /// The `nbf` claim is in the future
+    NotYetValid,
+    InvalidIssuer,
+    InvalidAudience,
+    /// The claims are missing a required claim or do not match the claims type
+    InvalidClaims(String),
+    /// The key can not be used for signing
+    InvalidKey(String),
+}
+
+impl fmt::Display for JwtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JwtError::Malformed => write!(f, "Malformed token"),
+            JwtError::UnknownKey => write!(f, "Unknown key"),
+            JwtError::InvalidSignature => write!(f, "Invalid signature"),
+            JwtError::Expired => write!(f, "Token expired"),
+            JwtError::NotYetValid => write!(f, "Token not yet valid"),
+            JwtError::InvalidIssuer => write!(f, "Invalid issuer"),
+            JwtError::InvalidAudience => write!(f, "Invalid audience"),
+            JwtError::InvalidClaims(reason) => write!(f, "Invalid claims: {}", reason),
+            JwtError::InvalidKey(reason) => write!(f, "Invalid key: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for JwtError {}
+
+impl From<jsonwebtoken::errors::Error> for JwtError {
+    fn from(error: jsonwebtoken::errors::Error) -> Self {
+        match error.kind() {
+            ErrorKind::InvalidSignature => JwtError::InvalidSignature,
+            ErrorKind::ExpiredSignature => JwtError::Expired,
+            ErrorKind::ImmatureSignature => JwtError::NotYetValid,
+            ErrorKind::InvalidIssuer => JwtError::InvalidIssuer,
+            ErrorKind::InvalidAudience => JwtError::InvalidAudience,
+            ErrorKind::InvalidAlgorithm => JwtError::UnknownKey,
+            ErrorKind::MissingRequiredClaim(claim) => {
+                JwtError::InvalidClaims(format!("missing `{}`", claim))
+            }
+            ErrorKind::Json(error) => JwtError::InvalidClaims(error.to_string()),
+            ErrorKind::InvalidEcdsaKey
+            | ErrorKind::InvalidRsaKey(_)
+            | ErrorKind::RsaFailedSigning
+            | ErrorKind::InvalidKeyFormat => JwtError::InvalidKey(error.to_string()),
+            _ => JwtError::Malformed,
+        }
+    }
+}
+
+/// Reads a key file, mapping key parsing errors to `InvalidData`
+fn load_key<K>(
+    path: &str,
+    parse: impl FnOnce(&[u8]) -> jsonwebtoken::errors::Result<K>,
+) -> io::Result<K> {
+    let pem = fs::read(path)?;
+    parse(&pem).map_err(|error| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid key in {}: {}", path, error),
+        )
+    })
+}
+
+struct VerificationKey {
+    kid: Option<String>,
+    algorithm: JwtAlgorithm,
+    key: DecodingKey,
+}
+
+/// The keys tokens are verified with
+/// <br>
+/// A token naming a key id (`kid`) is only verified with the keys of that id, other tokens with every key of their
+/// algorithm. A key is never used with another algorithm than its own, so an RSA public key can not be abused as an
+/// HMAC secret.
+///
+/// # Example
+/// ``` rust
+/// let keys = JwtKeys::new()
+///     .rs256_pem(Some("2024-01"), "./keys/auth-service.pub.pem")?
+///     .jwks("./keys/partners.jwks.json")?
+///     .hs256(None, INTERNAL_SECRET);
+/// ```
+#[derive(Default)]
+pub struct JwtKeys {
+    keys: Vec<VerificationKey>,
+}
+
+#[allow(unused)]
+impl JwtKeys {
+    pub fn new() -> JwtKeys {
+        JwtKeys { keys: Vec::new() }
+    }
+
+    /// Adds an HS256 secret
+    /// # Arguments
+    /// * `kid` - The key id, `None` for tokens without a `kid` : Option<&str>
+    /// * `secret` - The shared secret, at least 32 random bytes : &[u8]
+    /// # Returns
+    /// * `JwtKeys` - The keys
+    pub fn hs256(self, kid: Option<&str>, secret: &[u8]) -> JwtKeys {
+        self.key(kid, JwtAlgorithm::HS256, DecodingKey::from_secret(secret))
+    }
+
+    /// Adds an RS256 public key from a PEM file (`BEGIN PUBLIC KEY` or `BEGIN RSA PUBLIC KEY`)
+    /// # Arguments
+    /// * `kid` - The key id, `None` for tokens without a `kid` : Option<&str>
+    /// * `path` - The path of the PEM file : &str
+    /// # Returns
+    /// * `io::Result<JwtKeys>` - The keys, or the error reading the file. Invalid keys are rejected with `InvalidData`
+    pub fn rs256_pem(self, kid: Option<&str>, path: &str) -> io::Result<JwtKeys> {
+        let key = load_key(path, DecodingKey::from_rsa_pem)?;
+        Ok(self.key(kid, JwtAlgorithm::RS256, key))
+    }
+
+    /// Adds an ES256 public key from a PEM file (`BEGIN PUBLIC KEY`)
+    /// # Arguments
+    /// * `kid` - The key id, `None` for tokens without a `kid` : Option<&str>
+    /// * `path` - The path of the PEM file : &str
+    /// # Returns
+    /// * `io::Result<JwtKeys>` - The keys, or the error reading the file. Invalid keys are rejected with `InvalidData`
+    pub fn es256_pem(self, kid: Option<&str>, path: &str) -> io::Result<JwtKeys> {
+        let key = load_key(path, DecodingKey::from_ec_pem)?;
+        Ok(self.key(kid, JwtAlgorithm::ES256, key))
+    }
+
+    /// Adds the keys of a JWKS file (`{"keys": [...]}`)
+    /// <br>
+    /// RSA keys are used for RS256, P-256 keys for ES256 and symmetric keys for HS256. Keys announcing another
+    /// algorithm in `alg` are skipped.
+    /// # Arguments
+    /// * `path` - The path of the JSON file : &str
+    /// # Returns
+    /// * `io::Result<JwtKeys>` - The keys, or the error reading the file. Malformed files, invalid keys and files
+    ///   without any supported key are rejected with `InvalidData`
+    pub fn jwks(mut self, path: &str) -> io::Result<JwtKeys> {
+        let invalid = |message: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid JWKS file {}: {}", path, message),
+            )
+        };
+        let set: JwkSet = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|error| invalid(error.to_string()))?;
+        let mut added = 0;
+        for jwk in &set.keys {
+            let algorithm = match &jwk.algorithm {
+                AlgorithmParameters::RSA(_) => JwtAlgorithm::RS256,
+                AlgorithmParameters::EllipticCurve(params)
+                    if params.curve == EllipticCurve::P256 =>
+                {
+                    JwtAlgorithm::ES256
+                }
+                AlgorithmParameters::OctetKey(_) => JwtAlgorithm::HS256,
+                _ => continue,
+            };
+            let announced = match jwk.common.key_algorithm {
+                Some(KeyAlgorithm::RS256) => Some(JwtAlgorithm::RS256),
+                Some(KeyAlgorithm::ES256) => Some(JwtAlgorithm::ES256),
+                Some(KeyAlgorithm::HS256) => Some(JwtAlgorithm::HS256),
+                Some(_) => continue,
+                None => None,
+            };
+            if announced.is_some_and(|announced| announced != algorithm) {
+                continue;
+            }
+            let key = DecodingKey::from_jwk(jwk).map_err(|error| invalid(error.to_string()))?;
+            self = self.key(jwk.common.key_id.as_deref(), algorithm, key);
+            added += 1;
+        }
+        match added {
+            0 => Err(invalid("no RS256, ES256 or HS256 key".to_string())),
+            _ => Ok(self),
+        }
+    }
+
+    fn key(mut self, kid: Option<&str>, algorithm: JwtAlgorithm, key: DecodingKey) -> JwtKeys {
+        self.keys.push(VerificationKey {
+            kid: kid.map(|kid| kid.to_string()),
+            algorithm,
+            key,
+        });
+        self
+    }
+}
+
+/// JWT bearer authentication
+/// <br>
+/// The token of the `Authorization: Bearer` header must be signed by one of the keys, must not be expired (`exp` is
+/// required), must be valid already (`nbf`) and must name the configured issuer and audience, if any. Time claims
+/// are checked with a clock skew of 60 seconds by default.
+/// <br>
+/// The claims are deserialized into `C` and inserted into the request extensions, handlers get them with
+/// `Extension<C>`. Requests without a token get `401 Unauthorized`, requests with an invalid token `401 Unauthorized`
+/// with `error="invalid_token"` and malformed headers `400 Bad Request` with `error="invalid_request"`.
+///
+/// # Example
+/// ``` rust
+/// #[derive(Clone, Deserialize)]
+/// struct Claims { sub: String, scope: String }
+///
+/// Server::new("127.0.0.1", 8000)
+///     .with(
+///         Jwt::<Claims>::new(JwtKeys::new().jwks("./keys/jwks.json")?)
+///             .issuer("https://auth.example.com")
+///             .audience("billing")
+///             .leeway(Duration::from_secs(30)),
+///     )
+///     .start();
+///
+/// fn invoices(Extension(claims): Extension<Claims>) -> String {
+///     format!("Invoices of {}", claims.sub)
+/// }
+/// ```
+pub struct Jwt<C> {
+    keys: JwtKeys,
+    realm: String,
+    issuers: Vec<String>,
+    audiences: Vec<String>,
+    leeway: Duration,
+    claims: PhantomData<fn() -> C>,
+}
+
+#[allow(unused)]
+impl<C: DeserializeOwned + Clone + Send + Sync + 'static> Jwt<C> {
+    pub fn new(keys: JwtKeys) -> Jwt<C> {
+        Jwt {
+            keys,
+            realm: "api".to_string(),
+            issuers: Vec::new(),
+            audiences: Vec::new(),
+            leeway: Duration::from_secs(60),
+            claims: PhantomData,
+        }
+    }
+
+    /// Sets the realm of the `WWW-Authenticate` challenge. Defaults to `api`
+    /// # Arguments
+    /// * `realm` - The protection space : &str
+    /// # Returns
+    /// * `Jwt<C>` - The middleware
+    pub fn realm(mut self, realm: &str) -> Jwt<C> {
+        self.realm = realm.to_string();
+        self
+    }
+
+    /// Accepts tokens of an issuer. Once an issuer is set, the `iss` claim is required
+    /// # Arguments
+    /// * `issuer` - The issuer, e.g. `https://auth.example.com` : &str
+    /// # Returns
+    /// * `Jwt<C>` - The middleware
+    pub fn issuer(mut self, issuer: &str) -> Jwt<C> {
+        self.issuers.push(issuer.to_string());
+        self
+    }
+
+    /// Accepts tokens for an audience. Once an audience is set, the `aud` claim is required.
+    /// Without an audience, tokens with an `aud` claim are rejected
+    /// # Arguments
+    /// * `audience` - The audience, usually the name of this service : &str
+    /// # Returns
+    /// * `Jwt<C>` - The middleware
+    pub fn audience(mut self, audience: &str) -> Jwt<C> {
+        self.audiences.push(audience.to_string());
+        self
+    }
+
+    /// Sets the clock skew tolerated for `exp` and `nbf`. Defaults to 60 seconds
+    /// # Arguments
+    /// * `leeway` - The tolerated skew : Duration
+    /// # Returns
+    /// * `Jwt<C>` - The middleware
+    pub fn leeway(mut self, leeway: Duration) -> Jwt<C> {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Verifies a token and deserializes its claims
+    /// # Arguments
+    /// * `token` - The token : &str
+    /// # Returns
+    /// * `Result<C, JwtError>` - The claims, or why the token was rejected
+    pub fn decode(&self, token: &str) -> Result<C, JwtError> {
+        let header = jsonwebtoken::decode_header(token).map_err(|_| JwtError::Malformed)?;
+        let mut candidates = self
+            .keys
+            .keys
+            .iter()
+            .filter(|key| key.algorithm.algorithm() == header.alg)
+            .filter(|key| header.kid.is_none() || key.kid == header.kid)
+            .peekable();
+        if candidates.peek().is_none() {
+            return Err(JwtError::UnknownKey);
+        }
+        let mut error = JwtError::UnknownKey;
+        for key in candidates {
+            match jsonwebtoken::decode::<C>(token, &key.key, &self.validation(key.algorithm)) {
+                Ok(data) => return Ok(data.claims),
+                // Other keys of the algorithm may have signed it, e.g. during a key rotation
+                Err(e) if *e.kind() == ErrorKind::InvalidSignature => error = e.into(),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(error)
+    }
+
+    fn validation(&self, algorithm: JwtAlgorithm) -> Validation {
+        let mut validation = Validation::new(algorithm.algorithm());
+        validation.leeway = self.leeway.as_secs();
+        validation.validate_nbf = true;
+        let mut required = vec!["exp"];
+        if !self.issuers.is_empty() {
+            validation.set_issuer(&self.issuers);
+            required.push("iss");
+        }
+        if !self.audiences.is_empty() {
+            validation.set_audience(&self.audiences);
+            required.push("aud");
+        }
+        validation.set_required_spec_claims(&required);
+        validation
+    }
+}
+
+impl<C: DeserializeOwned + Clone + Send + Sync + 'static> Middleware for Jwt<C> {
+    fn handle(&self, mut request: Request, next: Next) -> Response {
+        let logger: Logger = Logger {
+            c_name: "JWT",
+            level: get_log_level(),
+        };
+        let token = match authorization(&request, "Bearer") {
+            Some(token) if is_token68(&token) => token,
+            Some(_) => {
+                return challenge(
+                    StatusCode::BadRequest,
+                    "Bearer",
+                    &self.realm,
+                    Some("invalid_request"),
+                )
+            }
+            None => return challenge(StatusCode::Unauthorized, "Bearer", &self.realm, None),
+        };
+        match self.decode(&token) {
+            Ok(claims) => {
+                request.extensions.insert(claims);
+                next.run(request)
+            }
+            Err(error) => {
+                let reason = error.to_string();
+                logger.info(&["Rejected token for", request.path.as_str(), reason.as_str()]);
+                challenge(
+                    StatusCode::Unauthorized,
+                    "Bearer",
+                    &self.realm,
+                    Some("invalid_token"),
+                )
+            }
+        }
+    }
+}
+
+/// Issues signed tokens
+/// <br>
+/// `iat` and `exp` are added to the claims, as well as `iss` and `aud` if configured, unless the claims set them.
+/// Tokens expire after 15 minutes by default.
+///
+/// # Example
+/// ``` rust
+/// #[derive(Serialize)]
+/// struct Claims { sub: String, scope: String }
+///
+/// let issuer = JwtIssuer::es256_pem("./keys/service.pem")?
+///     .kid("2024-01")
+///     .issuer("https://orders.example.com")
+///     .audience("billing")
+///     .expires_in(Duration::from_secs(5 * 60));
+/// let token = issuer.issue(&Claims { sub: "orders".to_string(), scope: "invoices:write".to_string() })?;
+/// ```
+pub struct JwtIssuer {
+    algorithm: JwtAlgorithm,
+    key: EncodingKey,
+    kid: Option<String>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    expires_in: Duration,
+}
+
+#[allow(unused)]
+impl JwtIssuer {
+    fn new(algorithm: JwtAlgorithm, key: EncodingKey) -> JwtIssuer {
+        JwtIssuer {
+            algorithm,
+            key,
+            kid: None,
+            issuer: None,
+            audience: None,
+            expires_in: Duration::from_secs(15 * 60),
+        }
+    }
+
+    /// Issues HS256 tokens
+    /// # Arguments
+    /// * `secret` - The shared secret, at least 32 random bytes : &[u8]
+    /// # Returns
+    /// * `JwtIssuer` - The issuer
+    pub fn hs256(secret: &[u8]) -> JwtIssuer {
+        JwtIssuer::new(JwtAlgorithm::HS256, EncodingKey::from_secret(secret))
+    }
+
+    /// Issues RS256 tokens with a private key from a PEM file (`BEGIN PRIVATE KEY` or `BEGIN RSA PRIVATE KEY`)
+    /// # Arguments
+    /// * `path` - The path of the PEM file : &str
+    /// # Returns
+    /// * `io::Result<JwtIssuer>` - The issuer, or the error reading the file. Invalid keys are rejected with `InvalidData`
+    pub fn rs256_pem(path: &str) -> io::Result<JwtIssuer> {
+        let key = load_key(path, EncodingKey::from_rsa_pem)?;
+        Ok(JwtIssuer::new(JwtAlgorithm::RS256, key))
+    }
+
+    /// Issues ES256 tokens with a PKCS#8 private key from a PEM file (`BEGIN PRIVATE KEY`)
+    /// # Arguments
+    /// * `path` - The path of the PEM file : &str
+    /// # Returns
+    /// * `io::Result<JwtIssuer>` - The issuer, or the error reading the file. Invalid keys are rejected with `InvalidData`
+    pub fn es256_pem(path: &str) -> io::Result<JwtIssuer> {
+        let key = load_key(path, EncodingKey::from_ec_pem)?;
+        Ok(JwtIssuer::new(JwtAlgorithm::ES256, key))
+    }
+
+    /// Sets the key id (`kid`) written into the token header
+    pub fn kid(mut self, kid: &str) -> JwtIssuer {
+        self.kid = Some(kid.to_string());
+        self
+    }
+
+    /// Sets the `iss` claim
+    pub fn issuer(mut self, issuer: &str) -> JwtIssuer {
+        self.issuer = Some(issuer.to_string());
+        self
+    }
+
+    /// Sets the `aud` claim
+    pub fn audience(mut self, audience: &str) -> JwtIssuer {
+        self.audience = Some(audience.to_string());
+        self
+    }
+
+    /// Sets how long tokens are valid. Defaults to 15 minutes
+    pub fn expires_in(mut self, expires_in: Duration) -> JwtIssuer {
+        self.expires_in = expires_in;
+        self
+    }
+
+    /// Issues a token
+    /// # Arguments
+    /// * `claims` - The claims, serialized to a JSON object : &C
+    /// # Returns
+    /// * `Result<String, JwtError>` - The token, `InvalidClaims` if the claims are no JSON object
+    pub fn issue<C: Serialize>(&self, claims: &C) -> Result<String, JwtError> {
+        let mut claims = match serde_json::to_value(claims) {
+            Ok(serde_json::Value::Object(claims)) => claims,
+            Ok(_) => {
+                return Err(JwtError::InvalidClaims(
+                    "claims must be a JSON object".to_string(),
+                ))
+            }
+            Err(error) => return Err(JwtError::InvalidClaims(error.to_string())),
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        claims.entry("iat").or_insert(now.into());
+        claims
+            .entry("exp")
+            .or_insert((now + self.expires_in.as_secs()).into());
+        if let Some(issuer) = &self.issuer {
+            claims.entry("iss").or_insert(issuer.as_str().into());
+        }
+        if let Some(audience) = &self.audience {
+            claims.entry("aud").or_insert(audience.as_str().into());
+        }
+        let mut header = jsonwebtoken::Header::new(self.algorithm.algorithm());
+        header.kid = self.kid.clone();
+        Ok(jsonwebtoken::encode(&header, &claims, &self.key)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn jwt() -> Jwt<Value> {
+        Jwt::new(JwtKeys::new().hs256(None, SECRET))
+            .audience("billing")
+            .leeway(Duration::ZERO)
+    }
+
+    #[test]
+    fn accepts_valid_token() {
+        let token = JwtIssuer::hs256(SECRET)
+            .audience("billing")
+            .issue(&json!({ "sub": "orders" }))
+            .unwrap();
+        assert_eq!(jwt().decode(&token).unwrap()["sub"], "orders");
+    }
+
+    #[test]
+    fn rejects_expired_token() {
+        let token = JwtIssuer::hs256(SECRET)
+            .audience("billing")
+            .issue(&json!({ "sub": "orders", "exp": 1_000_000_000 }))
+            .unwrap();
+        assert_eq!(jwt().decode(&token), Err(JwtError::Expired));
+    }
+
+    #[test]
+    fn rejects_wrong_algorithm() {
+        let claims = json!({ "sub": "orders", "aud": "billing", "exp": u32::MAX });
+        let token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(Algorithm::HS512),
+            &claims,
+            &EncodingKey::from_secret(SECRET),
+        )
+        .unwrap();
+        assert_eq!(jwt().decode(&token), Err(JwtError::UnknownKey));
+    }
+
+    #[test]
+    fn rejects_wrong_audience() {
+        let token = JwtIssuer::hs256(SECRET)
+            .audience("shipping")
+            .issue(&json!({ "sub": "orders" }))
+            .unwrap();
+        assert_eq!(jwt().decode(&token), Err(JwtError::InvalidAudience));
+    }
+}
@@ -0,0 +1,499 @@
+// Release builds only compile the pass-through `handle`, leaving the rest of the module unused
+#![cfg_attr(not(debug_assertions), allow(unused))]
+
+use std::{
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, Once,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    error::Error,
+    handler::IntoResponse,
+    http::{
+        http_codes::StatusCode, http_content_types::ContentType, http_headers::Header,
+        http_methods::HttpMethod,
+    },
+    middleware::{security_headers::CspNonce, Middleware, Next},
+    server::get_log_level,
+    transmitters::body::Body,
+    utils::logger::Logger,
+    Request, Response,
+};
+
+/// Interval of the keep-alive comments sent to connected browsers, so closed connections are noticed
+const HEARTBEAT: Duration = Duration::from_secs(15);
+/// Time to wait for further changes before notifying browsers, editors often write a file several times
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Reloads the pages open in browsers when a file of a directory changes, in debug builds only
+/// <br>
+/// A script injected into HTML responses subscribes to a Server-Sent Events endpoint (`/__live_reload` by default).
+/// The directory is watched with inotify on Linux, other systems check it every second. The page is reloaded as well
+/// when the connection is re-established, e.g. after the server was restarted.
+/// <br>
+/// In release builds the middleware passes requests on unchanged and the watcher is not compiled in, so it can stay in
+/// the server setup.
+/// <br>
+/// Only uncompressed `200 OK` HTML responses which are not streamed get the script, so the middleware must be added
+/// after `Compression`. Added after `SecurityHeaders`, the script gets the `Content-Security-Policy` nonce of the request.
+/// <br>
+/// Every connected browser occupies a thread of the pool while the page is open. At most `max_connections` browsers
+/// are connected at once, further ones get `503 Service Unavailable`, so the pool keeps threads for other requests.
+///
+/// # Arguments
+/// * `directory` - The directory to watch : &str
+///
+/// # Example
+/// ``` rust
+/// Server::new("127.0.0.1", 8000)
+///     .with(Compression::new())
+///     .with(LiveReload::new("./static"))
+///     .mount("/", StaticFiles::new("./static"))
+///     .start();
+/// ```
+pub struct LiveReload {
+    directory: PathBuf,
+    endpoint: String,
+    max_connections: usize,
+    state: Arc<ReloadState>,
+    watcher: Once,
+}
+
+/// The number of changes seen so far, browsers waiting for the next one are woken up through the condition variable
+struct ReloadState {
+    generation: Mutex<u64>,
+    changed: Condvar,
+    connections: AtomicUsize,
+}
+
+#[cfg(debug_assertions)]
+impl ReloadState {
+    fn notify(&self) {
+        let mut generation = self.generation.lock().unwrap();
+        *generation += 1;
+        self.changed.notify_all();
+    }
+}
+
+#[allow(unused)]
+impl LiveReload {
+    pub fn new(directory: &str) -> LiveReload {
+        LiveReload {
+            directory: PathBuf::from(directory),
+            endpoint: "/__live_reload".to_string(),
+            max_connections: 4,
+            state: Arc::new(ReloadState {
+                generation: Mutex::new(0),
+                changed: Condvar::new(),
+                connections: AtomicUsize::new(0),
+            }),
+            watcher: Once::new(),
+        }
+    }
+
+    /// Sets the path of the Server-Sent Events endpoint. Defaults to `/__live_reload`
+    /// # Arguments
+    /// * `path` - The path : &str
+    /// # Returns
+    /// * `LiveReload` - The middleware
+    pub fn endpoint(mut self, path: &str) -> LiveReload {
+        self.endpoint = path.to_string();
+        self
+    }
+
+    /// Sets the maximum number of browsers connected at once, each occupying a thread of the pool. Defaults to 4
+    /// # Arguments
+    /// * `connections` - The number of connections : usize
+    /// # Returns
+    /// * `LiveReload` - The middleware
+    pub fn max_connections(mut self, connections: usize) -> LiveReload {
+        self.max_connections = connections;
+        self
+    }
+
+    /// Starts watching the directory in a background thread
+    #[cfg(debug_assertions)]
+    fn start_watcher(&self) {
+        let directory = self.directory.clone();
+        let state = Arc::clone(&self.state);
+        thread::spawn(move || {
+            let logger: Logger = Logger {
+                c_name: "LIVE_RELOAD",
+                level: get_log_level(),
+            };
+            logger.info(&["Watching", &directory.to_string_lossy()]);
+            if let Err(e) = watch(&directory, &state) {
+                logger.error(e.to_string().as_str(), &["Unable to watch the directory"]);
+            }
+        });
+    }
+
+    /// Returns the script reloading the page on change events, with the `Content-Security-Policy` nonce of the request if any
+    fn script(&self, nonce: Option<&CspNonce>) -> String {
+        let nonce = nonce
+            .map(|CspNonce(nonce)| format!(" nonce=\"{}\"", nonce))
+            .unwrap_or_default();
+        format!(
+            "<script{}>(function(){{var s=new EventSource(\"{}\"),d=false;\
+             s.addEventListener(\"reload\",function(){{location.reload()}});\
+             s.onerror=function(){{d=true}};s.onopen=function(){{if(d)location.reload()}}}})();</script>",
+            nonce, self.endpoint
+        )
+    }
+
+    /// Opens the event stream of a browser, `503 Service Unavailable` if `max_connections` browsers are connected
+    #[cfg(debug_assertions)]
+    fn connect(&self) -> Response {
+        let connections = self.state.connections.fetch_add(1, Ordering::SeqCst);
+        if connections >= self.max_connections {
+            self.state.connections.fetch_sub(1, Ordering::SeqCst);
+            return Error::new(
+                StatusCode::ServiceUnavailable,
+                "Too many live reload connections",
+            )
+            .into_response();
+        }
+        let mut response = Response::get_empty();
+        response
+            .add_header(Header::new(
+                "Content-Type".to_string(),
+                "text/event-stream".to_string(),
+            ))
+            .add_header(Header::new(
+                "Cache-Control".to_string(),
+                "no-cache".to_string(),
+            ))
+            .set_body(Body::reader(EventStream::new(Arc::clone(&self.state))));
+        response
+    }
+
+    /// Adds the script to an HTML response, before `</body>` or at the end
+    fn inject(&self, response: &mut Response, nonce: Option<&CspNonce>) {
+        let script = self.script(nonce);
+        let lower = response.data.to_ascii_lowercase();
+        let position = lower
+            .windows(7)
+            .rposition(|window| window == b"</body>")
+            .unwrap_or(lower.len());
+        let mut data = response.data[..position].to_vec();
+        data.extend_from_slice(script.as_bytes());
+        data.extend_from_slice(&response.data[position..]);
+        response.set_data(data);
+    }
+}
+
+/// Returns `true` if the response is an HTML page the script can be added to
+#[cfg(debug_assertions)]
+fn is_injectable(response: &Response) -> bool {
+    let is_html = match response.get_header("Content-Type") {
+        Some(header) => header.value().trim_start().starts_with("text/html"),
+        None => response.content_type == ContentType::TextHtmlCharsetUtf8,
+    };
+    is_html
+        && response.status == StatusCode::Ok
+        && response.body.is_none()
+        && response.get_header("Content-Encoding").is_none()
+}
+
+impl Middleware for LiveReload {
+    #[cfg(not(debug_assertions))]
+    fn handle(&self, request: Request, next: Next) -> Response {
+        next.run(request)
+    }
+
+    #[cfg(debug_assertions)]
+    fn handle(&self, request: Request, next: Next) -> Response {
+        self.watcher.call_once(|| self.start_watcher());
+        if request.path == self.endpoint && request.method == HttpMethod::GET {
+            return self.connect();
+        }
+        let nonce = request.extensions.get::<CspNonce>().cloned();
+        let mut response = next.run(request);
+        if is_injectable(&response) {
+            self.inject(&mut response, nonce.as_ref());
+        }
+        response
+    }
+}
+
+/// The stream of events sent to a browser: a `reload` event for every change and heartbeat comments in between
+/// <br>
+/// The stream holds one of the `max_connections` slots until it is dropped, i.e. until the browser disconnected.
+#[cfg(debug_assertions)]
+struct EventStream {
+    state: Arc<ReloadState>,
+    seen: u64,
+    pending: Vec<u8>,
+}
+
+#[cfg(debug_assertions)]
+impl EventStream {
+    fn new(state: Arc<ReloadState>) -> EventStream {
+        let seen = *state.generation.lock().unwrap();
+        EventStream {
+            state,
+            seen,
+            pending: b"retry: 1000\n\n".to_vec(),
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+impl Read for EventStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            let generation = self.state.generation.lock().unwrap();
+            let (generation, _) = self
+                .state
+                .changed
+                .wait_timeout_while(generation, HEARTBEAT, |generation| *generation == self.seen)
+                .unwrap();
+            self.pending = match *generation == self.seen {
+                true => b": heartbeat\n\n".to_vec(),
+                false => format!("event: reload\ndata: {}\n\n", generation).into_bytes(),
+            };
+            self.seen = *generation;
+        }
+        let length = buf.len().min(self.pending.len());
+        buf[..length].copy_from_slice(&self.pending[..length]);
+        self.pending.drain(..length);
+        Ok(length)
+    }
+}
+
+#[cfg(debug_assertions)]
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.state.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Watches a directory and its subdirectories with inotify, notifying the state on every change
+#[cfg(all(debug_assertions, target_os = "linux"))]
+fn watch(directory: &Path, state: &ReloadState) -> io::Result<()> {
+    use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+    use std::collections::HashMap;
+
+    fn add_watches(
+        inotify: &Inotify,
+        directory: &Path,
+        watched: &mut HashMap<WatchDescriptor, PathBuf>,
+    ) -> io::Result<()> {
+        let mask = WatchMask::CLOSE_WRITE
+            | WatchMask::MODIFY
+            | WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO;
+        let descriptor = inotify.watches().add(directory, mask)?;
+        watched.insert(descriptor, directory.to_path_buf());
+        for entry in std::fs::read_dir(directory)?.flatten() {
+            let path = entry.path();
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                add_watches(inotify, &path, watched)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut inotify = Inotify::init()?;
+    let mut watched = HashMap::new();
+    add_watches(&inotify, directory, &mut watched)?;
+    let mut buffer = [0u8; 4096];
+    let mut blocking = true;
+    loop {
+        let events = match blocking {
+            true => inotify.read_events_blocking(&mut buffer)?,
+            false => match inotify.read_events(&mut buffer) {
+                Ok(events) => events,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    state.notify();
+                    blocking = true;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            },
+        };
+        let created: Vec<PathBuf> = events
+            .filter(|event| {
+                event.mask.contains(EventMask::ISDIR)
+                    && (event.mask.contains(EventMask::CREATE)
+                        || event.mask.contains(EventMask::MOVED_TO))
+            })
+            .filter_map(|event| Some(watched.get(&event.wd)?.join(event.name?)))
+            .collect();
+        for directory in created {
+            // The directory may already be gone again
+            let _ = add_watches(&inotify, &directory, &mut watched);
+        }
+        thread::sleep(DEBOUNCE);
+        blocking = false;
+    }
+}
+
+/// Watches a directory by checking the modification times of its files every second
+#[cfg(all(debug_assertions, not(target_os = "linux")))]
+fn watch(directory: &Path, state: &ReloadState) -> io::Result<()> {
+    use std::time::SystemTime;
+
+    fn snapshot(
+        directory: &Path,
+        files: &mut Vec<(PathBuf, Option<SystemTime>)>,
+    ) -> io::Result<()> {
+        for entry in std::fs::read_dir(directory)?.flatten() {
+            let metadata = entry.metadata()?;
+            match metadata.is_dir() {
+                true => snapshot(&entry.path(), files)?,
+                false => files.push((entry.path(), metadata.modified().ok())),
+            }
+        }
+        Ok(())
+    }
+
+    let mut previous = Vec::new();
+    snapshot(directory, &mut previous)?;
+    loop {
+        thread::sleep(Duration::from_secs(1));
+        let mut current = Vec::new();
+        snapshot(directory, &mut current)?;
+        if current != previous {
+            state.notify();
+            previous = current;
+        }
+    }
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::sessions::session::generate_id;
+
+    /// A middleware watching an empty temporary directory
+    struct Fixture {
+        live_reload: Arc<LiveReload>,
+        directory: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(max_connections: usize) -> Fixture {
+            let directory = std::env::temp_dir().join(format!("live-reload-{}", generate_id()));
+            fs::create_dir_all(&directory).unwrap();
+            let live_reload =
+                LiveReload::new(directory.to_str().unwrap()).max_connections(max_connections);
+            Fixture {
+                live_reload: Arc::new(live_reload),
+                directory,
+            }
+        }
+
+        fn get(&self, path: &str, endpoint: &dyn Fn(Request) -> Response) -> Response {
+            let middlewares: [Arc<dyn Middleware>; 1] = [self.live_reload.clone()];
+            Next::new(&middlewares, endpoint).run(Request::test(HttpMethod::GET, path, &[]))
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.directory);
+        }
+    }
+
+    fn page(_: Request) -> Response {
+        let mut response = Response::get_empty();
+        response
+            .set_content_type(ContentType::TextHtmlCharsetUtf8)
+            .set_data("<html><BODY>page</BODY></html>");
+        response
+    }
+
+    fn read(stream: &mut impl Read) -> String {
+        let mut buffer = [0u8; 256];
+        let length = stream.read(&mut buffer).unwrap();
+        String::from_utf8_lossy(&buffer[..length]).into_owned()
+    }
+
+    #[test]
+    fn injects_script_before_body_end() {
+        let fixture = Fixture::new(1);
+        let html = String::from_utf8(fixture.get("/", &page).data).unwrap();
+        assert!(html.starts_with("<html><BODY>page<script>"));
+        assert!(html.ends_with("</script></BODY></html>"));
+        assert!(html.contains("new EventSource(\"/__live_reload\")"));
+
+        let mut response = page(Request::get_empty());
+        fixture
+            .live_reload
+            .inject(&mut response, Some(&CspNonce("abc".to_string())));
+        assert!(String::from_utf8(response.data)
+            .unwrap()
+            .contains("<script nonce=\"abc\">"));
+    }
+
+    #[test]
+    fn skips_responses_other_than_plain_html_pages() {
+        let fixture = Fixture::new(1);
+        let json = |request| {
+            let mut response = page(request);
+            response.set_content_type(ContentType::ApplicationJson);
+            response
+        };
+        let compressed = |request| {
+            let mut response = page(request);
+            response.add_header(Header::new(
+                "Content-Encoding".to_string(),
+                "gzip".to_string(),
+            ));
+            response
+        };
+        let missing = |request| {
+            let mut response = page(request);
+            response.set_status(StatusCode::NotFound);
+            response
+        };
+        for endpoint in [&json as &dyn Fn(Request) -> Response, &compressed, &missing] {
+            assert!(!String::from_utf8(fixture.get("/", endpoint).data)
+                .unwrap()
+                .contains("<script"));
+        }
+    }
+
+    #[test]
+    fn streams_reload_events_after_changes() {
+        let fixture = Fixture::new(1);
+        let mut response = fixture.get("/__live_reload", &|_| panic!("Endpoint was called"));
+        assert_eq!(
+            response.get_header("Content-Type").unwrap().value(),
+            "text/event-stream"
+        );
+        let mut stream = response.body.take().unwrap().into_reader();
+        assert_eq!(read(&mut stream), "retry: 1000\n\n");
+        fixture.live_reload.state.notify();
+        assert_eq!(read(&mut stream), "event: reload\ndata: 1\n\n");
+    }
+
+    #[test]
+    fn limits_concurrent_event_streams() {
+        let fixture = Fixture::new(2);
+        let open = || fixture.get("/__live_reload", &|_| panic!("Endpoint was called"));
+        let first = open();
+        let second = open();
+        assert_eq!(first.status, StatusCode::Ok);
+        assert_eq!(second.status, StatusCode::Ok);
+        assert_eq!(open().status, StatusCode::ServiceUnavailable);
+        drop(first);
+        assert_eq!(open().status, StatusCode::Ok);
+        assert_eq!(
+            fixture.live_reload.state.connections.load(Ordering::SeqCst),
+            1
+        );
+        drop(second);
+    }
+}
@@ -0,0 +1,146 @@
+pub mod auth;
+pub mod compression;
+pub mod cors;
+pub mod csrf;
+pub mod jwt;
+pub mod live_reload;
+pub mod rate_limit;
+pub mod security_headers;
+
+use std::sync::Arc;
+
+use crate::{Request, Response};
+
+/// Middleware wrapping the handling of a request
+/// <br>
+/// A middleware receives the request together with the rest of the chain (`next`). It may modify
+/// the request before passing it on, return a response early without calling `next` or transform
+/// the response returned by `next`.
+/// <br>
+/// Any `Fn(Request, Next) -> Response` closure is a middleware as well.
+///
+/// # Example
+/// ``` rust
+/// struct RequireToken;
+///
+/// impl Middleware for RequireToken {
+///     fn handle(&self, request: Request, next: Next) -> Response {
+///         if request.get_header("Authorization").is_none() {
+///             let mut response = Response::get_empty();
+///             response.set_status(StatusCode::Unauthorized);
+///             return response;
+///         }
+///         next.run(request)
+///     }
+/// }
+/// ```
+pub trait Middleware: Send + Sync {
+    fn handle(&self, request: Request, next: Next) -> Response;
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(Request, Next) -> Response + Send + Sync,
+{
+    fn handle(&self, request: Request, next: Next) -> Response {
+        self(request, next)
+    }
+}
+
+/// The remaining part of a middleware chain
+/// <br>
+/// Calling `run` passes the request to the next middleware, or to the endpoint once all middlewares have been called.
+pub struct Next<'a> {
+    middlewares: &'a [Arc<dyn Middleware>],
+    endpoint: &'a dyn Fn(Request) -> Response,
+}
+
+impl<'a> Next<'a> {
+    /// Creates a chain
+    /// # Arguments
+    /// * `middlewares` - The middlewares to call in order : &[Arc<dyn Middleware>]
+    /// * `endpoint` - The function called after the last middleware : &dyn Fn(Request) -> Response
+    pub fn new(
+        middlewares: &'a [Arc<dyn Middleware>],
+        endpoint: &'a dyn Fn(Request) -> Response,
+    ) -> Next<'a> {
+        Next {
+            middlewares,
+            endpoint,
+        }
+    }
+
+    /// Runs the rest of the chain
+    /// # Arguments
+    /// * `request` - The request to pass on : Request
+    /// # Returns
+    /// * `Response` - The response of the chain
+    pub fn run(self, request: Request) -> Response {
+        match self.middlewares.split_first() {
+            Some((middleware, rest)) => middleware.handle(
+                request,
+                Next {
+                    middlewares: rest,
+                    endpoint: self.endpoint,
+                },
+            ),
+            None => (self.endpoint)(request),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{http_codes::StatusCode, http_headers::Header, http_methods::HttpMethod};
+
+    /// Appends its name to the `X-Trace` header of the request and of the response
+    fn trace(name: &'static str) -> Arc<dyn Middleware> {
+        Arc::new(move |mut request: Request, next: Next| {
+            let trace = request
+                .get_header("X-Trace")
+                .map(|header| header.value().to_string())
+                .unwrap_or_default();
+            request.headers.retain(|header| header.key != "X-Trace");
+            request.add_header(Header::new("X-Trace".to_string(), trace + name));
+            let mut response = next.run(request);
+            response.add_header(Header::new("X-Trace".to_string(), name.to_string()));
+            response
+        })
+    }
+
+    fn endpoint(request: Request) -> Response {
+        let mut response = Response::get_empty();
+        response.data = request.get_header("X-Trace").unwrap().value().into();
+        response
+    }
+
+    #[test]
+    fn runs_middlewares_in_order_around_endpoint() {
+        let middlewares = [trace("a"), trace("b")];
+        let response =
+            Next::new(&middlewares, &endpoint).run(Request::test(HttpMethod::GET, "/", &[]));
+        assert_eq!(response.data, b"ab");
+        let traces: Vec<&str> = response
+            .headers
+            .iter()
+            .filter(|header| header.key == "X-Trace")
+            .map(|header| header.value())
+            .collect();
+        assert_eq!(traces, ["b", "a"]);
+    }
+
+    #[test]
+    fn returns_early_without_calling_rest_of_chain() {
+        let reject: Arc<dyn Middleware> = Arc::new(|_: Request, _: Next| {
+            let mut response = Response::get_empty();
+            response.set_status(StatusCode::Unauthorized);
+            response
+        });
+        let middlewares = [reject, trace("never")];
+        let response = Next::new(&middlewares, &|_| panic!("Endpoint was called"))
+            .run(Request::test(HttpMethod::GET, "/", &[]));
+        assert_eq!(response.status, StatusCode::Unauthorized);
+        assert!(response.headers.is_empty());
+    }
+}
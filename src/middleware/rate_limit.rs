@@ -0,0 +1,511 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::Error,
+    handler::IntoResponse,
+    http::{http_codes::StatusCode, http_headers::Header},
+    middleware::{auth::Principal, Middleware, Next},
+    server::get_log_level,
+    utils::logger::Logger,
+    Request, Response,
+};
+
+/// Returns the current unix time in milliseconds
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// How requests are counted
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum RateLimitAlgorithm {
+    /// A bucket holding up to `limit` tokens, refilled evenly over the period. Allows bursts of `limit` requests
+    TokenBucket,
+    /// The requests of the current window plus the share of the previous window still overlapping the last period
+    SlidingWindow,
+}
+
+/// The state of a client, kept by a `RateLimitStore`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RateLimitState {
+    /// The tokens left and the unix time (milliseconds) they were counted at
+    Bucket { tokens: f64, updated: u64 },
+    /// The unix time (milliseconds) the current window started at and the requests of the current and previous window
+    Window {
+        start: u64,
+        current: u64,
+        previous: u64,
+    },
+}
+
+type KeyFn = dyn Fn(&Request) -> Option<String> + Send + Sync;
+
+/// What the rate limited requests are counted by
+#[derive(Clone)]
+pub enum RateLimitKey {
+    /// The IP address of the client
+    PeerIp,
+    /// The `Principal` of `BasicAuth`, which must run before. For other authentication middlewares use
+    /// `RateLimitKey::principal`
+    Principal,
+    /// The value of a header, e.g. `X-API-Key`, so all clients sending the same value share a limit
+    /// <br>
+    /// The header is not authenticated: a client sending random values gets a new limit for every value, and anyone
+    /// knowing a value can use up its limit. Nest it in a `RateLimitKey::PeerIp` limit, e.g. of the server, to bound
+    /// such clients, or use a principal key after the middleware checking the API key.
+    Header(String),
+    /// The value of a header together with the IP address of the client, so every client gets its own limit per value
+    HeaderPerIp(String),
+    /// The key returned by a closure
+    Custom(Arc<KeyFn>),
+}
+
+#[allow(unused)]
+impl RateLimitKey {
+    /// Counts requests by the principal another authentication middleware inserted into the request extensions,
+    /// e.g. the claims of `Jwt<C>` or the principal of `BearerAuth<P>`
+    /// <br>
+    /// Requests without a principal of the type are counted by their IP address.
+    /// # Arguments
+    /// * `name` - Gets the name the principal is counted by : impl Fn(&P) -> String
+    /// # Returns
+    /// * `RateLimitKey` - The key
+    /// # Example
+    /// ``` rust
+    /// RateLimit::new(100, Duration::from_secs(60)).key(RateLimitKey::principal(|claims: &Claims| claims.sub.clone()))
+    /// ```
+    pub fn principal<P: Send + Sync + 'static>(
+        name: impl Fn(&P) -> String + Send + Sync + 'static,
+    ) -> RateLimitKey {
+        RateLimitKey::Custom(Arc::new(move |request: &Request| {
+            request.extensions.get::<P>().map(&name)
+        }))
+    }
+
+    /// Gets the key of a request, prefixed with its kind so different kinds never collide
+    /// <br>
+    /// Requests without a principal, header or custom key are counted by their IP address.
+    fn of(&self, request: &Request) -> String {
+        let ip = match request.peer_addr.parse::<SocketAddr>() {
+            Ok(address) => address.ip().to_string(),
+            Err(_) => request.peer_addr.clone(),
+        };
+        let key = match self {
+            RateLimitKey::PeerIp => None,
+            RateLimitKey::Principal => request
+                .extensions
+                .get::<Principal>()
+                .map(|principal| format!("principal:{}", principal.name)),
+            RateLimitKey::Header(name) => request
+                .get_header(name)
+                .map(|header| format!("header:{}", header.value().trim())),
+            RateLimitKey::HeaderPerIp(name) => request
+                .get_header(name)
+                .map(|header| format!("header-ip:{}:{}", ip, header.value().trim())),
+            RateLimitKey::Custom(key) => key(request).map(|key| format!("custom:{}", key)),
+        };
+        key.unwrap_or_else(|| format!("ip:{}", ip))
+    }
+}
+
+/// Storage backend of the rate limit states
+/// <br>
+/// Implementations must be safe to share between the worker threads.
+pub trait RateLimitStore: Send + Sync {
+    /// Atomically replaces the state of a key
+    /// # Arguments
+    /// * `key` - The client key : &str
+    /// * `ttl` - How long the new state is needed, after that the client is back to its full limit : Duration
+    /// * `update` - Computes the new state from the current one, `None` for unknown or expired keys : &mut dyn FnMut(Option<RateLimitState>) -> RateLimitState
+    fn update(
+        &self,
+        key: &str,
+        ttl: Duration,
+        update: &mut dyn FnMut(Option<RateLimitState>) -> RateLimitState,
+    );
+}
+
+/// In-memory rate limit store
+/// <br>
+/// Every state expires `ttl` after its last update. Expired states are evicted from the whole store at most once per
+/// eviction interval, during an update.
+///
+/// # Example
+/// ``` rust
+/// let store = MemoryRateLimitStore::new().eviction_interval(Duration::from_secs(5 * 60));
+/// ```
+pub struct MemoryRateLimitStore {
+    eviction_interval: Duration,
+    states: Mutex<MemoryStates>,
+}
+
+struct MemoryStates {
+    entries: HashMap<String, (RateLimitState, u64)>,
+    next_eviction: u64,
+}
+
+#[allow(unused)]
+impl MemoryRateLimitStore {
+    pub fn new() -> MemoryRateLimitStore {
+        MemoryRateLimitStore {
+            eviction_interval: Duration::from_secs(60),
+            states: Mutex::new(MemoryStates {
+                entries: HashMap::new(),
+                next_eviction: 0,
+            }),
+        }
+    }
+
+    /// Sets how often expired states are evicted. Defaults to 60 seconds
+    /// # Arguments
+    /// * `interval` - The interval : Duration
+    /// # Returns
+    /// * `MemoryRateLimitStore` - The store
+    pub fn eviction_interval(mut self, interval: Duration) -> MemoryRateLimitStore {
+        self.eviction_interval = interval;
+        self
+    }
+
+    /// Removes all expired states
+    pub fn evict_expired(&self) {
+        let now = unix_millis();
+        self.states
+            .lock()
+            .unwrap()
+            .entries
+            .retain(|_, (_, expires)| *expires > now);
+    }
+
+    /// Returns the number of clients with a state, including expired ones not evicted yet
+    pub fn len(&self) -> usize {
+        self.states.lock().unwrap().entries.len()
+    }
+
+    /// Returns `true` if no client has a state
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for MemoryRateLimitStore {
+    fn default() -> Self {
+        MemoryRateLimitStore::new()
+    }
+}
+
+impl RateLimitStore for MemoryRateLimitStore {
+    fn update(
+        &self,
+        key: &str,
+        ttl: Duration,
+        update: &mut dyn FnMut(Option<RateLimitState>) -> RateLimitState,
+    ) {
+        let now = unix_millis();
+        let mut states = self.states.lock().unwrap();
+        if now >= states.next_eviction {
+            states.entries.retain(|_, (_, expires)| *expires > now);
+            states.next_eviction = now + self.eviction_interval.as_millis() as u64;
+        }
+        let current = states
+            .entries
+            .get(key)
+            .filter(|(_, expires)| *expires > now)
+            .map(|(state, _)| *state);
+        let state = update(current);
+        states
+            .entries
+            .insert(key.to_string(), (state, now + ttl.as_millis() as u64));
+    }
+}
+
+/// The outcome of counting a request
+struct Decision {
+    allowed: bool,
+    remaining: u64,
+    /// Milliseconds until the client is back to its full limit
+    reset: u64,
+    /// Milliseconds until the next request is allowed
+    retry_after: u64,
+}
+
+/// Limits how many requests a client may send in a period
+/// <br>
+/// Clients are identified by their IP address by default, or by the authenticated principal, an API key header or a
+/// closure. Every response gets `RateLimit-Limit`, `RateLimit-Remaining`, `RateLimit-Reset` and `RateLimit-Policy`
+/// headers. Requests over the limit get `429 Too Many Requests` with `Retry-After`, without calling the handler.
+/// <br>
+/// The routes of a `RouteGroup` share the limit of the group's middleware, so different groups can get different
+/// limits. The states are kept in a `MemoryRateLimitStore` unless another `RateLimitStore` is set.
+///
+/// # Arguments
+/// * `limit` - The number of requests allowed per period : u64
+/// * `period` - The period : Duration
+///
+/// # Example
+/// ``` rust
+/// let api = RouteGroup::new("/api")
+///     .with(BasicAuth::htpasswd("API", "./users.htpasswd")?)
+///     .with(RateLimit::new(1000, Duration::from_secs(60 * 60)).key(RateLimitKey::Principal))
+///     .route(Route::new("/reports", reports, HttpMethod::GET));
+///
+/// Server::new("127.0.0.1", 8000)
+///     .with(RateLimit::new(20, Duration::from_secs(1)).algorithm(RateLimitAlgorithm::SlidingWindow))
+///     .routes(api.into_routes())
+///     .start();
+/// ```
+pub struct RateLimit {
+    limit: u64,
+    period: Duration,
+    algorithm: RateLimitAlgorithm,
+    key: RateLimitKey,
+    store: Box<dyn RateLimitStore>,
+}
+
+#[allow(unused)]
+impl RateLimit {
+    pub fn new(limit: u64, period: Duration) -> RateLimit {
+        RateLimit {
+            limit: limit.max(1),
+            period: period.max(Duration::from_millis(1)),
+            algorithm: RateLimitAlgorithm::TokenBucket,
+            key: RateLimitKey::PeerIp,
+            store: Box::new(MemoryRateLimitStore::new()),
+        }
+    }
+
+    /// Sets how requests are counted. Defaults to `RateLimitAlgorithm::TokenBucket`
+    /// # Arguments
+    /// * `algorithm` - The algorithm : RateLimitAlgorithm
+    /// # Returns
+    /// * `RateLimit` - The middleware
+    pub fn algorithm(mut self, algorithm: RateLimitAlgorithm) -> RateLimit {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets what requests are counted by. Defaults to `RateLimitKey::PeerIp`
+    /// # Arguments
+    /// * `key` - The key : RateLimitKey
+    /// # Returns
+    /// * `RateLimit` - The middleware
+    pub fn key(mut self, key: RateLimitKey) -> RateLimit {
+        self.key = key;
+        self
+    }
+
+    /// Counts requests by the key returned by a closure, requests without key are counted by their IP address
+    /// # Arguments
+    /// * `key` - The closure : impl Fn(&Request) -> Option<String>
+    /// # Returns
+    /// * `RateLimit` - The middleware
+    pub fn key_fn(
+        self,
+        key: impl Fn(&Request) -> Option<String> + Send + Sync + 'static,
+    ) -> RateLimit {
+        self.key(RateLimitKey::Custom(Arc::new(key)))
+    }
+
+    /// Sets where the states are kept. Defaults to a `MemoryRateLimitStore`
+    /// # Arguments
+    /// * `store` - The store : impl RateLimitStore
+    /// # Returns
+    /// * `RateLimit` - The middleware
+    pub fn store(mut self, store: impl RateLimitStore + 'static) -> RateLimit {
+        self.store = Box::new(store);
+        self
+    }
+
+    /// Counts a request of a client at a unix time in milliseconds
+    fn hit(&self, key: &str, now: u64) -> Decision {
+        let period = self.period.as_millis() as u64;
+        let limit = self.limit;
+        let mut decision = Decision {
+            allowed: false,
+            remaining: 0,
+            reset: 0,
+            retry_after: 0,
+        };
+        match self.algorithm {
+            RateLimitAlgorithm::TokenBucket => {
+                // Tokens refilled per millisecond
+                let rate = limit as f64 / period as f64;
+                self.store.update(key, self.period, &mut |state| {
+                    let tokens = match state {
+                        Some(RateLimitState::Bucket { tokens, updated }) => {
+                            (tokens + now.saturating_sub(updated) as f64 * rate).min(limit as f64)
+                        }
+                        _ => limit as f64,
+                    };
+                    decision.allowed = tokens >= 1.0;
+                    let tokens = match decision.allowed {
+                        true => tokens - 1.0,
+                        false => tokens,
+                    };
+                    decision.remaining = tokens.floor() as u64;
+                    decision.reset = ((limit as f64 - tokens) / rate).ceil() as u64;
+                    decision.retry_after = ((1.0 - tokens).max(0.0) / rate).ceil() as u64;
+                    RateLimitState::Bucket {
+                        tokens,
+                        updated: now,
+                    }
+                });
+            }
+            RateLimitAlgorithm::SlidingWindow => {
+                let window_start = now - now % period;
+                self.store.update(key, self.period * 2, &mut |state| {
+                    let (current, previous) = match state {
+                        Some(RateLimitState::Window {
+                            start,
+                            current,
+                            previous,
+                        }) => match window_start.saturating_sub(start) / period {
+                            0 => (current, previous),
+                            1 => (0, current),
+                            _ => (0, 0),
+                        },
+                        _ => (0, 0),
+                    };
+                    let elapsed = now - window_start;
+                    // Share of the previous window still inside the last period
+                    let overlap = (period - elapsed) as f64 / period as f64;
+                    let weighted = previous as f64 * overlap + current as f64;
+                    decision.allowed = weighted + 1.0 <= limit as f64;
+                    let current = match decision.allowed {
+                        true => current + 1,
+                        false => current,
+                    };
+                    let count = previous as f64 * overlap + current as f64;
+                    decision.remaining = (limit as f64 - count).max(0.0).floor() as u64;
+                    decision.reset = match current {
+                        0 => period - elapsed,
+                        _ => 2 * period - elapsed,
+                    };
+                    decision.retry_after = match decision.allowed {
+                        true => 0,
+                        // Wait until enough of the previous window slid out of the period
+                        false if current < limit => {
+                            let share = (limit - current - 1) as f64 / previous as f64;
+                            (((1.0 - share) * period as f64).ceil() as u64).saturating_sub(elapsed)
+                        }
+                        // The current window is full, it becomes the previous one and has to slide out as well
+                        false => {
+                            let share = (limit - 1) as f64 / current as f64;
+                            period - elapsed + ((1.0 - share) * period as f64).ceil() as u64
+                        }
+                    };
+                    RateLimitState::Window {
+                        start: window_start,
+                        current,
+                        previous,
+                    }
+                });
+            }
+        }
+        decision
+    }
+
+    fn add_headers(&self, response: &mut Response, decision: &Decision) {
+        let seconds = |millis: u64| millis.div_ceil(1000).to_string();
+        let headers = [
+            ("RateLimit-Limit", self.limit.to_string()),
+            ("RateLimit-Remaining", decision.remaining.to_string()),
+            ("RateLimit-Reset", seconds(decision.reset)),
+            (
+                "RateLimit-Policy",
+                format!("{};w={}", self.limit, self.period.as_secs().max(1)),
+            ),
+        ];
+        // Nested limits, e.g. of the server and a route group, keep the headers of the innermost one
+        for (name, value) in headers {
+            if response.get_header(name).is_some() {
+                continue;
+            }
+            response.add_header(Header::new(name.to_string(), value));
+        }
+    }
+}
+
+impl Middleware for RateLimit {
+    fn handle(&self, request: Request, next: Next) -> Response {
+        let logger: Logger = Logger {
+            c_name: "RATE LIMIT",
+            level: get_log_level(),
+        };
+        let key = self.key.of(&request);
+        let decision = self.hit(&key, unix_millis());
+        if !decision.allowed {
+            logger.debug(&["Rate limited", key.as_str(), "for", request.path.as_str()]);
+            let mut response = Error::from_status(StatusCode::TooManyRequests).into_response();
+            self.add_headers(&mut response, &decision);
+            response.add_header(Header::new(
+                "Retry-After".to_string(),
+                decision.retry_after.div_ceil(1000).max(1).to_string(),
+            ));
+            return response;
+        }
+        let mut response = next.run(request);
+        self.add_headers(&mut response, &decision);
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refills_bucket_over_period() {
+        let limit = RateLimit::new(2, Duration::from_secs(1));
+        assert_eq!(limit.hit("a", 0).remaining, 1);
+        assert_eq!(limit.hit("a", 0).remaining, 0);
+        let denied = limit.hit("a", 0);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, 500);
+        assert!(!limit.hit("a", 499).allowed);
+        assert!(limit.hit("a", 500).allowed);
+        assert!(limit.hit("b", 500).allowed);
+    }
+
+    #[test]
+    fn retries_after_previous_window_slid_out() {
+        let limit =
+            RateLimit::new(2, Duration::from_secs(1)).algorithm(RateLimitAlgorithm::SlidingWindow);
+        assert!(limit.hit("a", 1000).allowed);
+        assert!(limit.hit("a", 1000).allowed);
+        let denied = limit.hit("a", 1100);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, 1400);
+        assert!(!limit.hit("a", 2499).allowed);
+        assert!(limit.hit("a", 2500).allowed);
+    }
+
+    #[test]
+    fn keys_headers_and_principals_by_type() {
+        use crate::http::http_methods::HttpMethod;
+
+        let mut request = Request::test(HttpMethod::GET, "/", &[("X-API-Key", "k1")]);
+        request.peer_addr = "10.0.0.1:5000".to_string();
+        let header = RateLimitKey::Header("X-API-Key".to_string());
+        assert_eq!(header.of(&request), "header:k1");
+        let per_ip = RateLimitKey::HeaderPerIp("X-API-Key".to_string());
+        assert_eq!(per_ip.of(&request), "header-ip:10.0.0.1:k1");
+        request.peer_addr = "10.0.0.2:5000".to_string();
+        assert_eq!(header.of(&request), "header:k1");
+        assert_eq!(per_ip.of(&request), "header-ip:10.0.0.2:k1");
+        request.headers.clear();
+        assert_eq!(header.of(&request), "ip:10.0.0.2");
+        assert_eq!(per_ip.of(&request), "ip:10.0.0.2");
+
+        let principal = RateLimitKey::principal(|claims: &String| claims.clone());
+        assert_eq!(principal.of(&request), "ip:10.0.0.2");
+        request.extensions.insert("alice".to_string());
+        assert_eq!(principal.of(&request), "custom:alice");
+    }
+}
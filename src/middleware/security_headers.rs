@@ -0,0 +1,358 @@
+use std::time::Duration;
+
+use crate::{
+    http::http_headers::Header,
+    middleware::{Middleware, Next},
+    Request, Response,
+};
+
+/// Placeholder replaced with the nonce of the request in the `Content-Security-Policy`
+pub const NONCE_PLACEHOLDER: &str = "{nonce}";
+
+/// The `Content-Security-Policy` nonce of a request, for inline `<script nonce="...">` and `<style nonce="...">` elements
+/// <br>
+/// Inserted into `Request::extensions` by `SecurityHeaders` when its policy contains `{nonce}`.
+///
+/// # Example
+/// ``` rust
+/// fn page(Extension(CspNonce(nonce)): Extension<CspNonce>) -> Response {
+///     Response::new(
+///         StatusCode::Ok,
+///         ContentType::TextHtmlCharsetUtf8,
+///         Vec::new(),
+///         Vec::new(),
+///         format!("<script nonce=\"{}\">console.log('allowed')</script>", nonce),
+///     )
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CspNonce(pub String);
+
+/// Marks a response whose security headers were already set by a route-level `SecurityHeaders`
+struct SecurityHeadersApplied;
+
+/// Values of the `X-Frame-Options` header
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum FrameOptions {
+    /// The page can not be displayed in a frame
+    Deny,
+    /// The page can only be displayed in a frame on the same origin
+    SameOrigin,
+}
+
+/// Sets security headers on every response
+/// <br>
+/// Headers already set by the handler are kept. A `SecurityHeaders` added to a route or route group overrides the one
+/// of the server for its routes: the server-level middleware leaves their responses alone.
+/// <br>
+/// `{nonce}` in the `Content-Security-Policy` is replaced with a random nonce generated for every request, which
+/// handlers get as `CspNonce` from the request extensions. A route-level middleware reuses the nonce of the server-level one.
+/// <br>
+/// `strict` is meant for HTML pages, `api` for JSON APIs which are never rendered by browsers.
+/// `Strict-Transport-Security` is ignored by browsers for responses sent over plain HTTP.
+///
+/// # Example
+/// ``` rust
+/// Server::new("127.0.0.1", 8000)
+///     .with(SecurityHeaders::strict().referrer_policy("strict-origin-when-cross-origin"))
+///     .routes(add_routes!(
+///         Route::new("/api/users", users, HttpMethod::GET).with(SecurityHeaders::api()),
+///         Route::new("/embed", embed, HttpMethod::GET)
+///             .with(SecurityHeaders::strict().frame_options(None).content_security_policy("frame-ancestors https://partner.example"))
+///     ))
+///     .start();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SecurityHeaders {
+    headers: Vec<Header>,
+}
+
+#[allow(unused)]
+impl SecurityHeaders {
+    /// Creates the middleware without any header
+    pub fn new() -> SecurityHeaders {
+        SecurityHeaders {
+            headers: Vec::new(),
+        }
+    }
+
+    /// Headers for HTML pages: HSTS for two years, a restrictive policy allowing scripts and styles of the same origin
+    /// and with the request nonce, no framing, no referrer, disabled powerful features and cross-origin isolation
+    pub fn strict() -> SecurityHeaders {
+        SecurityHeaders::new()
+            .hsts(Duration::from_secs(2 * 365 * 24 * 60 * 60), true, false)
+            .content_security_policy(
+                "default-src 'self'; script-src 'self' 'nonce-{nonce}'; style-src 'self' 'nonce-{nonce}'; \
+                 img-src 'self' data:; object-src 'none'; base-uri 'self'; form-action 'self'; frame-ancestors 'none'",
+            )
+            .content_type_options(true)
+            .frame_options(Some(FrameOptions::Deny))
+            .referrer_policy("no-referrer")
+            .permissions_policy("camera=(), microphone=(), geolocation=(), payment=(), usb=()")
+            .cross_origin_opener_policy("same-origin")
+            .cross_origin_embedder_policy("require-corp")
+            .cross_origin_resource_policy("same-origin")
+    }
+
+    /// Headers for APIs: HSTS for two years, a policy forbidding any content and framing, and no referrer
+    pub fn api() -> SecurityHeaders {
+        SecurityHeaders::new()
+            .hsts(Duration::from_secs(2 * 365 * 24 * 60 * 60), true, false)
+            .content_security_policy("default-src 'none'; frame-ancestors 'none'")
+            .content_type_options(true)
+            .frame_options(Some(FrameOptions::Deny))
+            .referrer_policy("no-referrer")
+            .cross_origin_resource_policy("same-origin")
+    }
+
+    /// Sets `Strict-Transport-Security`
+    /// # Arguments
+    /// * `max_age` - How long browsers only use HTTPS for the host : Duration
+    /// * `include_subdomains` - Whether the subdomains are included : bool
+    /// * `preload` - Whether the host may be added to the preload lists of browsers : bool
+    /// # Returns
+    /// * `SecurityHeaders` - The middleware
+    pub fn hsts(
+        self,
+        max_age: Duration,
+        include_subdomains: bool,
+        preload: bool,
+    ) -> SecurityHeaders {
+        let mut value = format!("max-age={}", max_age.as_secs());
+        if include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if preload {
+            value.push_str("; preload");
+        }
+        self.header("Strict-Transport-Security", Some(&value))
+    }
+
+    /// Sets `Content-Security-Policy`, `{nonce}` is replaced with the nonce of the request
+    /// # Arguments
+    /// * `policy` - The policy, e.g. `default-src 'self'; script-src 'self' 'nonce-{nonce}'` : &str
+    /// # Returns
+    /// * `SecurityHeaders` - The middleware
+    pub fn content_security_policy(self, policy: &str) -> SecurityHeaders {
+        self.header("Content-Security-Policy", Some(policy))
+    }
+
+    /// Sets whether `X-Content-Type-Options: nosniff` is sent
+    pub fn content_type_options(self, nosniff: bool) -> SecurityHeaders {
+        self.header("X-Content-Type-Options", nosniff.then_some("nosniff"))
+    }
+
+    /// Sets `X-Frame-Options`, `None` to not send it. Use `frame-ancestors` in the policy for finer control
+    pub fn frame_options(self, options: Option<FrameOptions>) -> SecurityHeaders {
+        let value = options.map(|options| match options {
+            FrameOptions::Deny => "DENY",
+            FrameOptions::SameOrigin => "SAMEORIGIN",
+        });
+        self.header("X-Frame-Options", value)
+    }
+
+    /// Sets `Referrer-Policy`, e.g. `strict-origin-when-cross-origin`
+    pub fn referrer_policy(self, policy: &str) -> SecurityHeaders {
+        self.header("Referrer-Policy", Some(policy))
+    }
+
+    /// Sets `Permissions-Policy`, e.g. `camera=(), geolocation=(self)`
+    pub fn permissions_policy(self, policy: &str) -> SecurityHeaders {
+        self.header("Permissions-Policy", Some(policy))
+    }
+
+    /// Sets `Cross-Origin-Opener-Policy`, e.g. `same-origin`
+    pub fn cross_origin_opener_policy(self, policy: &str) -> SecurityHeaders {
+        self.header("Cross-Origin-Opener-Policy", Some(policy))
+    }
+
+    /// Sets `Cross-Origin-Embedder-Policy`, e.g. `require-corp` or `credentialless`
+    pub fn cross_origin_embedder_policy(self, policy: &str) -> SecurityHeaders {
+        self.header("Cross-Origin-Embedder-Policy", Some(policy))
+    }
+
+    /// Sets `Cross-Origin-Resource-Policy`, e.g. `same-origin` or `cross-origin`
+    pub fn cross_origin_resource_policy(self, policy: &str) -> SecurityHeaders {
+        self.header("Cross-Origin-Resource-Policy", Some(policy))
+    }
+
+    /// Sets any header, replacing the value set before
+    /// # Arguments
+    /// * `name` - The header name : &str
+    /// * `value` - The value, `None` to not send the header : Option<&str>
+    /// # Returns
+    /// * `SecurityHeaders` - The middleware
+    pub fn header(mut self, name: &str, value: Option<&str>) -> SecurityHeaders {
+        self.headers
+            .retain(|header| !header.key.eq_ignore_ascii_case(name));
+        if let Some(value) = value {
+            self.headers
+                .push(Header::new(name.to_string(), value.to_string()));
+        }
+        self
+    }
+
+    /// Returns `true` if a header value contains the nonce placeholder
+    fn uses_nonce(&self) -> bool {
+        self.headers
+            .iter()
+            .any(|header| header.value.contains(NONCE_PLACEHOLDER))
+    }
+}
+
+/// Generates a random nonce (128 bits, hex encoded)
+fn generate_nonce() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("Unable to generate CSP nonce");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Middleware for SecurityHeaders {
+    fn handle(&self, mut request: Request, next: Next) -> Response {
+        let nonce = match request.extensions.get::<CspNonce>() {
+            Some(CspNonce(nonce)) => Some(nonce.clone()),
+            None if self.uses_nonce() => {
+                let nonce = generate_nonce();
+                request.extensions.insert(CspNonce(nonce.clone()));
+                Some(nonce)
+            }
+            None => None,
+        };
+        let mut response = next.run(request);
+        if response.extensions.contains::<SecurityHeadersApplied>() {
+            return response;
+        }
+        for header in &self.headers {
+            if response.get_header(&header.key).is_some() {
+                continue;
+            }
+            let value = match &nonce {
+                Some(nonce) => header.value.replace(NONCE_PLACEHOLDER, nonce),
+                None => header.value.clone(),
+            };
+            response.add_header(Header::new(header.key.clone(), value));
+        }
+        response.extensions.insert(SecurityHeadersApplied);
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::http::http_methods::HttpMethod;
+
+    fn run(
+        middlewares: &[Arc<dyn Middleware>],
+        endpoint: &dyn Fn(Request) -> Response,
+    ) -> Response {
+        Next::new(middlewares, endpoint).run(Request::test(HttpMethod::GET, "/", &[]))
+    }
+
+    fn value(response: &Response, key: &str) -> Option<String> {
+        response
+            .get_header(key)
+            .map(|header| header.value().to_string())
+    }
+
+    /// Returns the nonce the handler received in its body
+    fn nonce_page(request: Request) -> Response {
+        let mut response = Response::get_empty();
+        let nonce = request.extensions.get::<CspNonce>().cloned();
+        response.set_data(nonce.map(|CspNonce(nonce)| nonce).unwrap_or_default());
+        response
+    }
+
+    #[test]
+    fn sets_preset_headers() {
+        let response = run(&[Arc::new(SecurityHeaders::strict())], &nonce_page);
+        assert_eq!(
+            value(&response, "Strict-Transport-Security").as_deref(),
+            Some("max-age=63072000; includeSubDomains")
+        );
+        assert_eq!(
+            value(&response, "X-Content-Type-Options").as_deref(),
+            Some("nosniff")
+        );
+        assert_eq!(value(&response, "X-Frame-Options").as_deref(), Some("DENY"));
+        assert_eq!(
+            value(&response, "Referrer-Policy").as_deref(),
+            Some("no-referrer")
+        );
+        assert_eq!(
+            value(&response, "Cross-Origin-Embedder-Policy").as_deref(),
+            Some("require-corp")
+        );
+        assert!(value(&response, "Permissions-Policy").is_some());
+
+        let response = run(&[Arc::new(SecurityHeaders::api())], &nonce_page);
+        assert_eq!(
+            value(&response, "Content-Security-Policy").as_deref(),
+            Some("default-src 'none'; frame-ancestors 'none'")
+        );
+        assert!(value(&response, "Permissions-Policy").is_none());
+        assert!(response.data.is_empty());
+    }
+
+    #[test]
+    fn replaces_nonce_placeholder_with_request_nonce() {
+        let first = run(&[Arc::new(SecurityHeaders::strict())], &nonce_page);
+        let second = run(&[Arc::new(SecurityHeaders::strict())], &nonce_page);
+        let nonce = String::from_utf8(first.data.clone()).unwrap();
+        assert_eq!(nonce.len(), 32);
+        assert!(nonce.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(first.data, second.data);
+        let policy = value(&first, "Content-Security-Policy").unwrap();
+        assert!(policy.contains(&format!("script-src 'self' 'nonce-{}'", nonce)));
+        assert!(!policy.contains(NONCE_PLACEHOLDER));
+    }
+
+    #[test]
+    fn keeps_handler_headers_and_removed_headers() {
+        let headers = SecurityHeaders::strict()
+            .frame_options(None)
+            .referrer_policy("same-origin")
+            .hsts(Duration::from_secs(60), false, true);
+        let response = run(&[Arc::new(headers)], &|_| {
+            let mut response = Response::get_empty();
+            response.add_header(Header::new(
+                "X-Content-Type-Options".to_string(),
+                "custom".to_string(),
+            ));
+            response
+        });
+        assert!(value(&response, "X-Frame-Options").is_none());
+        assert_eq!(
+            value(&response, "Referrer-Policy").as_deref(),
+            Some("same-origin")
+        );
+        assert_eq!(
+            value(&response, "Strict-Transport-Security").as_deref(),
+            Some("max-age=60; preload")
+        );
+        let nosniff: Vec<&Header> = response
+            .headers
+            .iter()
+            .filter(|header| header.key == "X-Content-Type-Options")
+            .collect();
+        assert_eq!(nosniff.len(), 1);
+        assert_eq!(nosniff[0].value(), "custom");
+    }
+
+    #[test]
+    fn route_headers_override_server_headers_and_share_nonce() {
+        let server: Arc<dyn Middleware> = Arc::new(SecurityHeaders::strict());
+        let route: Arc<dyn Middleware> =
+            Arc::new(SecurityHeaders::new().content_security_policy("script-src 'nonce-{nonce}'"));
+        let response = run(&[server, route], &nonce_page);
+        let nonce = String::from_utf8(response.data.clone()).unwrap();
+        assert_eq!(
+            value(&response, "Content-Security-Policy"),
+            Some(format!("script-src 'nonce-{}'", nonce))
+        );
+        assert!(value(&response, "X-Frame-Options").is_none());
+        assert!(value(&response, "Strict-Transport-Security").is_none());
+    }
+}
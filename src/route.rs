@@ -1,14 +1,24 @@
-use crate::{HttpMethod, Request, Response};
+use crate::{
+    guard::Guard,
+    handler::Handler,
+    http::http_codes::StatusCode,
+    middleware::{Middleware, Next},
+    utils::url::percent_decode,
+    HttpMethod, Request, Response,
+};
 
-use std::fmt::Display;
+use std::{fmt::Display, sync::Arc};
 
 /// Represents a route
 ///
 /// # Arguments
-/// * `path` - The path of the route
-/// * `handler` - The handler of the route
+/// * `path` - The path of the route, segments starting with `:` are path variables, e.g. `/users/:id`.
+///   A trailing `*` segment matches the rest of the path, which is passed as the last path variable, e.g. `/files/*`
+/// * `handler` - The handler of the route, any function whose arguments implement `FromRequest`
 /// * `method` - The method of the route
 ///
+/// Middlewares and guards can be attached to a single route with `with` and `guard`.
+///
 /// # Example
 /// ```
 /// use http::http_methods::HttpMethod;
@@ -17,6 +27,11 @@ use std::fmt::Display;
 /// use route::Route;
 ///
 /// let route = Route::new("/", index, HttpMethod::GET);
+/// let api = Route::new("/api", api, HttpMethod::POST)
+///     .with(RequireToken)
+///     .guard(content_type(ContentType::ApplicationJson));
+///
+/// let user = Route::new("/users/:id", get_user, HttpMethod::GET);
 ///
 /// fn index(request: Request) -> Response {
 ///     Response::new(
@@ -27,27 +42,105 @@ use std::fmt::Display;
 ///         fs::read_to_string("./static/index.html").unwrap(),
 ///     )
 /// }
+///
+/// fn get_user(Path(id): Path<u64>, State(db): State<Database>) -> impl IntoResponse {
+///     Json(db.find_user(id))
+/// }
 /// ```
 pub struct Route {
     pub path: String,
     pub handler: Box<dyn Fn(Request) -> Response + Send + Sync + 'static>,
     pub method: HttpMethod,
+    pub middlewares: Vec<Arc<dyn Middleware>>,
+    pub guards: Vec<Arc<dyn Guard>>,
 }
 
+#[allow(unused)]
 impl Route {
-    pub fn new(
-        path: &str,
-        handler: impl Fn(Request) -> Response + Send + Sync + 'static,
-        method: HttpMethod,
-    ) -> Route {
+    pub fn new<Args>(path: &str, handler: impl Handler<Args>, method: HttpMethod) -> Route {
         Route {
             path: path.to_string(),
-            handler: Box::new(handler),
+            handler: Box::new(move |request| handler.call(request)),
             method,
+            middlewares: Vec::new(),
+            guards: Vec::new(),
+        }
+    }
+
+    /// Adds a middleware to the route. It is called after the middlewares of the server and the route group.
+    /// # Arguments
+    /// * `middleware` - The middleware to add : impl Middleware
+    /// # Returns
+    /// * `Route` - The route
+    pub fn with(mut self, middleware: impl Middleware + 'static) -> Route {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Adds a guard to the route. The route is only called if all of its guards accept the request.
+    /// # Arguments
+    /// * `guard` - The guard to add : impl Guard
+    /// # Returns
+    /// * `Route` - The route
+    pub fn guard(mut self, guard: impl Guard + 'static) -> Route {
+        self.guards.push(Arc::new(guard));
+        self
+    }
+
+    /// Matches a request path against the path of the route
+    /// # Arguments
+    /// * `path` - The request path without query string : &str
+    /// # Returns
+    /// * `Option<Vec<String>>` - The decoded path variables, or `None` if the path does not match
+    pub fn matches(&self, path: &str) -> Option<Vec<String>> {
+        let mut pattern = self.path.split('/').peekable();
+        let mut segments = path.split('/');
+        let mut variables = Vec::new();
+        loop {
+            match (pattern.next(), segments.next()) {
+                (None, None) => return Some(variables),
+                (Some("*"), segment) if pattern.peek().is_none() => {
+                    let rest = segment
+                        .into_iter()
+                        .chain(segments)
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    variables.push(percent_decode(&rest));
+                    return Some(variables);
+                }
+                (Some(expected), Some(segment)) => {
+                    if expected.starts_with(':') {
+                        if segment.is_empty() {
+                            return None;
+                        }
+                        variables.push(percent_decode(segment));
+                    } else if expected != segment {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
         }
     }
+
+    /// Checks the guards of the route
+    /// # Arguments
+    /// * `request` - The request : &Request
+    /// # Returns
+    /// * `Result<(), StatusCode>` - The rejection status of the first guard rejecting the request
+    pub fn check(&self, request: &Request) -> Result<(), StatusCode> {
+        match self.guards.iter().find(|guard| !guard.check(request)) {
+            Some(guard) => Err(guard.rejection()),
+            None => Ok(()),
+        }
+    }
+    /// Calls the handler of the route through the middlewares of the route
+    /// # Arguments
+    /// * `data` - The request : Request
+    /// # Returns
+    /// * `Response` - The response of the handler
     pub fn call(&self, data: Request) -> Response {
-        (self.handler)(data)
+        Next::new(&self.middlewares, &self.handler).run(data)
     }
 }
 
@@ -56,3 +149,131 @@ impl Display for Route {
         write!(f, "{} - {} - <function>", self.method, self.path)
     }
 }
+
+/// Represents a group of routes sharing a path prefix and middlewares
+/// <br>
+/// The middlewares of the group are called in the order they were added, after the middlewares of the server.
+///
+/// # Arguments
+/// * `prefix` - The path prefix of the routes
+///
+/// # Example
+/// ```
+/// let api = RouteGroup::new("/api")
+///     .with(RequireToken)
+///     .route(Route::new("/users", users, HttpMethod::GET))
+///     .route(Route::new("/users", create_user, HttpMethod::POST));
+/// ```
+pub struct RouteGroup {
+    pub prefix: String,
+    pub middlewares: Vec<Arc<dyn Middleware>>,
+    pub routes: Vec<Route>,
+}
+
+#[allow(unused)]
+impl RouteGroup {
+    pub fn new(prefix: &str) -> RouteGroup {
+        RouteGroup {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            middlewares: Vec::new(),
+            routes: Vec::new(),
+        }
+    }
+
+    /// Adds a middleware to the group
+    /// # Arguments
+    /// * `middleware` - The middleware to add : impl Middleware
+    /// # Returns
+    /// * `RouteGroup` - The group
+    pub fn with(mut self, middleware: impl Middleware + 'static) -> RouteGroup {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Adds a route to the group
+    /// # Arguments
+    /// * `route` - The route to add, its path is relative to the prefix of the group : Route
+    /// # Returns
+    /// * `RouteGroup` - The group
+    pub fn route(mut self, route: Route) -> RouteGroup {
+        self.routes.push(route);
+        self
+    }
+
+    /// Consumes the group, returning its routes with the prefix and middlewares of the group applied
+    /// # Returns
+    /// * `Vec<Route>` - The routes of the group
+    pub fn into_routes(self) -> Vec<Route> {
+        let prefix = self.prefix;
+        let middlewares = self.middlewares;
+        self.routes
+            .into_iter()
+            .map(|mut route| {
+                route.path = match route.path.as_str() {
+                    "/" if !prefix.is_empty() => prefix.clone(),
+                    path => prefix.clone() + path,
+                };
+                let mut chain = middlewares.clone();
+                chain.append(&mut route.middlewares);
+                route.middlewares = chain;
+                route
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(path: &str) -> Route {
+        Route::new(path, |_: Request| Response::get_empty(), HttpMethod::GET)
+    }
+
+    #[test]
+    fn matches_path_variables_and_wildcards() {
+        assert_eq!(
+            route("/users/:id").matches("/users/4%202"),
+            Some(vec!["4 2".to_string()])
+        );
+        assert_eq!(route("/users/:id").matches("/users/"), None);
+        assert_eq!(route("/users/:id").matches("/users/4/posts"), None);
+        assert_eq!(
+            route("/files/*").matches("/files/a/b%20c.txt"),
+            Some(vec!["a/b c.txt".to_string()])
+        );
+        assert_eq!(route("/files/*").matches("/other/a"), None);
+    }
+
+    #[test]
+    fn rejects_with_status_of_first_failing_guard() {
+        let route = route("/")
+            .guard(|_: &Request| true)
+            .guard(crate::guard::host("example.com"))
+            .guard(|_: &Request| false);
+        let request = Request::test(HttpMethod::GET, "/", &[("Host", "other.com")]);
+        assert_eq!(route.check(&request), Err(StatusCode::NotFound));
+        let request = Request::test(HttpMethod::GET, "/", &[("Host", "example.com")]);
+        assert_eq!(route.check(&request), Err(StatusCode::BadRequest));
+    }
+
+    #[test]
+    fn applies_group_prefix_and_middlewares_before_route_middlewares() {
+        let tag = |name: &'static str| {
+            move |request: Request, next: Next| {
+                let mut response = next.run(request);
+                response.data.extend_from_slice(name.as_bytes());
+                response
+            }
+        };
+        let routes = RouteGroup::new("/api/")
+            .with(tag("group"))
+            .route(route("/").with(tag("route,")))
+            .route(route("/users"))
+            .into_routes();
+        assert_eq!(routes[0].path, "/api");
+        assert_eq!(routes[1].path, "/api/users");
+        let response = routes[0].call(Request::test(HttpMethod::GET, "/api", &[]));
+        assert_eq!(response.data, b"route,group");
+    }
+}
@@ -1,19 +1,27 @@
 use std::{
-    io::{BufRead, BufReader, Write},
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufReader, Read, Write},
     net::{SocketAddrV4, TcpListener, TcpStream},
-    sync::OnceLock,
+    sync::{Arc, OnceLock},
 };
 
-use regex::Regex;
-
 use crate::{
-    http::{http_codes::*, http_methods::*},
-    route::Route,
+    error::{DefaultErrorRenderer, Error, ErrorFormat, ErrorRenderer},
+    handler::{Handler, IntoResponse},
+    http::http_codes::StatusCode,
+    http::http_content_types::ContentType,
+    http::http_encodings::{decode_content, ContentEncoding},
+    http::http_headers::Header,
+    http::http_methods::*,
+    middleware::{Middleware, Next},
+    route::{Route, RouteGroup},
+    static_files::StaticFiles,
     thread_pool::ThreadPool,
-    utils::logger::Logger,
+    transmitters::extensions::Extensions,
+    utils::{logger::Logger, panic},
     LogLevel, Request, Response,
 };
-use std::sync::RwLock;
 
 static LOG_LEVEL: OnceLock<LogLevel> = OnceLock::new();
 
@@ -30,229 +38,838 @@ pub fn get_log_level() -> LogLevel {
     LOG_LEVEL.get_or_init(|| LogLevel::Debug).clone()
 }
 
-lazy_static::lazy_static! {
-    static ref ROUTES: RwLock<Vec<Route>> = RwLock::new(Vec::new());
+type Fallback = Box<dyn Fn(Request) -> Response + Send + Sync>;
+
+/// The routes, middlewares, application state and error handling shared by the worker threads
+struct App {
+    routes: Vec<Route>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    state: Arc<Extensions>,
+    error_renderer: Box<dyn ErrorRenderer>,
+    fallbacks: HashMap<u16, Fallback>,
+    error_pages: HashMap<u16, String>,
+    max_body_size: usize,
+    max_decompressed_size: usize,
 }
 
-#[allow(unused)]
-/// Starts the server
+/// # Server
 /// Will listen to new connections coming to the specified address and port.
 /// <br>
-/// If the PreRequest and PreResponse handlers are provided, they will be used to handle the requests and responses before processing them further.  Tey provide more control over the flow of the server.
+/// Every request is passed through the middlewares of the server in the order they were added before it is routed.
+/// Middlewares provide more control over the flow of the server: they can modify the request, return early or transform the response.
+/// <br>
+/// Application state, such as a database pool or configuration, is registered with `with_state` and available to handlers and middlewares through `Request::state`.
+/// <br>
+/// Errors returned by handlers, middlewares or the server itself are rendered by the fallback handler for their status (`not_found`, `method_not_allowed`, `internal_error`),
+/// otherwise by a static error page (`error_pages`) or finally by the error renderer, `DefaultErrorRenderer` unless set with `error_renderer`.
 ///
 /// # Arguments
-/// * `routes` - The routes to use : Vec<Route>
 /// * `address` - The address to listen on : &str
 /// * `port` - The port to listen on : u32
-/// * `threads` - The number of threads to use : usize
-/// * `log_level` - The log level to use : LogLevel
-/// * `pre_request` - The pre request handler to use : Option<PreRequestHandler>
-/// * `pre_response` - The pre response handler to use : Option<PreResponseHandler>
 ///
 /// # Example
 /// ``` rust
-/// server::start(
-///        add_routes!(
-///            Route::new("/", index, HttpMethod::GET),
-///            Route::new("", appliances, HttpMethod::GET),
-///            Route::new("/sleep", sleep, HttpMethod::OPTIONS),
-///            Route::new("/sleep", sleep, HttpMethod::GET),
-///            Route::new("/options", options, HttpMethod::OPTIONS)
-///        ),
-///        "127.0.0.1",
-///        8000,
-///        10,
-///        LogLevel::Info,
-///        Some(PreRequestHandler),
-///        Some(PreResponseHandler),
-///    );
+/// Server::new("127.0.0.1", 8000)
+///     .threads(10)
+///     .log_level(LogLevel::Info)
+///     .with(RequestLogger)
+///     .with_state(Arc::new(Database::connect()))
+///     .routes(add_routes!(
+///         Route::new("/", index, HttpMethod::GET),
+///         Route::new("/sleep", sleep, HttpMethod::GET)
+///     ))
+///     .mount("/", StaticFiles::new("./static"))
+///     .group(RouteGroup::new("/api").with(RequireToken).route(Route::new("/users", users, HttpMethod::GET)))
+///     .start();
 /// ```
-pub fn start(
-    routes: Vec<Route>,
-    address: &str,
-    mut port: u32,
+pub struct Server {
+    address: String,
+    port: u32,
     threads: usize,
     log_level: LogLevel,
-    pre_request: Option<PreRequestHandler>,
-    pre_response: Option<PreResponseHandler>,
-) {
-    set_log_level(log_level);
+    routes: Vec<Route>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    state: Extensions,
+    error_renderer: Box<dyn ErrorRenderer>,
+    fallbacks: HashMap<u16, Fallback>,
+    error_pages_directory: Option<String>,
+    max_body_size: usize,
+    max_decompressed_size: usize,
+}
+
+#[allow(unused)]
+impl Server {
+    pub fn new(address: &str, port: u32) -> Server {
+        Server {
+            address: address.to_string(),
+            port,
+            threads: 10,
+            log_level: LogLevel::Info,
+            routes: Vec::new(),
+            middlewares: Vec::new(),
+            state: Extensions::new(),
+            error_renderer: Box::new(DefaultErrorRenderer),
+            fallbacks: HashMap::new(),
+            error_pages_directory: None,
+            max_body_size: 10 * 1024 * 1024,
+            max_decompressed_size: 10 * 1024 * 1024,
+        }
+    }
+
+    /// Sets the number of threads to use
+    /// # Arguments
+    /// * `threads` - The number of threads : usize
+    /// # Returns
+    /// * `Server` - The server
+    pub fn threads(mut self, threads: usize) -> Server {
+        self.threads = threads;
+        self
+    }
+
+    /// Sets the log level to use
+    /// # Arguments
+    /// * `log_level` - The log level : LogLevel
+    /// # Returns
+    /// * `Server` - The server
+    pub fn log_level(mut self, log_level: LogLevel) -> Server {
+        self.log_level = log_level;
+        self
+    }
+
+    /// Adds routes to the server
+    /// # Arguments
+    /// * `routes` - The routes to add : Vec<Route>
+    /// # Returns
+    /// * `Server` - The server
+    pub fn routes(mut self, routes: Vec<Route>) -> Server {
+        self.routes.extend(routes);
+        self
+    }
+
+    /// Adds a group of routes to the server
+    /// # Arguments
+    /// * `group` - The group to add : RouteGroup
+    /// # Returns
+    /// * `Server` - The server
+    pub fn group(mut self, group: RouteGroup) -> Server {
+        self.routes.extend(group.into_routes());
+        self
+    }
+
+    /// Serves the files of a directory under a path prefix, for `GET` and `HEAD` requests
+    /// <br>
+    /// Routes are matched in the order they were added, so routes under the same prefix must be added before.
+    /// # Arguments
+    /// * `prefix` - The path prefix, e.g. `/assets` : &str
+    /// * `files` - The static files : StaticFiles
+    /// # Returns
+    /// * `Server` - The server
+    pub fn mount(mut self, prefix: &str, files: StaticFiles) -> Server {
+        let path = prefix.trim_end_matches('/').to_string() + "/*";
+        let files = Arc::new(files);
+        for method in [HttpMethod::GET, HttpMethod::HEAD] {
+            let files = Arc::clone(&files);
+            self.routes.push(Route::new(
+                &path,
+                move |request: Request| {
+                    let relative = request.path_variables.last().cloned().unwrap_or_default();
+                    files.serve(&relative, &request)
+                },
+                method,
+            ));
+        }
+        self
+    }
+
+    /// Adds a middleware to the server. Middlewares are called in the order they were added.
+    /// # Arguments
+    /// * `middleware` - The middleware to add : impl Middleware
+    /// # Returns
+    /// * `Server` - The server
+    pub fn with(mut self, middleware: impl Middleware + 'static) -> Server {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Registers application state. Only one value is kept per type.
+    /// # Arguments
+    /// * `state` - The state to share with handlers and middlewares : Arc<T>
+    /// # Returns
+    /// * `Server` - The server
+    pub fn with_state<T: Send + Sync + 'static>(mut self, state: Arc<T>) -> Server {
+        self.state.insert(state);
+        self
+    }
+
+    /// Sets the renderer used to turn errors into responses
+    /// # Arguments
+    /// * `renderer` - The error renderer : impl ErrorRenderer
+    /// # Returns
+    /// * `Server` - The server
+    pub fn error_renderer(mut self, renderer: impl ErrorRenderer + 'static) -> Server {
+        self.error_renderer = Box::new(renderer);
+        self
+    }
+
+    /// Sets the handler called when no route matches the request
+    /// <br>
+    /// The handler receives the method, path and headers of the request, the `Error` is available in its extensions.
+    /// # Arguments
+    /// * `handler` - The handler : impl Handler<Args>
+    /// # Returns
+    /// * `Server` - The server
+    pub fn not_found<Args>(self, handler: impl Handler<Args>) -> Server {
+        self.fallback(StatusCode::NotFound, handler)
+    }
+
+    /// Sets the handler called when the path of the request is only mapped for other methods
+    /// # Arguments
+    /// * `handler` - The handler : impl Handler<Args>
+    /// # Returns
+    /// * `Server` - The server
+    pub fn method_not_allowed<Args>(self, handler: impl Handler<Args>) -> Server {
+        self.fallback(StatusCode::MethodNotAllowed, handler)
+    }
+
+    /// Sets the handler called on internal errors, including panicking handlers
+    /// # Arguments
+    /// * `handler` - The handler : impl Handler<Args>
+    /// # Returns
+    /// * `Server` - The server
+    pub fn internal_error<Args>(self, handler: impl Handler<Args>) -> Server {
+        self.fallback(StatusCode::InternalServerError, handler)
+    }
+
+    /// Sets the handler called for errors with the given status
+    /// <br>
+    /// If the handler itself responds with an error, the error is rendered as if no handler was set.
+    /// # Arguments
+    /// * `status` - The status of the errors : StatusCode
+    /// * `handler` - The handler : impl Handler<Args>
+    /// # Returns
+    /// * `Server` - The server
+    pub fn fallback<Args>(mut self, status: StatusCode, handler: impl Handler<Args>) -> Server {
+        self.fallbacks.insert(
+            status as u16,
+            Box::new(move |request| handler.call(request)),
+        );
+        self
+    }
+
+    /// Sets the directory of the static error pages
+    /// <br>
+    /// Pages are named after their status, e.g. `404.html`, and loaded when the server starts.
+    /// They are sent for errors without a fallback handler, unless the client asks for JSON.
+    /// # Arguments
+    /// * `directory` - The directory of the error pages : &str
+    /// # Returns
+    /// * `Server` - The server
+    pub fn error_pages(mut self, directory: &str) -> Server {
+        self.error_pages_directory = Some(directory.to_string());
+        self
+    }
+
+    /// Sets the maximum accepted request body size, larger requests are answered with `413 Payload Too Large`
+    /// # Arguments
+    /// * `max_body_size` - The maximum size in bytes : usize
+    /// # Returns
+    /// * `Server` - The server
+    pub fn max_body_size(mut self, max_body_size: usize) -> Server {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Sets the maximum size of request bodies after decoding their `Content-Encoding`, larger bodies are answered with
+    /// `413 Payload Too Large`. Guards against small compressed bodies expanding to huge ones
+    /// # Arguments
+    /// * `max_decompressed_size` - The maximum size in bytes : usize
+    /// # Returns
+    /// * `Server` - The server
+    pub fn max_decompressed_size(mut self, max_decompressed_size: usize) -> Server {
+        self.max_decompressed_size = max_decompressed_size;
+        self
+    }
+
+    /// Starts the server
+    /// <br>
+    /// Blocks the current thread while accepting connections.
+    pub fn start(self) {
+        set_log_level(self.log_level);
+        panic::install_hook();
+        let logger: Logger = Logger {
+            c_name: "SERVER",
+            level: get_log_level(),
+        };
+        let mut port = self.port;
+        if port > 65535 || port == 0 {
+            port = 8080;
+        }
+
+        let address: SocketAddrV4 =
+            SocketAddrV4::new(self.address.parse().unwrap(), port.try_into().unwrap());
+        let listener = TcpListener::bind(address).expect("Unable to start server");
+        let thread_pool = ThreadPool::new(self.threads);
+        let app = Arc::new(App {
+            routes: self.routes,
+            middlewares: self.middlewares,
+            state: Arc::new(self.state),
+            error_renderer: self.error_renderer,
+            fallbacks: self.fallbacks,
+            error_pages: match &self.error_pages_directory {
+                Some(directory) => load_error_pages(directory),
+                None => HashMap::new(),
+            },
+            max_body_size: self.max_body_size,
+            max_decompressed_size: self.max_decompressed_size,
+        });
+
+        logger.info(&[
+            "Server started",
+            "on",
+            ("http://".to_string() + &listener.local_addr().unwrap().to_string()).as_str(),
+        ]);
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let app = Arc::clone(&app);
+                    thread_pool.execute(move || {
+                        handle_connection(stream, app);
+                    });
+                }
+                Err(e) => {
+                    logger.error(e.to_string().as_str(), &["Unable to connect to client"]);
+                }
+            }
+        }
+    }
+}
+
+/// Loads the error pages named after their status, e.g. `404.html`, from a directory
+/// # Arguments
+/// * `directory` - The directory of the error pages : &str
+/// # Returns
+/// * `HashMap<u16, String>` - The pages by status
+fn load_error_pages(directory: &str) -> HashMap<u16, String> {
     let logger: Logger = Logger {
         c_name: "SERVER",
         level: get_log_level(),
     };
-    if port > 65535 || port <= 0 {
-        port = 8080;
-    }
-    ROUTES.write().unwrap().extend(routes);
-
-    let address: SocketAddrV4 =
-        SocketAddrV4::new(address.parse().unwrap(), port.try_into().unwrap());
-    let listener = TcpListener::bind(address).expect("Unable to start server");
-    let thread_pool = ThreadPool::new(threads as usize);
-
-    logger.info(&[
-        "Server started",
-        "on",
-        ("http://".to_string() + &listener.local_addr().unwrap().to_string()).as_str(),
-    ]);
-    for stream in listener.incoming() {
-        let pre_request_clone = pre_request.clone();
-        let pre_response_clone = pre_response.clone();
-        match stream {
-            Ok(stream) => {
-                thread_pool.execute(|| {
-                    handle_connection(stream, pre_request_clone, pre_response_clone);
-                });
+    let mut pages = HashMap::new();
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            logger.error(
+                e.to_string().as_str(),
+                &["Unable to read error pages", directory],
+            );
+            return pages;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let status = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".html"))
+            .and_then(|name| name.parse::<u16>().ok());
+        if let Some(status) = status {
+            match fs::read_to_string(&path) {
+                Ok(page) => {
+                    pages.insert(status, page);
+                }
+                Err(e) => logger.error(e.to_string().as_str(), &["Unable to read error page"]),
             }
-            Err(e) => {
-                logger.error(e.to_string().as_str(), &["Unable to connect to client"]);
+        }
+    }
+    pages
+}
+
+/// Searches for the appropriate Route mapping and calls it
+/// <br>
+/// Routes whose guards reject the request are skipped. If there is no mapping found, it will return a `404` response,
+/// a `405` response with an `Allow` header if the path is mapped for other methods only,
+/// or the rejection status of the first failing guard if a mapping was skipped.
+/// # Arguments
+/// * `app` - The routes of the server : &App
+/// * `request` - The request to route : Request
+/// # Returns
+/// * `Response` - The response of the route
+fn dispatch(app: &App, mut request: Request) -> Response {
+    let mut rejection: Option<StatusCode> = None;
+    let mut allowed: Vec<HttpMethod> = Vec::new();
+    for route in app.routes.iter() {
+        let variables = match route.matches(&request.path) {
+            Some(variables) => variables,
+            None => continue,
+        };
+        if route.method != request.method {
+            if !allowed.contains(&route.method) {
+                allowed.push(route.method);
             }
+            continue;
         }
+        request.set_path_variables(variables);
+        match route.check(&request) {
+            Ok(_) => return route.call(request),
+            Err(status) => {
+                rejection.get_or_insert(status);
+            }
+        }
+    }
+    if rejection.is_none() && !allowed.is_empty() {
+        let mut response = Error::from_status(StatusCode::MethodNotAllowed).into_response();
+        response.add_header(Header::new(
+            "Allow".to_string(),
+            allowed
+                .iter()
+                .map(|method| method.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ));
+        return response;
     }
+    Error::from_status(rejection.unwrap_or(StatusCode::NotFound)).into_response()
 }
-/// Submits a request to the client
+
+/// Renders the error kept in the response, if any
+/// <br>
+/// The error is rendered by, in order of preference:
+/// <ol>
+/// <li>The fallback handler registered for its status</li>
+/// <li>The error page registered for its status, unless the client asks for JSON</li>
+/// <li>The error renderer of the server</li>
+/// </ol>
+/// Headers and cookies already set on the response, e.g. by middlewares, are kept. Internal causes of server errors are logged.
+/// Errors with a status below `400`, e.g. `304 Not Modified`, are sent without body.
 /// # Arguments
-/// * `stream` - The stream to write to : &TcpStream
-/// * `route` - The route to use : &Route
-/// * `data` - The data to send : Request
-/// * `pre_response` - The pre response handler to use : Option<PreResponseHandler>
+/// * `app` - The error handling configuration of the server : &App
+/// * `request` - The method, path and headers of the request : &Request
+/// * `response` - The response : Response
 /// # Returns
-/// * `Result<(), std::io::Error>`
-
-fn submit(
-    mut stream: &TcpStream,
-    route: &Route,
-    data: Request,
-    pre_response: Option<PreResponseHandler>,
-) -> Result<(), std::io::Error> {
+/// * `Response` - The rendered response
+fn render_error(app: &App, request: &Request, response: Response) -> Response {
     let logger: Logger = Logger {
         c_name: "SERVER",
         level: get_log_level(),
     };
-    let resp = route.call(data);
-    let response = match pre_response {
-        Some(function) => function.call(stream.peer_addr().unwrap().to_string(), resp),
-        None => resp,
+    let error = match response.extensions.get::<Error>() {
+        Some(error) => error,
+        None => return response,
     };
-    let resp = response.prepare();
-    logger.debug(&["Sending response", resp.as_str()]);
-    match stream.write_all(resp.as_bytes()) {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            logger.error(e.to_string().as_str(), &["Unable to write to client"]);
-            Err(e)
-        }
+    let status = error.status();
+    if (status as u16) < 400 {
+        return response;
     }
+    if status as u16 >= 500 {
+        logger.error(
+            error.to_string().as_str(),
+            &["Error handling", request.path.as_str()],
+        );
+    }
+
+    let fallback = app.fallbacks.get(&(status as u16)).and_then(|handler| {
+        let mut context = request.clone();
+        context
+            .extensions
+            .insert(Error::new(status, error.message()));
+        let fallback = match panic::catch(|| handler(context)) {
+            Ok(fallback) => fallback,
+            Err(panic) => {
+                logger.error(
+                    panic.backtrace.as_str(),
+                    &["Fallback handler panicked:", panic.message.as_str()],
+                );
+                return None;
+            }
+        };
+        match fallback.extensions.get::<Error>() {
+            Some(_) => None,
+            None => Some(fallback),
+        }
+    });
+    let mut rendered = match fallback {
+        Some(fallback) => fallback,
+        None => match app.error_pages.get(&(status as u16)) {
+            Some(page) if ErrorFormat::negotiate(request) != ErrorFormat::Json => Response::new(
+                status,
+                ContentType::TextHtmlCharsetUtf8,
+                Vec::new(),
+                Vec::new(),
+                page.clone(),
+            ),
+            _ => app.error_renderer.render(error, request),
+        },
+    };
+    rendered.headers.extend(response.headers.iter().cloned());
+    rendered.cookies.extend(response.cookies.iter().cloned());
+    rendered
 }
 
 /// Handles the connection with the clients. If the client sends the request this function will handle it in following flow:
 /// <ol>
 /// <li>Read request string</li>
 /// <li>Parse the request</li>
-/// <li>Execute if provided PreRequest function</li>
-/// <li>Search for appropriate Route mapping</li>
-/// <li>Call the submit function</li>
+/// <li>Pass the request through the middlewares of the server</li>
+/// <li>Search for appropriate Route mapping and call it through the middlewares of the route</li>
+/// <li>Send the response</li>
 /// </ol>
 ///
-/// If there is no mapping found, it will return a `404` response or if there is an error it will return a `500` response.
+/// If there is no mapping found, it will return a `404` response. If a handler or middleware panics, the panic is logged and a `500` response is returned.
+/// Malformed or too large requests are answered without calling the middlewares.
+/// Errors are rendered by the fallback handlers, error pages or error renderer of the server.
 ///
 /// # Arguments
-/// * `stream` - The stream to write to : &TcpStream
-/// * `pre_request` - The pre request handler to use : Option<PreRequestHandler>
-/// * `pre_response` - The pre response handler to use : Option<PreResponseHandler>
-fn handle_connection(
-    mut stream: TcpStream,
-    pre_request: Option<PreRequestHandler>,
-    pre_response: Option<PreResponseHandler>,
-) {
+/// * `stream` - The stream to write to : TcpStream
+/// * `app` - The routes and middlewares of the server : Arc<App>
+fn handle_connection(mut stream: TcpStream, app: Arc<App>) {
     let logger: Logger = Logger {
         c_name: "SERVER",
         level: get_log_level(),
     };
-    let buf_reader_string = BufReader::new(&mut stream)
-        .lines()
-        .map(|result| result.unwrap())
-        .take_while(|line| !line.is_empty())
-        .collect::<String>();
-
-    let request = match pre_request {
-        Some(function) => function.call(
-            stream.peer_addr().unwrap().to_string(),
-            Request::parse(buf_reader_string),
-        ),
-        None => Request::parse(buf_reader_string),
-    };
+    let peer_addr = stream
+        .peer_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_default();
 
-    let routes = ROUTES.read().unwrap();
-    let appliances_regex = Regex::new(r"(.css|.html|.js|.ico)$").unwrap();
+    let (error_context, response) =
+        match read_request(&mut stream, app.max_body_size, app.max_decompressed_size) {
+            Ok(Some(mut request)) => {
+                request.peer_addr = peer_addr.clone();
+                request.app_state = Arc::clone(&app.state);
+                let error_context = Request::new(
+                    request.method,
+                    request.path.clone(),
+                    Vec::new(),
+                    request.headers.clone(),
+                    Vec::new(),
+                    String::new(),
+                );
 
-    let mut handled_successfully = false;
+                let endpoint = |request: Request| dispatch(&app, request);
+                let response =
+                    match panic::catch(|| Next::new(&app.middlewares, &endpoint).run(request)) {
+                        Ok(response) => response,
+                        Err(panic) => {
+                            logger.error(
+                                panic.backtrace.as_str(),
+                                &["Handler panicked:", panic.message.as_str()],
+                            );
+                            Error::from_status(StatusCode::InternalServerError).into_response()
+                        }
+                    };
+                (error_context, response)
+            }
+            Ok(None) => return,
+            Err(error) => {
+                logger.debug(&["Rejected request from", peer_addr.as_str(), error.message()]);
+                let mut error_context = Request::get_empty();
+                error_context.peer_addr = peer_addr.clone();
+                let unsupported_encoding = error.status() == StatusCode::UnsupportedMediaType;
+                let mut response = error.into_response();
+                if unsupported_encoding {
+                    response.add_header(Header::new(
+                        "Accept-Encoding".to_string(),
+                        ContentEncoding::ALL
+                            .map(|encoding| encoding.name())
+                            .join(", "),
+                    ));
+                }
+                (error_context, response)
+            }
+        };
+    let mut response = render_error(&app, &error_context, response);
 
-    if request.method == HttpMethod::GET && appliances_regex.is_match(request.path.as_str()) {
-        match submit(&stream, &routes[1], request, pre_response.clone()) {
-            Ok(_) => {
-                handled_successfully = true;
+    let head = response.prepare_head();
+    logger.debug(&["Sending response", head.as_str()]);
+    let written = stream.write_all(head.as_bytes()).and_then(|_| {
+        if error_context.method == HttpMethod::HEAD || !response.has_body() {
+            return Ok(());
+        }
+        match response.body.take() {
+            Some(body) => body.write_to(&mut stream),
+            None => stream.write_all(&response.data),
+        }
+    });
+    match written {
+        Ok(_) => {
+            logger.info(&["Response sent", peer_addr.as_str()]);
+        }
+        Err(e) => {
+            logger.error(e.to_string().as_str(), &["Unable to write to client"]);
+        }
+    }
+    let _ = stream.flush();
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+}
+
+/// Maximum size of the request line and headers
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// Reads the request head and, if `Content-Length` is present, the body from the stream
+/// <br>
+/// Bodies with a `Content-Encoding` are decoded, the `Content-Encoding` header is removed and `Content-Length` is set to the decoded size.
+/// The body is passed to the request as read, it is not parsed as part of the head.
+/// # Arguments
+/// * `stream` - The stream to read from : &mut impl Read
+/// * `max_body_size` - The maximum size of the body as sent : usize
+/// * `max_decompressed_size` - The maximum size of the decoded body : usize
+/// # Returns
+/// * `Result<Option<Request>, Error>` - The request, `None` if the client closed the connection without sending one or
+///   an error (`400`, `413`, `415` or `431`) if the request is malformed, too large or uses an unsupported encoding
+fn read_request(
+    stream: &mut impl Read,
+    max_body_size: usize,
+    max_decompressed_size: usize,
+) -> Result<Option<Request>, Error> {
+    let logger: Logger = Logger {
+        c_name: "SERVER",
+        level: get_log_level(),
+    };
+    let mut reader = BufReader::new(stream);
+    let mut payload = String::new();
+    let mut content_length = 0;
+    let mut content_encoding: Option<String> = None;
+    let mut head_size = 0;
+    loop {
+        let mut line = String::new();
+        // Reads at most one byte over the limit, so a line without newline can not grow without bound
+        let limit = (MAX_HEAD_SIZE - head_size + 1) as u64;
+        let read = match (&mut reader).take(limit).read_line(&mut line) {
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                return Err(Error::new(
+                    StatusCode::BadRequest,
+                    "Request is not valid UTF-8",
+                ));
             }
             Err(e) => {
-                logger.error(e.to_string().as_str(), &["Unable to send response"]);
-                stream
-                    .write_all(get_status_line(StatusCode::InternalServerError).as_bytes())
-                    .unwrap();
+                logger.error(e.to_string().as_str(), &["Unable to read request"]);
+                return Ok(None);
             }
+        };
+        if read == 0 && payload.is_empty() {
+            return Ok(None);
         }
-    } else {
-        let mut found = false;
-        for route in routes.iter() {
-            if route.method == request.method && route.path == request.path {
-                match submit(&stream, route, request, pre_response.clone()) {
-                    Ok(_) => {
-                        handled_successfully = true;
-                        found = true;
-                        logger.info(&[
-                            "Response sent",
-                            stream.peer_addr().unwrap().to_string().as_str(),
-                        ]);
-                    }
-                    Err(e) => {
-                        logger.error(e.to_string().as_str(), &["Unable to send response"]);
-
-                        stream
-                            .write_all(get_status_line(StatusCode::InternalServerError).as_bytes())
-                            .unwrap();
-                    }
-                }
-                break;
+        head_size += read;
+        if head_size > MAX_HEAD_SIZE {
+            return Err(Error::from_status(StatusCode::RequestHeaderFieldsTooLarge));
+        }
+        if read == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if payload.is_empty() && line.split_whitespace().count() != 3 {
+            return Err(Error::new(StatusCode::BadRequest, "Malformed request line"));
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            if key.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse().map_err(|_| {
+                    Error::new(StatusCode::BadRequest, "Invalid Content-Length header")
+                })?;
+                continue;
+            }
+            if key.trim().eq_ignore_ascii_case("Content-Encoding") {
+                content_encoding = Some(value.trim().to_string());
+                continue;
             }
         }
-        if !found {
-            stream
-                .write_all(get_status_line(StatusCode::NotFound).as_bytes())
-                .unwrap();
+        payload.push_str(line.trim_end());
+        payload.push_str("\r\n");
+    }
+    if content_length > max_body_size {
+        return Err(Error::from_status(StatusCode::PayloadTooLarge));
+    }
+    if content_length == 0 {
+        return Ok(Some(Request::parse(payload)));
+    }
+    let mut body = vec![0; content_length];
+    if let Err(e) = reader.read_exact(&mut body) {
+        logger.error(e.to_string().as_str(), &["Unable to read request body"]);
+        return Err(Error::new(
+            StatusCode::BadRequest,
+            "Incomplete request body",
+        ));
+    }
+    if let Some(encoding) = content_encoding {
+        body = decode_content(&encoding, body, max_decompressed_size)?;
+    }
+    payload.push_str(format!("Content-Length: {}\r\n", body.len()).as_str());
+    let mut request = Request::parse(payload);
+    request.set_raw_data(body);
+    Ok(Some(request))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    fn read(head: &str, body: &[u8]) -> Result<Option<Request>, Error> {
+        let mut payload = head.as_bytes().to_vec();
+        payload.extend_from_slice(body);
+        read_request(&mut payload.as_slice(), 1024, 4096)
+    }
+
+    fn app(routes: Vec<Route>) -> App {
+        App {
+            routes,
+            middlewares: Vec::new(),
+            state: Arc::new(Extensions::new()),
+            error_renderer: Box::new(DefaultErrorRenderer),
+            fallbacks: HashMap::new(),
+            error_pages: HashMap::new(),
+            max_body_size: 1024,
+            max_decompressed_size: 4096,
         }
     }
 
-    if !handled_successfully {
-        stream
-            .write_all(get_status_line(StatusCode::InternalServerError).as_bytes())
-            .unwrap();
+    /// Dispatches a request and renders its error, as `handle_connection` does
+    fn respond(app: &App, request: Request) -> Response {
+        let response = dispatch(app, request.clone());
+        render_error(app, &request, response)
     }
-    stream.flush().unwrap();
-    stream.shutdown(std::net::Shutdown::Both).unwrap();
-    return;
-}
 
-#[derive(Clone)]
-pub struct PreRequestHandler;
-#[derive(Clone)]
-pub struct PreResponseHandler;
+    #[test]
+    fn answers_unknown_paths_and_methods() {
+        let app = app(vec![
+            Route::new("/users", || "list", HttpMethod::GET),
+            Route::new("/users", || "create", HttpMethod::POST),
+        ]);
+        let response = respond(&app, Request::test(HttpMethod::DELETE, "/users", &[]));
+        assert_eq!(response.status, StatusCode::MethodNotAllowed);
+        assert_eq!(response.get_header("Allow").unwrap().value(), "GET, POST");
+        let response = respond(&app, Request::test(HttpMethod::GET, "/missing", &[]));
+        assert_eq!(response.status, StatusCode::NotFound);
+    }
 
-#[allow(unused)]
-pub trait PreRequest: Send + Sync {
-    fn call(&self, data: String, request: Request) -> Request;
-}
+    #[test]
+    fn renders_errors_with_fallback_then_error_page_then_renderer() {
+        let mut app = app(vec![Route::new(
+            "/fail",
+            || {
+                let mut response =
+                    Error::from_status(StatusCode::InternalServerError).into_response();
+                response.add_header(Header::new("X-Request-Id".to_string(), "7".to_string()));
+                response
+            },
+            HttpMethod::GET,
+        )]);
+        app.error_pages.insert(404, "<h1>Lost</h1>".to_string());
+        app.fallbacks.insert(
+            500,
+            Box::new(|request: Request| {
+                let error = request.extensions.get::<Error>().unwrap();
+                format!("Sorry: {}", error.message()).into_response()
+            }),
+        );
 
-#[allow(unused)]
-pub trait PreResponse: Send + Sync {
-    fn call(&self, data: String, request: Response) -> Response;
+        let response = respond(&app, Request::test(HttpMethod::GET, "/fail", &[]));
+        assert_eq!(response.data, b"Sorry: Internal Server Error");
+        assert_eq!(response.get_header("X-Request-Id").unwrap().value(), "7");
+
+        let html = Request::test(HttpMethod::GET, "/missing", &[("Accept", "text/html")]);
+        let response = respond(&app, html);
+        assert_eq!(response.status, StatusCode::NotFound);
+        assert_eq!(response.data, b"<h1>Lost</h1>");
+
+        let json = Request::test(
+            HttpMethod::GET,
+            "/missing",
+            &[("Accept", "application/json")],
+        );
+        let response = respond(&app, json);
+        assert_eq!(response.content_type, ContentType::ApplicationProblemJson);
+    }
+
+    #[test]
+    fn renders_error_when_fallback_panics() {
+        panic::install_hook();
+        let mut app = app(Vec::new());
+        app.fallbacks
+            .insert(404, Box::new(|_: Request| panic!("fallback failed")));
+        let response = respond(&app, Request::test(HttpMethod::GET, "/missing", &[]));
+        assert_eq!(response.status, StatusCode::NotFound);
+        assert_eq!(response.data, b"Not Found");
+    }
+
+    #[test]
+    fn loads_error_pages_named_after_status() {
+        let directory = std::env::temp_dir().join(format!("error-pages-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("404.html"), "lost").unwrap();
+        fs::write(directory.join("notes.html"), "ignored").unwrap();
+        let pages = load_error_pages(directory.to_str().unwrap());
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[&404], "lost");
+    }
+
+    #[test]
+    fn rejects_unbounded_request_heads() {
+        let head = format!("GET / HTTP/1.1\r\nX-Long: {}", "a".repeat(MAX_HEAD_SIZE));
+        match read(&head, &[]) {
+            Err(error) => assert_eq!(error.status(), StatusCode::RequestHeaderFieldsTooLarge),
+            Ok(_) => panic!("Unbounded head was accepted"),
+        }
+        match read("GET /\r\n\r\n", &[]) {
+            Err(error) => assert_eq!(error.status(), StatusCode::BadRequest),
+            Ok(_) => panic!("Malformed request line was accepted"),
+        }
+        assert!(read("", &[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn keeps_decompressed_body_unchanged() {
+        let body = b"{\r\n  \"a\": 1\r\n}\r\n\r\n\r\ntrailing  \r\n";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        let gzip = encoder.finish().unwrap();
+        let head = format!(
+            "POST /upload HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            gzip.len()
+        );
+
+        let request = read(&head, &gzip).unwrap().unwrap();
+        assert_eq!(request.raw_data, body);
+        assert_eq!(request.data.as_bytes(), body);
+        assert!(request.get_header("Content-Encoding").is_none());
+        assert_eq!(
+            request.get_header("Content-Length").unwrap().value(),
+            body.len().to_string()
+        );
+    }
+
+    #[test]
+    fn keeps_binary_body() {
+        let body = [0u8, 0xff, b'\r', b'\n', b'\r', b'\n', 0x80];
+        let head = format!(
+            "PUT /blob HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        );
+        let request = read(&head, &body).unwrap().unwrap();
+        assert_eq!(request.method, HttpMethod::PUT);
+        assert_eq!(request.raw_data, body);
+    }
+
+    #[test]
+    fn rejects_oversized_bodies() {
+        let head = "POST / HTTP/1.1\r\nContent-Length: 2048\r\n\r\n";
+        match read(head, &[0; 2048]) {
+            Err(error) => assert_eq!(error.status(), StatusCode::PayloadTooLarge),
+            Ok(_) => panic!("Oversized body was accepted"),
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[b'a'; 8192]).unwrap();
+        let gzip = encoder.finish().unwrap();
+        let head = format!(
+            "POST / HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            gzip.len()
+        );
+        match read(&head, &gzip) {
+            Err(error) => assert_eq!(error.status(), StatusCode::PayloadTooLarge),
+            Ok(_) => panic!("Oversized decompressed body was accepted"),
+        }
+    }
 }
@@ -0,0 +1,3 @@
+pub mod session;
+pub mod session_manager;
+pub mod session_store;
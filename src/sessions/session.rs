@@ -0,0 +1,166 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use super::session_store::{unix_time, SessionRecord};
+
+/// Generates a new random session ID (256 bits, hex encoded)
+pub(crate) fn generate_id() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).expect("Unable to generate session ID");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) struct SessionState {
+    pub(crate) id: Option<String>,
+    pub(crate) record: SessionRecord,
+    pub(crate) changed_keys: HashSet<String>,
+    pub(crate) regenerate: bool,
+    pub(crate) destroyed: bool,
+}
+
+/// Represents a session attached to a request
+/// <br>
+/// The session is a handle: clones share the same data, so changes made by a handler are
+/// seen by the `SessionManager` when it persists the session after the handler has run.
+/// Values are stored as JSON and can be read back as any deserializable type.
+///
+/// # Example
+/// ``` rust
+/// fn login(request: Request) -> Response {
+///     let session = request.session.clone().unwrap();
+///     session.insert("user_id", 42);
+///     session.regenerate();
+///     let visits: u32 = session.get("visits").unwrap_or(0);
+///     session.insert("visits", visits + 1);
+///     Response::get_empty()
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Session {
+    state: Arc<Mutex<SessionState>>,
+}
+
+#[allow(unused)]
+impl Session {
+    /// Creates a new session without an ID. The ID is assigned once the session is persisted.
+    pub fn new() -> Session {
+        let now = unix_time();
+        Session::from_state(
+            None,
+            SessionRecord {
+                data: HashMap::new(),
+                created_at: now,
+                last_accessed: now,
+            },
+        )
+    }
+
+    /// Creates a session from a stored record
+    /// # Arguments
+    /// * `id` - The session ID : String
+    /// * `record` - The stored session : SessionRecord
+    pub fn from_record(id: String, record: SessionRecord) -> Session {
+        Session::from_state(Some(id), record)
+    }
+
+    fn from_state(id: Option<String>, record: SessionRecord) -> Session {
+        Session {
+            state: Arc::new(Mutex::new(SessionState {
+                id,
+                record,
+                changed_keys: HashSet::new(),
+                regenerate: false,
+                destroyed: false,
+            })),
+        }
+    }
+
+    pub(crate) fn state(&self) -> MutexGuard<'_, SessionState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Gets the session ID
+    /// # Returns
+    /// * `Option<String>` - The ID or `None` if the session was not stored yet
+    pub fn id(&self) -> Option<String> {
+        self.state().id.clone()
+    }
+
+    /// Returns `true` if the session was not stored yet
+    pub fn is_new(&self) -> bool {
+        self.state().id.is_none()
+    }
+
+    /// Gets a value from the session
+    /// # Arguments
+    /// * `key` - The key of the value : &str
+    /// # Returns
+    /// * `Option<T>` - The value or `None` if it is missing or has a different type
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.state().record.data.get(key)?.clone();
+        serde_json::from_value(value).ok()
+    }
+
+    /// Inserts a value into the session
+    /// # Arguments
+    /// * `key` - The key of the value : &str
+    /// * `value` - The value : T
+    pub fn insert<T: Serialize>(&self, key: &str, value: T) {
+        let value = serde_json::to_value(value).unwrap_or(Value::Null);
+        let mut state = self.state();
+        state.record.data.insert(key.to_string(), value);
+        state.changed_keys.insert(key.to_string());
+    }
+
+    /// Removes a value from the session
+    /// # Arguments
+    /// * `key` - The key of the value : &str
+    /// # Returns
+    /// * `Option<T>` - The removed value
+    pub fn remove<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let mut state = self.state();
+        state.changed_keys.insert(key.to_string());
+        let value = state.record.data.remove(key)?;
+        serde_json::from_value(value).ok()
+    }
+
+    /// Returns `true` if the session contains a value for the key
+    /// # Arguments
+    /// * `key` - The key of the value : &str
+    pub fn contains(&self, key: &str) -> bool {
+        self.state().record.data.contains_key(key)
+    }
+
+    /// Removes all values from the session, keeping its ID
+    pub fn clear(&self) {
+        let mut state = self.state();
+        let keys: Vec<String> = state.record.data.keys().cloned().collect();
+        state.changed_keys.extend(keys);
+        state.record.data.clear();
+    }
+
+    /// Requests a new session ID. The data is kept and moved to the new ID once the session is persisted.
+    /// <br>
+    /// Should be called whenever the privilege level changes, e.g. on login, to prevent session fixation.
+    pub fn regenerate(&self) {
+        self.state().regenerate = true;
+    }
+
+    /// Destroys the session. The stored record is removed and the cookie is cleared once the session is persisted.
+    pub fn destroy(&self) {
+        let mut state = self.state();
+        state.destroyed = true;
+        state.record.data.clear();
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
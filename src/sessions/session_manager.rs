@@ -0,0 +1,307 @@
+use std::time::Duration;
+
+use crate::{
+    http::http_cookies::{Cookie, SameSite},
+    middleware::{Middleware, Next},
+    server::get_log_level,
+    utils::logger::Logger,
+    Request, Response,
+};
+
+use super::{
+    session::{generate_id, Session},
+    session_store::{unix_time, SessionRecord, SessionStore},
+};
+
+/// Configuration of the sessions
+/// # Arguments
+/// * `cookie_name` - The name of the session-ID cookie : String
+/// * `cookie_path` - The `Path` attribute of the cookie : String
+/// * `secure` - Whether the cookie is only sent over HTTPS : bool
+/// * `http_only` - Whether the cookie is hidden from scripts : bool
+/// * `same_site` - The `SameSite` attribute of the cookie : SameSite
+/// * `idle_timeout` - The session expires if it is not used for this long : Option<Duration>
+/// * `absolute_timeout` - The session expires this long after it was created, regardless of use : Option<Duration>
+/// * `login_key` - Changing this session key (e.g. `"user_id"`) regenerates the session ID : Option<String>
+///
+/// # Example
+/// ``` rust
+/// let config = SessionConfig {
+///     idle_timeout: Some(Duration::from_secs(15 * 60)),
+///     login_key: Some("user_id".to_string()),
+///     ..SessionConfig::default()
+/// };
+/// ```
+#[derive(Clone, Debug)]
+pub struct SessionConfig {
+    pub cookie_name: String,
+    pub cookie_path: String,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: SameSite,
+    pub idle_timeout: Option<Duration>,
+    pub absolute_timeout: Option<Duration>,
+    pub login_key: Option<String>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            cookie_name: "SESSIONID".to_string(),
+            cookie_path: "/".to_string(),
+            secure: false,
+            http_only: true,
+            same_site: SameSite::Lax,
+            idle_timeout: Some(Duration::from_secs(30 * 60)),
+            absolute_timeout: Some(Duration::from_secs(24 * 60 * 60)),
+            login_key: None,
+        }
+    }
+}
+
+/// Manages the sessions of the server
+/// <br>
+/// Used as a middleware: before the rest of the chain runs the session referenced by the
+/// session-ID cookie is loaded into `Request::session`, afterwards the session is persisted to
+/// the store and the cookie is set, refreshed or cleared on the response.
+///
+/// # Arguments
+/// * `config` - The session configuration : SessionConfig
+/// * `store` - The storage backend : impl SessionStore
+///
+/// # Example
+/// ``` rust
+/// Server::new("127.0.0.1", 8000)
+///     .with(SessionManager::new(
+///         SessionConfig::default(),
+///         MemorySessionStore::new(Duration::from_secs(30 * 60)),
+///     ))
+///     .start();
+/// ```
+pub struct SessionManager {
+    config: SessionConfig,
+    store: Box<dyn SessionStore>,
+}
+
+#[allow(unused)]
+impl SessionManager {
+    pub fn new(config: SessionConfig, store: impl SessionStore + 'static) -> SessionManager {
+        SessionManager {
+            config,
+            store: Box::new(store),
+        }
+    }
+
+    /// Gets the session configuration
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    fn is_expired(&self, record: &SessionRecord, now: u64) -> bool {
+        let idle = self
+            .config
+            .idle_timeout
+            .is_some_and(|t| now.saturating_sub(record.last_accessed) > t.as_secs());
+        let absolute = self
+            .config
+            .absolute_timeout
+            .is_some_and(|t| now.saturating_sub(record.created_at) > t.as_secs());
+        idle || absolute
+    }
+
+    /// Loads the session of the request
+    /// <br>
+    /// A missing, unknown or expired session ID results in a new, empty session.
+    /// # Arguments
+    /// * `request` - The request to attach the session to : &mut Request
+    pub fn load(&self, request: &mut Request) {
+        let logger: Logger = Logger {
+            c_name: "SESSION",
+            level: get_log_level(),
+        };
+        let now = unix_time();
+        let session = match request.get_cookie(&self.config.cookie_name) {
+            Some(cookie) => match self.store.load(cookie.value()) {
+                Some(record) if !self.is_expired(&record, now) => {
+                    Session::from_record(cookie.value().to_string(), record)
+                }
+                Some(_) => {
+                    logger.debug(&["Session expired"]);
+                    if let Err(e) = self.store.destroy(cookie.value()) {
+                        logger.error(e.to_string().as_str(), &["Unable to remove session"]);
+                    }
+                    Session::new()
+                }
+                None => Session::new(),
+            },
+            None => Session::new(),
+        };
+        request.session = Some(session);
+    }
+
+    /// Persists the session and updates the session cookie of the response
+    /// # Arguments
+    /// * `session` - The session to persist : &Session
+    /// * `response` - The response to set the cookie on : &mut Response
+    pub fn persist(&self, session: &Session, response: &mut Response) {
+        let logger: Logger = Logger {
+            c_name: "SESSION",
+            level: get_log_level(),
+        };
+        let mut state = session.state();
+
+        if state.destroyed {
+            if let Some(id) = state.id.take() {
+                if let Err(e) = self.store.destroy(&id) {
+                    logger.error(e.to_string().as_str(), &["Unable to remove session"]);
+                }
+                response.add_cookie(self.cookie(String::new(), Some(0)));
+            }
+            return;
+        }
+        if state.id.is_none() && state.record.data.is_empty() {
+            return;
+        }
+
+        let login_changed = self
+            .config
+            .login_key
+            .as_ref()
+            .is_some_and(|key| state.changed_keys.contains(key));
+        let mut issue_cookie = state.id.is_none();
+        if state.id.is_some() && (state.regenerate || login_changed) {
+            if let Err(e) = self.store.destroy(state.id.as_ref().unwrap()) {
+                logger.error(e.to_string().as_str(), &["Unable to remove session"]);
+            }
+            state.id = None;
+            issue_cookie = true;
+        }
+        let id = state.id.get_or_insert_with(generate_id).clone();
+
+        state.record.last_accessed = unix_time();
+        state.regenerate = false;
+        state.changed_keys.clear();
+        if let Err(e) = self.store.save(&id, &state.record) {
+            logger.error(e.to_string().as_str(), &["Unable to save session"]);
+            return;
+        }
+        if issue_cookie {
+            let max_age = self.config.absolute_timeout.map(|t| t.as_secs() as i64);
+            response.add_cookie(self.cookie(id, max_age));
+        }
+    }
+
+    fn cookie(&self, value: String, max_age: Option<i64>) -> Cookie {
+        let mut cookie = Cookie::new(self.config.cookie_name.clone(), value);
+        cookie
+            .set_path(&self.config.cookie_path)
+            .set_http_only(self.config.http_only)
+            .set_secure(self.config.secure)
+            .set_same_site(self.config.same_site);
+        if let Some(max_age) = max_age {
+            cookie.set_max_age(max_age);
+        }
+        cookie
+    }
+}
+
+impl Middleware for SessionManager {
+    fn handle(&self, mut request: Request, next: Next) -> Response {
+        self.load(&mut request);
+        let session = request.session.clone();
+        let mut response = next.run(request);
+        if let Some(session) = session {
+            self.persist(&session, &mut response);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::http_methods::HttpMethod, sessions::session_store::MemorySessionStore};
+
+    fn manager(config: SessionConfig) -> SessionManager {
+        SessionManager::new(config, MemorySessionStore::new(Duration::from_secs(60)))
+    }
+
+    /// Stores a record and returns a request carrying its session cookie
+    fn stored(manager: &SessionManager, id: &str, record: SessionRecord) -> Request {
+        manager.store.save(id, &record).unwrap();
+        let cookie = format!("SESSIONID={}", id);
+        Request::test(HttpMethod::GET, "/", &[("Cookie", cookie.as_str())])
+    }
+
+    fn record(created_at: u64, last_accessed: u64) -> SessionRecord {
+        let mut record = SessionRecord {
+            created_at,
+            last_accessed,
+            ..SessionRecord::default()
+        };
+        record.data.insert("cart".to_string(), 3.into());
+        record
+    }
+
+    #[test]
+    fn regenerates_id_when_login_key_changes() {
+        let manager = manager(SessionConfig {
+            login_key: Some("user_id".to_string()),
+            ..SessionConfig::default()
+        });
+        let now = unix_time();
+        let mut request = stored(&manager, "old", record(now, now));
+        manager.load(&mut request);
+        let session = request.session.unwrap();
+        assert_eq!(session.id().as_deref(), Some("old"));
+        session.insert("user_id", 42);
+
+        let mut response = Response::get_empty();
+        manager.persist(&session, &mut response);
+        let id = response
+            .get_cookie("SESSIONID")
+            .unwrap()
+            .value()
+            .to_string();
+        assert_ne!(id, "old");
+        assert!(manager.store.load("old").is_none());
+        let record = manager.store.load(&id).unwrap();
+        assert_eq!(record.data["cart"], 3);
+        assert_eq!(record.data["user_id"], 42);
+    }
+
+    #[test]
+    fn keeps_id_without_login() {
+        let manager = manager(SessionConfig::default());
+        let now = unix_time();
+        let mut request = stored(&manager, "abc", record(now, now));
+        manager.load(&mut request);
+        let session = request.session.unwrap();
+        session.insert("cart", 4);
+
+        let mut response = Response::get_empty();
+        manager.persist(&session, &mut response);
+        assert!(response.get_cookie("SESSIONID").is_none());
+        assert_eq!(manager.store.load("abc").unwrap().data["cart"], 4);
+    }
+
+    #[test]
+    fn expires_idle_sessions() {
+        let manager = manager(SessionConfig::default());
+        let now = unix_time();
+        let mut request = stored(&manager, "idle", record(now - 3600, now - 31 * 60));
+        manager.load(&mut request);
+        assert!(request.session.unwrap().is_new());
+        assert!(manager.store.load("idle").is_none());
+    }
+
+    #[test]
+    fn expires_sessions_after_absolute_timeout() {
+        let manager = manager(SessionConfig::default());
+        let now = unix_time();
+        let mut request = stored(&manager, "old", record(now - 25 * 60 * 60, now));
+        manager.load(&mut request);
+        assert!(request.session.unwrap().is_new());
+        assert!(manager.store.load("old").is_none());
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{server::get_log_level, utils::logger::Logger};
+
+/// Returns the current unix time in seconds
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The persisted state of a session
+/// # Arguments
+/// * `data` - The key/value pairs of the session : HashMap<String, Value>
+/// * `created_at` - Unix time (seconds) the session was created at : u64
+/// * `last_accessed` - Unix time (seconds) the session was last used at : u64
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub data: HashMap<String, Value>,
+    pub created_at: u64,
+    pub last_accessed: u64,
+}
+
+/// Storage backend of the sessions
+/// <br>
+/// Implementations must be safe to share between the worker threads.
+pub trait SessionStore: Send + Sync {
+    /// Loads a session
+    /// # Arguments
+    /// * `id` - The session ID : &str
+    /// # Returns
+    /// * `Option<SessionRecord>` - The session or `None` if it does not exist or has expired
+    fn load(&self, id: &str) -> Option<SessionRecord>;
+
+    /// Saves a session, replacing any previous record with the same ID
+    /// # Arguments
+    /// * `id` - The session ID : &str
+    /// * `record` - The session to save : &SessionRecord
+    fn save(&self, id: &str, record: &SessionRecord) -> std::io::Result<()>;
+
+    /// Removes a session
+    /// # Arguments
+    /// * `id` - The session ID : &str
+    fn destroy(&self, id: &str) -> std::io::Result<()>;
+}
+
+/// In-memory session store
+/// <br>
+/// Every record expires `ttl` after it was last saved. Expired records are dropped when
+/// they are loaded and purged from the whole store on every save.
+///
+/// # Arguments
+/// * `ttl` - How long a record is kept after its last save : Duration
+///
+/// # Example
+/// ``` rust
+/// let store = MemorySessionStore::new(Duration::from_secs(30 * 60));
+/// ```
+pub struct MemorySessionStore {
+    ttl: Duration,
+    records: Mutex<HashMap<String, (SessionRecord, Instant)>>,
+}
+
+#[allow(unused)]
+impl MemorySessionStore {
+    pub fn new(ttl: Duration) -> MemorySessionStore {
+        MemorySessionStore {
+            ttl,
+            records: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Removes all expired records
+    pub fn purge_expired(&self) {
+        let now = Instant::now();
+        self.records
+            .lock()
+            .unwrap()
+            .retain(|_, (_, expires)| *expires > now);
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn load(&self, id: &str) -> Option<SessionRecord> {
+        let mut records = self.records.lock().unwrap();
+        match records.get(id) {
+            Some((record, expires)) if *expires > Instant::now() => Some(record.clone()),
+            Some(_) => {
+                records.remove(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn save(&self, id: &str, record: &SessionRecord) -> std::io::Result<()> {
+        self.purge_expired();
+        self.records
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), (record.clone(), Instant::now() + self.ttl));
+        Ok(())
+    }
+
+    fn destroy(&self, id: &str) -> std::io::Result<()> {
+        self.records.lock().unwrap().remove(id);
+        Ok(())
+    }
+}
+
+/// File system session store
+/// <br>
+/// Every session is kept as a JSON file named after its ID inside `directory`.
+/// IDs that are not plain alphanumeric strings are rejected, so a forged cookie can not
+/// point the store outside of its directory.
+/// <br>
+/// Every file expires `ttl` after it was last saved. Expired files are ignored when they are loaded and removed from
+/// the whole directory at most once per purge interval, during a save.
+///
+/// # Arguments
+/// * `directory` - The directory the sessions are stored in : &str
+/// * `ttl` - How long a file is kept after its last save : Duration
+///
+/// # Example
+/// ``` rust
+/// let store = FileSessionStore::new("./sessions", Duration::from_secs(30 * 60))
+///     .unwrap()
+///     .purge_interval(Duration::from_secs(5 * 60));
+/// ```
+pub struct FileSessionStore {
+    directory: PathBuf,
+    ttl: Duration,
+    purge_interval: Duration,
+    next_purge: Mutex<Instant>,
+}
+
+#[allow(unused)]
+impl FileSessionStore {
+    pub fn new(directory: &str, ttl: Duration) -> std::io::Result<FileSessionStore> {
+        fs::create_dir_all(directory)?;
+        Ok(FileSessionStore {
+            directory: PathBuf::from(directory),
+            ttl,
+            purge_interval: Duration::from_secs(60),
+            next_purge: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Sets how often expired files are removed. Defaults to 60 seconds
+    /// # Arguments
+    /// * `interval` - The interval : Duration
+    /// # Returns
+    /// * `FileSessionStore` - The store
+    pub fn purge_interval(mut self, interval: Duration) -> FileSessionStore {
+        self.purge_interval = interval;
+        self
+    }
+
+    /// Removes all expired session files
+    pub fn purge_expired(&self) {
+        let logger: Logger = Logger {
+            c_name: "SESSION",
+            level: get_log_level(),
+        };
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                logger.error(
+                    e.to_string().as_str(),
+                    &["Unable to read session directory"],
+                );
+                return;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") && self.is_expired(&path) {
+                if let Err(e) = fs::remove_file(&path) {
+                    logger.error(e.to_string().as_str(), &["Unable to remove session"]);
+                }
+            }
+        }
+    }
+
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(self.directory.join(id.to_string() + ".json"))
+    }
+
+    /// Returns `true` if the file was last saved more than `ttl` ago
+    fn is_expired(&self, path: &Path) -> bool {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > self.ttl))
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn load(&self, id: &str) -> Option<SessionRecord> {
+        let logger: Logger = Logger {
+            c_name: "SESSION",
+            level: get_log_level(),
+        };
+        let path = self.path(id)?;
+        if self.is_expired(&path) {
+            return None;
+        }
+        let data = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&data) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                logger.error(e.to_string().as_str(), &["Corrupted session file", id]);
+                None
+            }
+        }
+    }
+
+    fn save(&self, id: &str, record: &SessionRecord) -> std::io::Result<()> {
+        let path = self.path(id).ok_or(std::io::ErrorKind::InvalidInput)?;
+        let purge = {
+            let mut next_purge = self.next_purge.lock().unwrap();
+            let now = Instant::now();
+            let due = now >= *next_purge;
+            if due {
+                *next_purge = now + self.purge_interval;
+            }
+            due
+        };
+        if purge {
+            self.purge_expired();
+        }
+        fs::write(path, serde_json::to_string(record)?)
+    }
+
+    fn destroy(&self, id: &str) -> std::io::Result<()> {
+        let path = self.path(id).ok_or(std::io::ErrorKind::InvalidInput)?;
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sessions::session::generate_id;
+
+    fn directory() -> String {
+        let path = std::env::temp_dir().join(format!("sessions-{}", generate_id()));
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn rejects_ids_outside_of_directory() {
+        let directory = directory();
+        let store = FileSessionStore::new(&directory, Duration::from_secs(60)).unwrap();
+        let record = SessionRecord::default();
+        for id in ["../escape", "a/b", "a.json", ""] {
+            assert!(store.save(id, &record).is_err());
+            assert!(store.load(id).is_none());
+            assert!(store.destroy(id).is_err());
+        }
+        store.save("abc123", &record).unwrap();
+        assert!(store.load("abc123").is_some());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn purges_expired_files() {
+        let directory = directory();
+        let store = FileSessionStore::new(&directory, Duration::from_millis(50))
+            .unwrap()
+            .purge_interval(Duration::ZERO);
+        store.save("old", &SessionRecord::default()).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(store.load("old").is_none());
+
+        store.save("new", &SessionRecord::default()).unwrap();
+        assert!(!Path::new(&directory).join("old.json").exists());
+        assert!(store.load("new").is_some());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
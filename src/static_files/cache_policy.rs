@@ -0,0 +1,156 @@
+use super::StaticFiles;
+use crate::http::http_cache_control::CacheControl;
+
+/// Minimum length of a hexadecimal fingerprint, e.g. the `3f9a2c1b` of `app.3f9a2c1b.js`
+const MIN_FINGERPRINT: usize = 8;
+
+/// A `Cache-Control` rule for the files matching a pattern
+pub(super) struct CacheRule {
+    pattern: String,
+    cache_control: CacheControl,
+}
+
+impl CacheRule {
+    pub(super) fn new(pattern: &str, cache_control: CacheControl) -> CacheRule {
+        CacheRule {
+            pattern: pattern.trim_start_matches('/').to_string(),
+            cache_control,
+        }
+    }
+
+    /// Returns `true` if the rule applies to a normalized path relative to the root
+    /// <br>
+    /// `*.ext` matches the extension, case-insensitively, `dir/*` everything below a directory and
+    /// any other pattern the exact path.
+    fn matches(&self, path: &str) -> bool {
+        if let Some(extension) = self.pattern.strip_prefix("*.") {
+            let name = path.rsplit('/').next().unwrap_or_default();
+            return name.rsplit_once('.').is_some_and(|(stem, ext)| {
+                !stem.is_empty() && ext.eq_ignore_ascii_case(extension)
+            });
+        }
+        if let Some(directory) = self.pattern.strip_suffix('*') {
+            return directory.is_empty() || path.starts_with(directory);
+        }
+        self.pattern == path
+    }
+}
+
+/// Returns `true` if a file name contains a content hash, so its content never changes under the same name
+/// <br>
+/// Recognized are hashes between dots (`app.3f9a2c1b.js`, `main.3f9a2c1b.chunk.js`) and after the last dash of the
+/// stem (`index-3f9a2c1b.js`). A hash has at least 8 hexadecimal characters and contains both a digit and a letter,
+/// so words such as `facade` and dates such as `20240101` are not mistaken for one. HTML files are never
+/// fingerprinted, since their names are the URLs users visit.
+/// # Arguments
+/// * `name` - The file name : &str
+pub fn is_fingerprinted(name: &str) -> bool {
+    let parts: Vec<&str> = name.split('.').collect();
+    if parts.len() < 2 || parts[0].is_empty() {
+        return false;
+    }
+    let extension = parts[parts.len() - 1];
+    if extension.eq_ignore_ascii_case("html") || extension.eq_ignore_ascii_case("htm") {
+        return false;
+    }
+    let dotted = parts[1..parts.len() - 1].iter().any(|part| is_hash(part));
+    let dashed = parts[0]
+        .rsplit_once('-')
+        .is_some_and(|(stem, hash)| !stem.is_empty() && is_hash(hash));
+    dotted || dashed
+}
+
+/// Returns `true` if a part of a file name has the shape of a hexadecimal content hash
+fn is_hash(part: &str) -> bool {
+    part.len() >= MIN_FINGERPRINT
+        && part.chars().all(|c| c.is_ascii_hexdigit())
+        && part.chars().any(|c| c.is_ascii_digit())
+        && part.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+impl StaticFiles {
+    /// Chooses the `Cache-Control` value of a served file
+    /// <br>
+    /// Rules are checked in the order they were added, then fingerprinted file names get immutable caching,
+    /// other files the default value, if any.
+    /// # Arguments
+    /// * `served` - The path of the served file relative to the root : &str
+    /// # Returns
+    /// * `Option<CacheControl>` - The value, `None` to send no `Cache-Control` header
+    pub(super) fn cache_control_for(&self, served: &str) -> Option<CacheControl> {
+        let path = served
+            .split('/')
+            .filter(|segment| !segment.is_empty() && *segment != ".")
+            .collect::<Vec<&str>>()
+            .join("/");
+        if let Some(rule) = self.cache_rules.iter().find(|rule| rule.matches(&path)) {
+            return Some(rule.cache_control.clone());
+        }
+        let name = path.rsplit('/').next().unwrap_or_default();
+        if self.fingerprinted && is_fingerprinted(name) {
+            return Some(CacheControl::immutable());
+        }
+        self.default_cache_control.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_hexadecimal_hashes() {
+        for name in [
+            "app.3f9a2c1b.js",
+            "main.3F9A2C1B.chunk.js",
+            "index-3f9a2c1b.css",
+            "vendor-react-0a1b2c3d4e5f.js",
+            "logo.5d41402abc4b2a76.svg",
+        ] {
+            assert!(is_fingerprinted(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn ignores_names_resembling_hashes() {
+        for name in [
+            "report-20240101.pdf",
+            "book-chapter1.html",
+            "user-profile1.js",
+            "app.decade.js",
+            "module.facade.js",
+            "app.3f9a2c.js",
+            "index-BxK3f9aQ.js",
+            "index.3f9a2c1b.html",
+            "page-3f9a2c1b.htm",
+            "3f9a2c1b",
+            ".3f9a2c1b.js",
+            "-3f9a2c1b.js",
+        ] {
+            assert!(!is_fingerprinted(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn caches_fingerprinted_files_only_when_enabled() {
+        let files = StaticFiles::new(".");
+        assert!(files.cache_control_for("assets/app.3f9a2c1b.js").is_none());
+        let files = files.fingerprinted(true);
+        assert_eq!(
+            files.cache_control_for("assets/app.3f9a2c1b.js"),
+            Some(CacheControl::immutable())
+        );
+        assert!(files.cache_control_for("assets/app.js").is_none());
+        let files = files
+            .cache_control("assets/*", CacheControl::no_cache())
+            .default_cache_control(CacheControl::max_age(60));
+        assert_eq!(
+            files.cache_control_for("/assets/./app.3f9a2c1b.js"),
+            Some(CacheControl::no_cache())
+        );
+        assert_eq!(
+            files.cache_control_for("app.js"),
+            Some(CacheControl::max_age(60))
+        );
+    }
+}
@@ -0,0 +1,357 @@
+use std::{cmp::Ordering, fs, path::Path, time::SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::StaticFiles;
+use crate::{
+    error::Error,
+    http::{
+        http_codes::StatusCode,
+        http_content_types::{get_type, ContentType},
+        http_headers::parse_quality_list,
+    },
+    utils::{
+        html::escape_html,
+        url::{percent_decode, percent_encode},
+    },
+    Request, Response,
+};
+
+/// An entry of a directory listing
+struct ListingEntry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+    content_type: Option<ContentType>,
+}
+
+impl ListingEntry {
+    fn mime_type(&self) -> String {
+        self.content_type.map(get_type).unwrap_or_default()
+    }
+
+    fn modified_rfc3339(&self) -> Option<String> {
+        self.modified
+            .map(|time| DateTime::<Utc>::from(time).to_rfc3339())
+    }
+}
+
+/// The sorting query parameters of a directory listing, e.g. `?sort=size&order=desc`
+#[derive(Deserialize, Default)]
+struct ListingQuery {
+    sort: Option<String>,
+    order: Option<String>,
+}
+
+/// Returns `true` if the client prefers JSON over HTML
+fn wants_json(request: &Request) -> bool {
+    let accept = match request.get_header("Accept") {
+        Some(header) => header.value().to_string(),
+        None => return false,
+    };
+    for (media_type, quality) in parse_quality_list(&accept) {
+        if quality <= 0.0 {
+            continue;
+        }
+        match media_type.as_str() {
+            "application/json" => return true,
+            "text/html" | "application/xhtml+xml" | "*/*" => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Formats a size in bytes for humans, e.g. `1.5 KiB`
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", size),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
+
+impl StaticFiles {
+    /// Reads the entries of a directory which may be served, following the hidden-file and symlink rules
+    fn read_entries(&self, directory: &Path, relative: &str) -> Result<Vec<ListingEntry>, Error> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(directory)? {
+            let name = match entry.map(|entry| entry.file_name().into_string()) {
+                Ok(Ok(name)) => name,
+                _ => continue,
+            };
+            let path = match self.resolve(&format!("{}/{}", relative, name)) {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            entries.push(ListingEntry {
+                content_type: match metadata.is_dir() {
+                    true => None,
+                    false => Some(ContentType::parse_file_name(&name)),
+                },
+                name,
+                is_dir: metadata.is_dir(),
+                size: metadata.len(),
+                modified: metadata.modified().ok(),
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Lists the content of a directory as HTML, or as JSON if the client prefers it
+    /// <br>
+    /// Entries are sorted by the `sort` (`name`, `size`, `modified` or `type`) and `order` (`asc` or `desc`) query
+    /// parameters, directories first.
+    /// # Arguments
+    /// * `directory` - The canonical path of the directory : &Path
+    /// * `relative` - The decoded path of the directory relative to the root : &str
+    /// * `request` - The request : &Request
+    /// # Returns
+    /// * `Result<Response, Error>` - The listing
+    pub(super) fn list_directory(
+        &self,
+        directory: &Path,
+        relative: &str,
+        request: &Request,
+    ) -> Result<Response, Error> {
+        let mut entries = self.read_entries(directory, relative)?;
+        let query: ListingQuery = serde_urlencoded::from_str(&request.query).unwrap_or_default();
+        let sort = query.sort.unwrap_or_else(|| "name".to_string());
+        let descending = query.order.as_deref() == Some("desc");
+        entries.sort_by(|a, b| {
+            let ordering = match sort.as_str() {
+                "size" => a.size.cmp(&b.size),
+                "modified" => a.modified.cmp(&b.modified),
+                "type" => a.mime_type().cmp(&b.mime_type()),
+                _ => Ordering::Equal,
+            }
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+            let ordering = match descending {
+                true => ordering.reverse(),
+                false => ordering,
+            };
+            b.is_dir.cmp(&a.is_dir).then(ordering)
+        });
+
+        let mut response = match wants_json(request) {
+            true => json_listing(&entries),
+            false => html_listing(
+                &entries,
+                request,
+                !relative.trim_matches('/').is_empty(),
+                &sort,
+                descending,
+            ),
+        };
+        response.add_vary("Accept");
+        Ok(response)
+    }
+}
+
+/// Renders a directory listing as JSON
+fn json_listing(entries: &[ListingEntry]) -> Response {
+    let entries: Vec<serde_json::Value> = entries
+        .iter()
+        .map(|entry| {
+            serde_json::json!({
+                "name": entry.name,
+                "type": if entry.is_dir { "directory" } else { "file" },
+                "size": entry.size,
+                "modified": entry.modified_rfc3339(),
+                "mime_type": entry.content_type.map(get_type),
+            })
+        })
+        .collect();
+    Response::new(
+        StatusCode::Ok,
+        ContentType::ApplicationJson,
+        Vec::new(),
+        Vec::new(),
+        serde_json::Value::Array(entries).to_string(),
+    )
+}
+
+/// Renders a directory listing as an HTML page with sortable columns
+fn html_listing(
+    entries: &[ListingEntry],
+    request: &Request,
+    has_parent: bool,
+    sort: &str,
+    descending: bool,
+) -> Response {
+    let column = |key: &str, title: &str| {
+        let order = match sort == key && !descending {
+            true => "desc",
+            false => "asc",
+        };
+        format!(
+            "<th><a href=\"?sort={}&amp;order={}\">{}</a></th>",
+            key, order, title
+        )
+    };
+    let mut rows = String::new();
+    if has_parent {
+        rows.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td><td></td></tr>");
+    }
+    for entry in entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{0}{1}\">{2}{1}</a></td><td>{3}</td><td>{4}</td><td>{5}</td></tr>",
+            percent_encode(&entry.name),
+            suffix,
+            escape_html(&entry.name),
+            match entry.is_dir {
+                true => "-".to_string(),
+                false => format_size(entry.size),
+            },
+            entry
+                .modified
+                .map(|time| DateTime::<Utc>::from(time)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string())
+                .unwrap_or_default(),
+            escape_html(&entry.mime_type()),
+        ));
+    }
+    let title = format!("Index of {}", escape_html(&percent_decode(&request.path)));
+    Response::new(
+        StatusCode::Ok,
+        ContentType::TextHtmlCharsetUtf8,
+        Vec::new(),
+        Vec::new(),
+        format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{0}</title></head><body><h1>{0}</h1>\
+             <table><thead><tr>{1}{2}{3}{4}</tr></thead><tbody>{5}</tbody></table></body></html>",
+            title,
+            column("name", "Name"),
+            column("size", "Size"),
+            column("modified", "Modified"),
+            column("type", "Type"),
+            rows,
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::http_methods::HttpMethod,
+        static_files::tests::{get, header, Fixture},
+    };
+
+    fn fixture() -> Fixture {
+        Fixture::new(&[
+            ("files/b.txt", "bb"),
+            ("files/A.css", "aaaa"),
+            ("files/c <x>.png", "c"),
+            ("files/.secret", "s"),
+            ("files/sub/d.txt", "d"),
+        ])
+    }
+
+    fn names(response: &Response) -> Vec<String> {
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(&response.data).unwrap();
+        entries
+            .iter()
+            .map(|entry| entry["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    const JSON: (&str, &str) = ("Accept", "application/json");
+
+    #[test]
+    fn lists_directories_only_when_enabled() {
+        let fixture = fixture();
+        let files = StaticFiles::new(fixture.root());
+        assert_eq!(get(&files, "/files/", &[]).status, StatusCode::NotFound);
+        let files = files.directory_listing(true);
+        let response = get(&files, "/files/", &[]);
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.content_type, ContentType::TextHtmlCharsetUtf8);
+        assert_eq!(header(&response, "Vary").as_deref(), Some("Accept"));
+    }
+
+    #[test]
+    fn lists_entries_as_json_without_hidden_files() {
+        let fixture = fixture();
+        let files = StaticFiles::new(fixture.root()).directory_listing(true);
+        let response = get(&files, "/files/", &[JSON]);
+        assert_eq!(response.content_type, ContentType::ApplicationJson);
+        assert_eq!(names(&response), ["sub", "A.css", "b.txt", "c <x>.png"]);
+
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(&response.data).unwrap();
+        assert_eq!(entries[0]["type"], "directory");
+        assert_eq!(entries[0]["mime_type"], serde_json::Value::Null);
+        assert_eq!(entries[1]["type"], "file");
+        assert_eq!(entries[1]["size"], 4);
+        assert_eq!(
+            entries[1]["mime_type"],
+            get_type(ContentType::TextCssCharsetUtf8)
+        );
+        assert!(entries[1]["modified"].is_string());
+
+        let files = files.hidden_files(true);
+        assert!(names(&get(&files, "/files/", &[JSON])).contains(&".secret".to_string()));
+    }
+
+    #[test]
+    fn sorts_by_query_with_directories_first() {
+        let fixture = fixture();
+        let files = StaticFiles::new(fixture.root()).directory_listing(true);
+        let sorted = |query: &str| names(&get(&files, &format!("/files/?{}", query), &[JSON]));
+        assert_eq!(sorted("sort=size"), ["sub", "c <x>.png", "b.txt", "A.css"]);
+        assert_eq!(
+            sorted("sort=size&order=desc"),
+            ["sub", "A.css", "b.txt", "c <x>.png"]
+        );
+        assert_eq!(
+            sorted("sort=name&order=desc"),
+            ["sub", "c <x>.png", "b.txt", "A.css"]
+        );
+    }
+
+    #[test]
+    fn escapes_names_in_html_listing() {
+        let fixture = fixture();
+        let files = StaticFiles::new(fixture.root()).directory_listing(true);
+        let html = String::from_utf8(get(&files, "/files/", &[]).data).unwrap();
+        assert!(html.contains("<a href=\"c%20%3Cx%3E.png\">c &lt;x&gt;.png</a>"));
+        assert!(html.contains("<a href=\"sub/\">sub/</a>"));
+        assert!(html.contains("<a href=\"../\">../</a>"));
+        assert!(!html.contains(".secret"));
+        assert!(html.contains("<a href=\"?sort=name&amp;order=desc\">Name</a>"));
+
+        let root = String::from_utf8(get(&files, "/", &[]).data).unwrap();
+        assert!(!root.contains("../"));
+    }
+
+    #[test]
+    fn prefers_html_unless_json_is_preferred() {
+        let request = |accept: &str| Request::test(HttpMethod::GET, "/", &[("Accept", accept)]);
+        assert!(wants_json(&request("application/json")));
+        assert!(wants_json(&request("application/json, text/html;q=0.5")));
+        assert!(!wants_json(&request("text/html, application/json")));
+        assert!(!wants_json(&request("application/json;q=0, */*")));
+        assert!(!wants_json(&Request::test(HttpMethod::GET, "/", &[])));
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+}
@@ -0,0 +1,276 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{redirect_to_directory, StaticFiles};
+use crate::{
+    error::Error,
+    http::{
+        http_codes::StatusCode,
+        http_content_types::ContentType,
+        http_encodings::{negotiate_encoding, ContentEncoding},
+        http_headers::Header,
+        http_preconditions::{
+            evaluate_preconditions, format_http_date, not_modified, Precondition,
+        },
+        http_ranges::apply_ranges,
+    },
+    Request, Response,
+};
+
+/// A file embedded into the binary with `embed_dir!`
+pub struct EmbeddedFile {
+    /// The path relative to the embedded directory, with `/` separators
+    pub path: &'static str,
+    pub data: &'static [u8],
+    /// The content type, resolved from the extension at compile time
+    pub content_type: ContentType,
+    /// The quoted strong ETag of the content
+    pub etag: &'static str,
+    /// The modification time in seconds since the Unix epoch
+    pub modified: u64,
+    /// The gzip variant, if the file was compressed at compile time
+    pub gzip: Option<&'static [u8]>,
+    /// The brotli variant, if the file was compressed at compile time
+    pub brotli: Option<&'static [u8]>,
+}
+
+#[allow(unused)]
+impl EmbeddedFile {
+    /// Returns the modification time of the file
+    pub fn modified(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.modified)
+    }
+
+    /// Returns the precompressed variant of the file for a coding
+    pub fn variant(&self, encoding: ContentEncoding) -> Option<&'static [u8]> {
+        match encoding {
+            ContentEncoding::Brotli => self.brotli,
+            ContentEncoding::Gzip => self.gzip,
+            ContentEncoding::Deflate => None,
+        }
+    }
+}
+
+/// A directory embedded into the binary with `embed_dir!`
+/// <br>
+/// `embed_dir!` expands to `crate::static_files::...` paths and therefore only works inside this crate.
+///
+/// # Example
+/// ``` rust
+/// use embed_macro_def::embed_dir;
+///
+/// static ASSETS: EmbeddedDir = embed_dir!("static", compress);
+/// ```
+pub struct EmbeddedDir {
+    /// The canonical path the directory was embedded from
+    pub directory: &'static str,
+    /// The files, sorted by path
+    pub files: &'static [EmbeddedFile],
+}
+
+#[allow(unused)]
+impl EmbeddedDir {
+    /// Gets a file by its path
+    /// # Arguments
+    /// * `path` - The path relative to the embedded directory, with `/` separators : &str
+    /// # Returns
+    /// * `Option<&EmbeddedFile>` - The file, `None` if no file was embedded at the path
+    pub fn get(&self, path: &str) -> Option<&'static EmbeddedFile> {
+        self.files
+            .binary_search_by(|file| file.path.cmp(path))
+            .ok()
+            .map(|index| &self.files[index])
+    }
+
+    /// Returns `true` if the path is a directory containing embedded files, `""` being the root
+    /// # Arguments
+    /// * `path` - The path relative to the embedded directory, without trailing slash : &str
+    pub fn is_directory(&self, path: &str) -> bool {
+        if path.is_empty() {
+            return true;
+        }
+        let prefix = format!("{}/", path);
+        let index = self
+            .files
+            .partition_point(|file| file.path < prefix.as_str());
+        self.files
+            .get(index)
+            .is_some_and(|file| file.path.starts_with(&prefix))
+    }
+}
+
+impl StaticFiles {
+    /// Serves an embedded file, like `serve_path` does for files on disk
+    /// <br>
+    /// Directories are answered with their index file, listings are not available for embedded directories.
+    /// # Arguments
+    /// * `dir` - The embedded directory : &EmbeddedDir
+    /// * `relative` - The decoded path relative to the root : &str
+    /// * `request` - The request : &Request
+    /// # Returns
+    /// * `Result<Response, Error>` - The file or the requested byte ranges of it, `304 Not Modified`, a redirect
+    ///   to the directory path with a trailing slash or `404 Not Found`
+    pub(super) fn serve_embedded(
+        &self,
+        dir: &'static EmbeddedDir,
+        relative: &str,
+        request: &Request,
+    ) -> Result<Response, Error> {
+        let mut path = self.segments(relative)?.join("/");
+        if dir.is_directory(&path) {
+            if !request.path.ends_with('/') {
+                return Ok(redirect_to_directory(request));
+            }
+            path = self
+                .index_files
+                .iter()
+                .map(|name| match path.is_empty() {
+                    true => name.clone(),
+                    false => format!("{}/{}", path, name),
+                })
+                .find(|index| dir.get(index).is_some())
+                .ok_or_else(|| Error::from_status(StatusCode::NotFound))?;
+        }
+        let file = dir
+            .get(&path)
+            .ok_or_else(|| Error::from_status(StatusCode::NotFound))?;
+
+        let mut headers = Vec::new();
+        let mut data = file.data;
+        let mut etag = file.etag.to_string();
+        let available: Vec<ContentEncoding> = [ContentEncoding::Brotli, ContentEncoding::Gzip]
+            .into_iter()
+            .filter(|encoding| file.variant(*encoding).is_some())
+            .collect();
+        if self.precompressed && !available.is_empty() {
+            headers.push(Header::new(
+                "Vary".to_string(),
+                "Accept-Encoding".to_string(),
+            ));
+            if let Some(encoding) = negotiate_encoding(request, &available) {
+                headers.push(Header::new(
+                    "Content-Encoding".to_string(),
+                    encoding.name().to_string(),
+                ));
+                data = file.variant(encoding).unwrap_or(file.data);
+                etag = format!("{}-{}\"", etag.trim_end_matches('"'), encoding.name());
+            }
+        }
+        if let Some(cache_control) = self.cache_control_for(&path) {
+            headers.push(Header::new(
+                "Cache-Control".to_string(),
+                cache_control.to_string(),
+            ));
+        }
+        headers.push(Header::new("ETag".to_string(), etag.clone()));
+        headers.push(Header::new(
+            "Last-Modified".to_string(),
+            format_http_date(file.modified()),
+        ));
+        let mut response = Response::new(
+            StatusCode::Ok,
+            file.content_type,
+            headers,
+            Vec::new(),
+            Vec::new(),
+        );
+        match evaluate_preconditions(request, Some(&etag), Some(file.modified())) {
+            Precondition::Passed => {}
+            Precondition::NotModified => return Ok(not_modified(response)),
+            Precondition::Failed => return Err(Error::from_status(StatusCode::PreconditionFailed)),
+        }
+        response.set_data(data);
+        Ok(apply_ranges(request, response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embed_macro_def::embed_dir;
+
+    use super::*;
+    use crate::http::{http_etag::content_etag, http_methods::HttpMethod};
+
+    static ASSETS: EmbeddedDir = embed_dir!("tests/fixtures/embedded", compress);
+
+    fn request(path: &str, headers: &[(&str, &str)]) -> Request {
+        Request::test(HttpMethod::GET, path, headers)
+    }
+
+    fn header(response: &Response, key: &str) -> Option<String> {
+        response
+            .get_header(key)
+            .map(|header| header.value().to_string())
+    }
+
+    #[test]
+    fn embeds_files_sorted_with_build_time_metadata() {
+        let paths: Vec<&str> = ASSETS.files.iter().map(|file| file.path).collect();
+        assert_eq!(
+            paths,
+            ["app.css", "index.html", "sub-notes.txt", "sub/data.json"]
+        );
+        let css = ASSETS.get("app.css").unwrap();
+        assert_eq!(css.content_type, ContentType::TextCssCharsetUtf8);
+        assert_eq!(css.etag, content_etag(css.data));
+        assert!(css.gzip.is_some() && css.brotli.is_some());
+        assert_eq!(
+            ASSETS.get("sub/data.json").unwrap().content_type,
+            ContentType::ApplicationJson
+        );
+    }
+
+    #[test]
+    fn finds_directories() {
+        assert!(ASSETS.is_directory(""));
+        assert!(ASSETS.is_directory("sub"));
+        assert!(!ASSETS.is_directory("su"));
+        assert!(!ASSETS.is_directory("app.css"));
+    }
+
+    #[test]
+    fn serves_index_files_and_redirects_directories() {
+        let files = StaticFiles::embedded(&ASSETS);
+        let response = files.serve("", &request("/", &[])).unwrap();
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.content_type, ContentType::TextHtmlCharsetUtf8);
+        assert_eq!(response.data, ASSETS.get("index.html").unwrap().data);
+
+        let response = files.serve("sub", &request("/sub", &[])).unwrap();
+        assert_eq!(response.status, StatusCode::MovedPermanently);
+        assert_eq!(header(&response, "Location").as_deref(), Some("/sub/"));
+
+        match files.serve("missing.txt", &request("/missing.txt", &[])) {
+            Err(error) => assert_eq!(error.status(), StatusCode::NotFound),
+            Ok(_) => panic!("Missing file was served"),
+        }
+    }
+
+    #[test]
+    fn serves_precompressed_variants_and_validators() {
+        let files = StaticFiles::embedded(&ASSETS);
+        let css = ASSETS.get("app.css").unwrap();
+        let response = files
+            .serve(
+                "app.css",
+                &request("/app.css", &[("Accept-Encoding", "gzip")]),
+            )
+            .unwrap();
+        assert_eq!(
+            header(&response, "Content-Encoding").as_deref(),
+            Some("gzip")
+        );
+        assert_eq!(response.data, css.gzip.unwrap());
+        let etag = header(&response, "ETag").unwrap();
+
+        let response = files
+            .serve(
+                "app.css",
+                &request(
+                    "/app.css",
+                    &[("Accept-Encoding", "gzip"), ("If-None-Match", &etag)],
+                ),
+            )
+            .unwrap();
+        assert_eq!(response.status, StatusCode::NotModified);
+    }
+}
@@ -0,0 +1,715 @@
+mod cache_policy;
+mod directory_listing;
+pub mod embedded;
+
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::Error,
+    http::{
+        http_cache_control::CacheControl,
+        http_codes::StatusCode,
+        http_content_types::ContentType,
+        http_encodings::{is_compressible, negotiate_encoding, ContentEncoding},
+        http_headers::Header,
+        http_preconditions::{
+            evaluate_preconditions, file_etag, format_http_date, not_modified, Precondition,
+        },
+        http_ranges::apply_ranges,
+    },
+    server::get_log_level,
+    transmitters::body::Body,
+    utils::logger::Logger,
+    Request, Response,
+};
+use cache_policy::CacheRule;
+use embedded::EmbeddedDir;
+
+/// How symbolic links inside the root directory are handled
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SymlinkPolicy {
+    /// Paths containing a symbolic link are not served
+    Deny,
+    /// Symbolic links are followed as long as their target is inside the root directory
+    WithinRoot,
+    /// Symbolic links are followed wherever they point to
+    Follow,
+}
+
+/// Serves the files of a directory
+/// <br>
+/// Requests can never leave the root directory: `..` segments are rejected and the resolved path
+/// must be inside the canonicalized root. Hidden files (starting with `.`) are not served unless enabled
+/// with `hidden_files`. A request for a directory is redirected to the path with a trailing slash and
+/// answered with the first index file found in it, or with a listing of its content if `directory_listing` is enabled.
+/// <br>
+/// Rejected and missing paths are answered with `404 Not Found`, so the response does not reveal whether a file exists.
+/// <br>
+/// Files from `stream_threshold` on are streamed from disk, with `sendfile` on Linux. Precompressed `.br` and `.gz`
+/// siblings of compressible files are preferred for clients accepting them.
+/// <br>
+/// With `spa_fallback`, unknown paths without a file extension are answered with the fallback file of a single-page application.
+/// <br>
+/// `Cache-Control` values are set per path or extension with `cache_control`. With `fingerprinted`, file names containing
+/// a content hash, such as `app.3f9a2c1b.js`, are sent with immutable caching.
+/// <br>
+/// Files embedded into the binary with `embed_dir!` are served with `StaticFiles::embedded`, with the same rules.
+///
+/// # Arguments
+/// * `root` - The directory to serve : &str
+///
+/// # Example
+/// ``` rust
+/// Server::new("127.0.0.1", 8000)
+///     .routes(add_routes!(Route::new("/api/users", users, HttpMethod::GET)))
+///     .mount("/assets", StaticFiles::new("./static").index_file("index.htm"))
+///     .mount("/", StaticFiles::new("./app/dist")
+///         .spa_fallback("index.html")
+///         .api_prefix("/api")
+///         .cache_control("*.html", CacheControl::no_cache())
+///         .fingerprinted(true)
+///         .default_cache_control(CacheControl::max_age(3600)))
+///     .start();
+/// ```
+pub struct StaticFiles {
+    root: PathBuf,
+    index_files: Vec<String>,
+    serve_hidden: bool,
+    symlinks: SymlinkPolicy,
+    stream_threshold: u64,
+    precompressed: bool,
+    directory_listing: bool,
+    spa_fallback: Option<String>,
+    api_prefixes: Vec<String>,
+    embedded: Option<&'static EmbeddedDir>,
+    cache_rules: Vec<CacheRule>,
+    fingerprinted: bool,
+    default_cache_control: Option<CacheControl>,
+}
+
+#[allow(unused)]
+impl StaticFiles {
+    pub fn new(root: &str) -> StaticFiles {
+        StaticFiles {
+            root: PathBuf::from(root),
+            index_files: vec!["index.html".to_string()],
+            serve_hidden: false,
+            symlinks: SymlinkPolicy::WithinRoot,
+            stream_threshold: 1024 * 1024,
+            precompressed: true,
+            directory_listing: false,
+            spa_fallback: None,
+            api_prefixes: Vec::new(),
+            embedded: None,
+            cache_rules: Vec::new(),
+            fingerprinted: false,
+            default_cache_control: None,
+        }
+    }
+
+    /// Serves files embedded into the binary with `embed_dir!` instead of files on disk
+    /// <br>
+    /// Content types, ETags and precompressed variants are computed at compile time.
+    /// Directory listings and symbolic links do not apply to embedded files.
+    /// # Arguments
+    /// * `dir` - The embedded directory : &'static EmbeddedDir
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    /// # Example
+    /// ``` rust
+    /// static ASSETS: EmbeddedDir = embed_dir!("static", compress);
+    ///
+    /// Server::new("127.0.0.1", 8000)
+    ///     .mount("/", StaticFiles::embedded(&ASSETS).debug_from_disk(true))
+    ///     .start();
+    /// ```
+    pub fn embedded(dir: &'static EmbeddedDir) -> StaticFiles {
+        let mut files = StaticFiles::new(dir.directory);
+        files.embedded = Some(dir);
+        files
+    }
+
+    /// Serves embedded files from the directory they were embedded from in debug builds, so changes are visible
+    /// without recompiling. Release builds always serve the embedded files. Defaults to `false`
+    /// # Arguments
+    /// * `enabled` - Whether to read the files from disk in debug builds : bool
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn debug_from_disk(mut self, enabled: bool) -> StaticFiles {
+        if enabled && cfg!(debug_assertions) {
+            self.embedded = None;
+        }
+        self
+    }
+
+    /// Adds an index file, searched in order after the ones already set. Defaults to `index.html`
+    /// # Arguments
+    /// * `name` - The file name : &str
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn index_file(mut self, name: &str) -> StaticFiles {
+        self.index_files.push(name.to_string());
+        self
+    }
+
+    /// Replaces the index files, an empty list disables them
+    /// # Arguments
+    /// * `names` - The file names : &[&str]
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn index_files(mut self, names: &[&str]) -> StaticFiles {
+        self.index_files = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// Sets whether hidden files and directories are served. Defaults to `false`
+    /// # Arguments
+    /// * `serve` - Whether to serve hidden files : bool
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn hidden_files(mut self, serve: bool) -> StaticFiles {
+        self.serve_hidden = serve;
+        self
+    }
+
+    /// Sets how symbolic links are handled. Defaults to `SymlinkPolicy::WithinRoot`
+    /// # Arguments
+    /// * `policy` - The policy : SymlinkPolicy
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> StaticFiles {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Sets the size from which files are streamed from disk instead of being read into memory. Defaults to 1 MiB
+    /// # Arguments
+    /// * `bytes` - The size in bytes : u64
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn stream_threshold(mut self, bytes: u64) -> StaticFiles {
+        self.stream_threshold = bytes;
+        self
+    }
+
+    /// Sets whether precompressed `.br` and `.gz` siblings of files are served to clients accepting them. Defaults to `true`
+    /// # Arguments
+    /// * `enabled` - Whether to serve precompressed files : bool
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn precompressed(mut self, enabled: bool) -> StaticFiles {
+        self.precompressed = enabled;
+        self
+    }
+
+    /// Sets whether directories without an index file are answered with a listing of their content. Defaults to `false`
+    /// <br>
+    /// The listing is an HTML page, or JSON for clients preferring `application/json`. Hidden files and links which
+    /// must not be served are not listed.
+    /// # Arguments
+    /// * `enabled` - Whether to list directories : bool
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn directory_listing(mut self, enabled: bool) -> StaticFiles {
+        self.directory_listing = enabled;
+        self
+    }
+
+    /// Enables the single-page application mode: unknown paths are answered with a fallback file, e.g. `index.html`
+    /// <br>
+    /// Only paths whose last segment has no file extension fall back, so missing assets such as `/app.js` still get a `404 Not Found`.
+    /// # Arguments
+    /// * `file` - The path of the fallback file relative to the root : &str
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn spa_fallback(mut self, file: &str) -> StaticFiles {
+        self.spa_fallback = Some(file.to_string());
+        self
+    }
+
+    /// Excludes request paths starting with a prefix, e.g. `/api`, from the single-page application fallback
+    /// # Arguments
+    /// * `prefix` - The request path prefix : &str
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn api_prefix(mut self, prefix: &str) -> StaticFiles {
+        self.api_prefixes
+            .push(prefix.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Adds a `Cache-Control` rule, checked in the order rules were added
+    /// <br>
+    /// Patterns are relative to the root: `*.html` matches an extension, `assets/*` everything below a directory
+    /// and any other pattern an exact path.
+    /// # Arguments
+    /// * `pattern` - The pattern : &str
+    /// * `cache_control` - The header value for matching files : CacheControl
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn cache_control(mut self, pattern: &str, cache_control: CacheControl) -> StaticFiles {
+        self.cache_rules
+            .push(CacheRule::new(pattern, cache_control));
+        self
+    }
+
+    /// Sets the `Cache-Control` value of files matching no rule. By default no header is sent
+    /// # Arguments
+    /// * `cache_control` - The header value : CacheControl
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn default_cache_control(mut self, cache_control: CacheControl) -> StaticFiles {
+        self.default_cache_control = Some(cache_control);
+        self
+    }
+
+    /// Sets whether fingerprinted file names, e.g. `app.3f9a2c1b.js` or `index-3f9a2c1b.js`, are cached as immutable. Defaults to `false`
+    /// <br>
+    /// Only enable it if the build tool names files by a hexadecimal hash, see `is_fingerprinted`. HTML files are never
+    /// treated as fingerprinted.
+    /// # Arguments
+    /// * `enabled` - Whether to recognize fingerprinted files : bool
+    /// # Returns
+    /// * `StaticFiles` - The static files
+    pub fn fingerprinted(mut self, enabled: bool) -> StaticFiles {
+        self.fingerprinted = enabled;
+        self
+    }
+
+    /// Returns `true` if a request for an unknown path is answered with the single-page application fallback
+    fn falls_back(&self, relative: &str, request: &Request) -> bool {
+        let last_segment = relative.rsplit('/').next().unwrap_or_default();
+        let has_extension = last_segment.rfind('.').is_some_and(|position| position > 0);
+        let is_api = self.api_prefixes.iter().any(|prefix| {
+            request.path == *prefix || request.path.starts_with(&(prefix.clone() + "/"))
+        });
+        self.spa_fallback.is_some() && !has_extension && !is_api
+    }
+
+    /// Resolves a path relative to the root into a file system path
+    /// # Arguments
+    /// * `relative` - The decoded path relative to the root : &str
+    /// # Returns
+    /// * `Result<PathBuf, Error>` - The canonical path, `404 Not Found` if it is missing or must not be served
+    pub fn resolve(&self, relative: &str) -> Result<PathBuf, Error> {
+        let logger: Logger = Logger {
+            c_name: "STATIC",
+            level: get_log_level(),
+        };
+        let root = fs::canonicalize(&self.root)?;
+        let mut path = root.clone();
+        path.extend(self.segments(relative)?);
+        self.check_symlinks(&root, &path)?;
+        let canonical = fs::canonicalize(&path)
+            .map_err(|e| Error::from_status(StatusCode::NotFound).with_cause(e))?;
+        if self.symlinks != SymlinkPolicy::Follow && !canonical.starts_with(&root) {
+            logger.info(&["Rejected path outside of the root", relative]);
+            return Err(Error::from_status(StatusCode::NotFound));
+        }
+        Ok(canonical)
+    }
+
+    /// Splits a path relative to the root into its segments, skipping empty and `.` segments
+    /// # Arguments
+    /// * `relative` - The decoded path relative to the root : &str
+    /// # Returns
+    /// * `Result<Vec<&str>, Error>` - The segments, `404 Not Found` for `..` segments and hidden files which must not be served
+    fn segments<'a>(&self, relative: &'a str) -> Result<Vec<&'a str>, Error> {
+        let logger: Logger = Logger {
+            c_name: "STATIC",
+            level: get_log_level(),
+        };
+        let mut segments = Vec::new();
+        for segment in relative.split('/') {
+            match segment {
+                "" | "." => continue,
+                ".." => {
+                    logger.info(&["Rejected path traversal", relative]);
+                    return Err(Error::from_status(StatusCode::NotFound));
+                }
+                _ if segment.contains('\0') || segment.contains('\\') => {
+                    return Err(Error::from_status(StatusCode::NotFound));
+                }
+                _ if segment.starts_with('.') && !self.serve_hidden => {
+                    return Err(Error::from_status(StatusCode::NotFound));
+                }
+                _ => segments.push(segment),
+            }
+        }
+        Ok(segments)
+    }
+
+    /// Rejects the path if it contains a symbolic link below the root and links are denied
+    fn check_symlinks(&self, root: &Path, path: &Path) -> Result<(), Error> {
+        if self.symlinks != SymlinkPolicy::Deny {
+            return Ok(());
+        }
+        let mut current = root.to_path_buf();
+        for component in path.strip_prefix(root).unwrap_or(path).components() {
+            current.push(component);
+            let metadata = fs::symlink_metadata(&current)
+                .map_err(|e| Error::from_status(StatusCode::NotFound).with_cause(e))?;
+            if metadata.file_type().is_symlink() {
+                return Err(Error::from_status(StatusCode::NotFound));
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the first index file of a directory
+    /// # Arguments
+    /// * `relative` - The decoded path of the directory relative to the root : &str
+    /// # Returns
+    /// * `Option<(PathBuf, String)>` - The canonical path of the index file and its path relative to the root
+    fn find_index(&self, relative: &str) -> Option<(PathBuf, String)> {
+        self.index_files.iter().find_map(|name| {
+            let index = format!("{}/{}", relative, name);
+            self.resolve(&index)
+                .ok()
+                .filter(|path| path.is_file())
+                .map(|path| (path, index))
+        })
+    }
+
+    /// Finds the precompressed sibling of a file to send, e.g. `app.js.br` for `app.js`
+    /// # Arguments
+    /// * `relative` - The decoded path of the file relative to the root : &str
+    /// * `request` - The request : &Request
+    /// # Returns
+    /// * `(Option<(ContentEncoding, PathBuf)>, bool)` - The chosen coding and canonical path of the sibling,
+    ///   and whether any sibling exists
+    fn find_precompressed(
+        &self,
+        relative: &str,
+        request: &Request,
+    ) -> (Option<(ContentEncoding, PathBuf)>, bool) {
+        let siblings: Vec<(ContentEncoding, PathBuf)> =
+            [ContentEncoding::Brotli, ContentEncoding::Gzip]
+                .into_iter()
+                .filter_map(|encoding| {
+                    let extension = encoding.extension()?;
+                    self.resolve(&format!("{}.{}", relative, extension))
+                        .ok()
+                        .filter(|path| path.is_file())
+                        .map(|path| (encoding, path))
+                })
+                .collect();
+        let available: Vec<ContentEncoding> =
+            siblings.iter().map(|(encoding, _)| *encoding).collect();
+        let chosen = negotiate_encoding(request, &available).and_then(|chosen| {
+            siblings
+                .iter()
+                .find(|(encoding, _)| *encoding == chosen)
+                .cloned()
+        });
+        (chosen, !siblings.is_empty())
+    }
+
+    /// Serves a file
+    /// # Arguments
+    /// * `relative` - The decoded path relative to the root : &str
+    /// * `request` - The request : &Request
+    /// # Returns
+    /// * `Result<Response, Error>` - The file with its `ETag` and `Last-Modified` headers or the requested byte ranges of it,
+    ///   `304 Not Modified` if the client's copy is up to date, a redirect to the directory path with a trailing slash,
+    ///   the single-page application fallback or `404 Not Found`
+    pub fn serve(&self, relative: &str, request: &Request) -> Result<Response, Error> {
+        match self.serve_path(relative, request) {
+            Err(error)
+                if error.status() == StatusCode::NotFound && self.falls_back(relative, request) =>
+            {
+                let fallback = self.spa_fallback.as_deref().unwrap_or_default();
+                self.serve_path(fallback, request)
+            }
+            result => result,
+        }
+    }
+
+    /// Serves a file, without the single-page application fallback
+    fn serve_path(&self, relative: &str, request: &Request) -> Result<Response, Error> {
+        if let Some(dir) = self.embedded {
+            return self.serve_embedded(dir, relative, request);
+        }
+        let mut path = self.resolve(relative)?;
+        let mut served = relative.to_string();
+        if path.is_dir() {
+            if !request.path.ends_with('/') {
+                return Ok(redirect_to_directory(request));
+            }
+            (path, served) = match self.find_index(relative) {
+                Some(index) => index,
+                None if self.directory_listing => {
+                    return self.list_directory(&path, relative, request)
+                }
+                None => return Err(Error::from_status(StatusCode::NotFound)),
+            };
+        }
+        let content_type = ContentType::parse_file_name(&path.to_string_lossy());
+        let mut headers = Vec::new();
+        if self.precompressed && is_compressible(content_type) {
+            let (chosen, any) = self.find_precompressed(&served, request);
+            if any {
+                headers.push(Header::new(
+                    "Vary".to_string(),
+                    "Accept-Encoding".to_string(),
+                ));
+            }
+            if let Some((encoding, sibling)) = chosen {
+                headers.push(Header::new(
+                    "Content-Encoding".to_string(),
+                    encoding.name().to_string(),
+                ));
+                path = sibling;
+            }
+        }
+
+        if let Some(cache_control) = self.cache_control_for(&served) {
+            headers.push(Header::new(
+                "Cache-Control".to_string(),
+                cache_control.to_string(),
+            ));
+        }
+
+        let metadata = fs::metadata(&path)?;
+        let etag = file_etag(&metadata);
+        let last_modified = metadata.modified().ok();
+        headers.push(Header::new("ETag".to_string(), etag.clone()));
+        let mut response = Response::new(
+            StatusCode::Ok,
+            content_type,
+            headers,
+            Vec::new(),
+            Vec::new(),
+        );
+        if let Some(modified) = last_modified {
+            response.add_header(Header::new(
+                "Last-Modified".to_string(),
+                format_http_date(modified),
+            ));
+        }
+        match evaluate_preconditions(request, Some(&etag), last_modified) {
+            Precondition::Passed => {}
+            Precondition::NotModified => return Ok(not_modified(response)),
+            Precondition::Failed => return Err(Error::from_status(StatusCode::PreconditionFailed)),
+        }
+        if metadata.len() >= self.stream_threshold {
+            response.set_body(Body::file(File::open(&path)?)?);
+        } else {
+            response.set_data(fs::read(&path)?);
+        }
+        Ok(apply_ranges(request, response))
+    }
+}
+
+/// Redirects a directory request to the path with a trailing slash, so relative links resolve inside the directory
+/// <br>
+/// Leading slashes are collapsed, `//evil.com/` would be followed by browsers as a protocol-relative URL.
+fn redirect_to_directory(request: &Request) -> Response {
+    let mut location = format!("/{}/", request.path.trim_start_matches(['/', '\\']));
+    if !request.query.is_empty() {
+        location = location + "?" + &request.query;
+    }
+    let mut response = Response::get_empty();
+    response
+        .set_status(StatusCode::MovedPermanently)
+        .add_header(Header::new("Location".to_string(), location));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler::IntoResponse, http::http_methods::HttpMethod, sessions::session::generate_id,
+    };
+
+    /// A temporary directory, removed when dropped
+    pub(super) struct Fixture {
+        pub(super) path: PathBuf,
+    }
+
+    impl Fixture {
+        /// Creates the directory with files, given by their path relative to it
+        pub(super) fn new(files: &[(&str, &str)]) -> Fixture {
+            let path = std::env::temp_dir().join(format!("static-{}", generate_id()));
+            for (name, content) in files {
+                let file = path.join(name);
+                fs::create_dir_all(file.parent().unwrap()).unwrap();
+                fs::write(file, content).unwrap();
+            }
+            fs::create_dir_all(&path).unwrap();
+            Fixture { path }
+        }
+
+        pub(super) fn root(&self) -> &str {
+            self.path.to_str().unwrap()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+
+    pub(super) fn get(files: &StaticFiles, path: &str, headers: &[(&str, &str)]) -> Response {
+        let request = Request::test(HttpMethod::GET, path, headers);
+        let relative = request.path.trim_start_matches('/').to_string();
+        match files.serve(&relative, &request) {
+            Ok(response) => response,
+            Err(error) => error.into_response(),
+        }
+    }
+
+    pub(super) fn header(response: &Response, key: &str) -> Option<String> {
+        response
+            .get_header(key)
+            .map(|header| header.value().to_string())
+    }
+
+    #[test]
+    fn serves_files_with_content_type_and_validators() {
+        let fixture = Fixture::new(&[("site/app.css", "body {}")]);
+        let files = StaticFiles::new(fixture.root());
+        let response = get(&files, "/site/app.css", &[]);
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.content_type, ContentType::TextCssCharsetUtf8);
+        assert_eq!(response.data, b"body {}");
+        assert!(header(&response, "ETag").is_some());
+        assert!(header(&response, "Last-Modified").is_some());
+    }
+
+    #[test]
+    fn rejects_traversal_and_hidden_files() {
+        let fixture = Fixture::new(&[
+            ("public/a.txt", "a"),
+            ("secret.txt", "s"),
+            ("public/.env", "e"),
+        ]);
+        let files = StaticFiles::new(&format!("{}/public", fixture.root()));
+        for path in [
+            "/../secret.txt",
+            "/x/../../secret.txt",
+            "/.env",
+            "/a.txt%00",
+            "/..\\secret.txt",
+        ] {
+            assert_eq!(
+                get(&files, path, &[]).status,
+                StatusCode::NotFound,
+                "{}",
+                path
+            );
+        }
+        assert!(files.resolve("a.txt\0").is_err());
+        assert_eq!(get(&files, "/./a.txt", &[]).status, StatusCode::Ok);
+        let files = files.hidden_files(true);
+        assert_eq!(get(&files, "/.env", &[]).status, StatusCode::Ok);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn applies_symlink_policy() {
+        let fixture = Fixture::new(&[("public/a.txt", "a"), ("secret.txt", "s")]);
+        let public = fixture.path.join("public");
+        std::os::unix::fs::symlink(public.join("a.txt"), public.join("inside.txt")).unwrap();
+        std::os::unix::fs::symlink(fixture.path.join("secret.txt"), public.join("outside.txt"))
+            .unwrap();
+        let files = |policy| StaticFiles::new(public.to_str().unwrap()).symlinks(policy);
+
+        let deny = files(SymlinkPolicy::Deny);
+        assert_eq!(get(&deny, "/inside.txt", &[]).status, StatusCode::NotFound);
+        assert_eq!(get(&deny, "/a.txt", &[]).status, StatusCode::Ok);
+        let within = files(SymlinkPolicy::WithinRoot);
+        assert_eq!(get(&within, "/inside.txt", &[]).data, b"a");
+        assert_eq!(
+            get(&within, "/outside.txt", &[]).status,
+            StatusCode::NotFound
+        );
+        let follow = files(SymlinkPolicy::Follow);
+        assert_eq!(get(&follow, "/outside.txt", &[]).data, b"s");
+    }
+
+    #[test]
+    fn redirects_directories_and_serves_index_files() {
+        let fixture = Fixture::new(&[("docs/index.htm", "docs")]);
+        let files = StaticFiles::new(fixture.root()).index_file("index.htm");
+        let response = get(&files, "/docs?page=2", &[]);
+        assert_eq!(response.status, StatusCode::MovedPermanently);
+        assert_eq!(
+            header(&response, "Location").as_deref(),
+            Some("/docs/?page=2")
+        );
+        assert_eq!(get(&files, "/docs/", &[]).data, b"docs");
+        assert_eq!(get(&files, "/", &[]).status, StatusCode::NotFound);
+
+        let mut request = Request::test(HttpMethod::GET, "//evil.com", &[]);
+        request.path = "//evil.com".to_string();
+        let response = redirect_to_directory(&request);
+        assert_eq!(header(&response, "Location").as_deref(), Some("/evil.com/"));
+    }
+
+    #[test]
+    fn serves_precompressed_siblings() {
+        let fixture = Fixture::new(&[
+            ("app.js", "plain"),
+            ("app.js.br", "brotli"),
+            ("app.js.gz", "gzip"),
+            ("logo.png", "png"),
+            ("logo.png.gz", "png gzip"),
+        ]);
+        let files = StaticFiles::new(fixture.root());
+        let response = get(&files, "/app.js", &[("Accept-Encoding", "gzip, br")]);
+        assert_eq!(response.data, b"brotli");
+        assert_eq!(header(&response, "Content-Encoding").as_deref(), Some("br"));
+        assert_eq!(
+            header(&response, "Vary").as_deref(),
+            Some("Accept-Encoding")
+        );
+        assert_eq!(
+            response.content_type,
+            ContentType::parse_file_name("app.js")
+        );
+        let response = get(&files, "/app.js", &[("Accept-Encoding", "gzip")]);
+        assert_eq!(response.data, b"gzip");
+        let response = get(&files, "/app.js", &[]);
+        assert_eq!(response.data, b"plain");
+        assert_eq!(
+            header(&response, "Vary").as_deref(),
+            Some("Accept-Encoding")
+        );
+
+        let response = get(&files, "/logo.png", &[("Accept-Encoding", "gzip")]);
+        assert_eq!(response.data, b"png");
+        assert!(header(&response, "Vary").is_none());
+        let files = files.precompressed(false);
+        let response = get(&files, "/app.js", &[("Accept-Encoding", "br")]);
+        assert_eq!(response.data, b"plain");
+    }
+
+    #[test]
+    fn falls_back_to_spa_entry_for_unknown_routes() {
+        let fixture = Fixture::new(&[("index.html", "app"), ("app.js", "js")]);
+        let files = StaticFiles::new(fixture.root());
+        assert_eq!(get(&files, "/users/42", &[]).status, StatusCode::NotFound);
+
+        let files = files.spa_fallback("index.html").api_prefix("/api/");
+        for path in ["/users/42", "/settings", "/apiary", "/v1.2/users"] {
+            let response = get(&files, path, &[]);
+            assert_eq!(response.status, StatusCode::Ok, "{}", path);
+            assert_eq!(response.data, b"app", "{}", path);
+        }
+        assert_eq!(get(&files, "/app.js", &[]).data, b"js");
+        for path in ["/missing.js", "/favicon.ico", "/api", "/api/users"] {
+            assert_eq!(
+                get(&files, path, &[]).status,
+                StatusCode::NotFound,
+                "{}",
+                path
+            );
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     thread,
 };
 
-use crate::{server::get_log_level, Logger};
+use crate::{server::get_log_level, utils::panic::catch, Logger};
 /// # ThreadPool
 ///
 /// The WorkerPool is a struct that represents a pool of worker threads. This
@@ -12,6 +12,9 @@ use crate::{server::get_log_level, Logger};
 /// started and waiting for jobs to execute. When a job is added to the pool,
 /// the next available worker thread will execute it. The pool also handles the
 /// shutdown of the worker threads when the pool is dropped.
+/// <br>
+/// A panicking job does not take its worker down, the panic is caught and logged.
+/// Should a worker thread die anyway it is respawned when the next job is added.
 ///
 /// # Arguments
 /// * `size` - The number of threads in the worker pool
@@ -25,7 +28,8 @@ use crate::{server::get_log_level, Logger};
 /// });
 /// ```
 pub struct ThreadPool {
-    workers: Vec<Worker>,
+    workers: Mutex<Vec<Worker>>,
+    receiver: Arc<Mutex<mpsc::Receiver<Job>>>,
     sender: Option<mpsc::Sender<Job>>,
 }
 
@@ -40,7 +44,8 @@ impl ThreadPool {
         }
 
         ThreadPool {
-            workers,
+            workers: Mutex::new(workers),
+            receiver,
             sender: Some(sender),
         }
     }
@@ -49,18 +54,42 @@ impl ThreadPool {
     where
         F: FnOnce() + Send + 'static,
     {
+        self.respawn_dead_workers();
         let job = Box::new(f);
         self.sender.as_ref().unwrap().send(job).unwrap();
     }
+
+    /// Replaces the workers whose thread has died
+    fn respawn_dead_workers(&self) {
+        let logger = Logger::new("POOL", get_log_level());
+        let mut workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        for worker in workers.iter_mut() {
+            let dead = worker
+                .thread
+                .as_ref()
+                .is_some_and(|thread| thread.is_finished());
+            if dead {
+                if let Some(Err(_)) = worker.thread.take().map(|thread| thread.join()) {
+                    logger.error(
+                        "Thread panicked",
+                        &["Worker", &worker.id.to_string(), "died"],
+                    );
+                }
+                logger.info(&["Respawning worker", &worker.id.to_string()]);
+                *worker = Worker::new(worker.id, Arc::clone(&self.receiver));
+            }
+        }
+    }
 }
 impl Drop for ThreadPool {
     fn drop(&mut self) {
         drop(self.sender.take());
         let logger = Logger::new("WORKER", get_log_level());
-        for worker in &mut self.workers {
-            logger.info(&["Worker", &worker.id.to_string(), "started"]);
+        let workers = self.workers.get_mut().unwrap_or_else(|e| e.into_inner());
+        for worker in workers.iter_mut() {
+            logger.info(&["Worker", &worker.id.to_string(), "stopping"]);
             if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
+                let _ = thread.join();
             }
         }
     }
@@ -94,7 +123,12 @@ impl Worker {
             match message {
                 Ok(job) => {
                     logger.info(&["Worker", &id.to_string(), "started"]);
-                    job();
+                    if let Err(panic) = catch(job) {
+                        logger.error(
+                            panic.backtrace.as_str(),
+                            &["Worker", &id.to_string(), "job panicked:", &panic.message],
+                        );
+                    }
                 }
                 Err(err) => {
                     logger.error(
@@ -112,3 +146,27 @@ impl Worker {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use super::*;
+    use crate::utils::panic::install_hook;
+
+    #[test]
+    fn keeps_running_jobs_after_a_job_panicked() {
+        install_hook();
+        let pool = ThreadPool::new(1);
+        let (sender, receiver) = mpsc::channel();
+        pool.execute(|| panic!("job failed"));
+        for job in 0..3 {
+            let sender = sender.clone();
+            pool.execute(move || sender.send(job).unwrap());
+        }
+        let done: Vec<i32> = (0..3)
+            .map(|_| receiver.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(done, [0, 1, 2]);
+    }
+}
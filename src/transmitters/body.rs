@@ -0,0 +1,317 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    net::TcpStream,
+};
+
+/// Size of the chunks read from a streamed body
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A response body which is streamed to the client instead of being kept in memory
+/// <br>
+/// Bodies of unknown length are sent with `Transfer-Encoding: chunked`. File bodies are sent with
+/// `sendfile` on Linux, so the content never has to pass through user space.
+///
+/// # Example
+/// ``` rust
+/// fn download(_: Request) -> Result<Response, Error> {
+///     let mut response = Response::get_empty();
+///     response
+///         .set_content_type(ContentType::ApplicationZip)
+///         .set_body(Body::file(File::open("./downloads/archive.zip")?)?);
+///     Ok(response)
+/// }
+///
+/// fn logs(_: Request) -> Result<Response, Error> {
+///     let child = Command::new("journalctl").arg("-n100").stdout(Stdio::piped()).spawn()?;
+///     let mut response = Response::get_empty();
+///     response.set_body(Body::reader(child.stdout.unwrap()));
+///     Ok(response)
+/// }
+/// ```
+pub enum Body {
+    /// Any reader, `length` bytes are sent or, if unknown, everything until the end of the reader
+    Reader {
+        reader: Box<dyn Read + Send>,
+        length: Option<u64>,
+    },
+    /// `length` bytes of a file, starting at `offset`
+    File {
+        file: File,
+        offset: u64,
+        length: u64,
+    },
+}
+
+#[allow(unused)]
+impl Body {
+    /// Creates a body of unknown length, sent with chunked encoding
+    /// # Arguments
+    /// * `reader` - The reader : impl Read
+    /// # Returns
+    /// * `Body` - The body
+    pub fn reader(reader: impl Read + Send + 'static) -> Body {
+        Body::Reader {
+            reader: Box::new(reader),
+            length: None,
+        }
+    }
+
+    /// Creates a body of known length
+    /// # Arguments
+    /// * `reader` - The reader : impl Read
+    /// * `length` - The number of bytes to send : u64
+    /// # Returns
+    /// * `Body` - The body
+    pub fn sized_reader(reader: impl Read + Send + 'static, length: u64) -> Body {
+        Body::Reader {
+            reader: Box::new(reader),
+            length: Some(length),
+        }
+    }
+
+    /// Creates a body sending a whole file
+    /// # Arguments
+    /// * `file` - The file : File
+    /// # Returns
+    /// * `io::Result<Body>` - The body, or the error reading the metadata of the file
+    pub fn file(file: File) -> io::Result<Body> {
+        let length = file.metadata()?.len();
+        Ok(Body::File {
+            file,
+            offset: 0,
+            length,
+        })
+    }
+
+    /// Creates a body sending a part of a file
+    /// # Arguments
+    /// * `file` - The file : File
+    /// * `offset` - The position of the first byte to send : u64
+    /// * `length` - The number of bytes to send : u64
+    /// # Returns
+    /// * `Body` - The body
+    pub fn file_range(file: File, offset: u64, length: u64) -> Body {
+        Body::File {
+            file,
+            offset,
+            length,
+        }
+    }
+
+    /// Returns the length of the body, `None` if it is sent with chunked encoding
+    pub fn length(&self) -> Option<u64> {
+        match self {
+            Body::Reader { length, .. } => *length,
+            Body::File { length, .. } => Some(*length),
+        }
+    }
+
+    /// Converts the body into a reader of its content
+    pub fn into_reader(self) -> Box<dyn Read + Send> {
+        match self {
+            Body::Reader {
+                reader,
+                length: Some(length),
+            } => Box::new(reader.take(length)),
+            Body::Reader { reader, .. } => reader,
+            Body::File {
+                file,
+                offset,
+                length,
+            } => Box::new(FileReader {
+                file,
+                offset,
+                remaining: length,
+                positioned: false,
+            }),
+        }
+    }
+
+    /// Reads the whole body into memory
+    /// # Returns
+    /// * `io::Result<Vec<u8>>` - The content of the body
+    pub fn read_to_end(self) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.into_reader().read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Writes the body to the client
+    /// # Arguments
+    /// * `stream` - The stream to write to : &mut TcpStream
+    /// # Returns
+    /// * `io::Result<()>` - The error writing to the client or reading the body
+    pub fn write_to(self, stream: &mut TcpStream) -> io::Result<()> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Body::File {
+                file,
+                offset,
+                length,
+            } => send_file(stream, &file, offset, length),
+            body if body.length().is_some() => {
+                let length = body.length().unwrap_or_default();
+                let copied = io::copy(&mut body.into_reader(), stream)?;
+                match copied == length {
+                    true => Ok(()),
+                    false => Err(io::ErrorKind::UnexpectedEof.into()),
+                }
+            }
+            body => write_chunked(stream, body.into_reader()),
+        }
+    }
+}
+
+/// Reads a part of a file, seeking to its start on the first read
+/// <br>
+/// Several readers can share the same open file as long as they are read one after the other.
+struct FileReader {
+    file: File,
+    offset: u64,
+    remaining: u64,
+    positioned: bool,
+}
+
+impl Read for FileReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.positioned {
+            self.file.seek(SeekFrom::Start(self.offset))?;
+            self.positioned = true;
+        }
+        let max = buf.len().min(self.remaining as usize);
+        let read = self.file.read(&mut buf[..max])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// Writes a reader with `Transfer-Encoding: chunked`
+fn write_chunked(stream: &mut TcpStream, mut reader: Box<dyn Read + Send>) -> io::Result<()> {
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        stream.write_all(format!("{:x}\r\n", read).as_bytes())?;
+        stream.write_all(&buffer[..read])?;
+        stream.write_all(b"\r\n")?;
+    }
+    stream.write_all(b"0\r\n\r\n")
+}
+
+/// Sends a part of a file with `sendfile`, the kernel copies the content directly to the socket
+#[cfg(target_os = "linux")]
+fn send_file(stream: &mut TcpStream, file: &File, offset: u64, length: u64) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let mut position = offset as libc::off_t;
+    let mut remaining = length;
+    while remaining > 0 {
+        let count = remaining.min(0x7fff_f000) as usize;
+        // SAFETY: both descriptors are valid for the duration of the call and `position` is a valid pointer.
+        let sent =
+            unsafe { libc::sendfile(stream.as_raw_fd(), file.as_raw_fd(), &mut position, count) };
+        match sent {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            sent if sent > 0 => remaining -= sent as u64,
+            _ => {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, net::TcpListener, path::PathBuf, thread};
+
+    use super::*;
+    use crate::sessions::session::generate_id;
+
+    /// Writes `content` to a new temporary file and returns its path
+    fn temp_file(content: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("body-{}", generate_id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// Writes the body to a connected socket and returns what the client received
+    fn sent(body: Body) -> Vec<u8> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut received = Vec::new();
+            TcpStream::connect(address)
+                .unwrap()
+                .read_to_end(&mut received)
+                .unwrap();
+            received
+        });
+        let (mut stream, _) = listener.accept().unwrap();
+        body.write_to(&mut stream).unwrap();
+        drop(stream);
+        client.join().unwrap()
+    }
+
+    #[test]
+    fn reports_length_and_limits_sized_readers() {
+        assert_eq!(Body::reader(&b"abc"[..]).length(), None);
+        let body = Body::sized_reader(&b"abcdef"[..], 4);
+        assert_eq!(body.length(), Some(4));
+        assert_eq!(body.read_to_end().unwrap(), b"abcd");
+    }
+
+    #[test]
+    fn reads_whole_files_and_file_ranges() {
+        let path = temp_file(b"0123456789");
+        let body = Body::file(File::open(&path).unwrap()).unwrap();
+        assert_eq!(body.length(), Some(10));
+        assert_eq!(body.read_to_end().unwrap(), b"0123456789");
+
+        let file = File::open(&path).unwrap();
+        let first = Body::file_range(file.try_clone().unwrap(), 2, 3);
+        let second = Body::file_range(file, 7, 10);
+        assert_eq!(first.read_to_end().unwrap(), b"234");
+        assert_eq!(second.read_to_end().unwrap(), b"789");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sends_unknown_length_with_chunked_encoding() {
+        let content = vec![b'x'; CHUNK_SIZE + 10];
+        let mut expected = format!("{:x}\r\n", CHUNK_SIZE).into_bytes();
+        expected.extend_from_slice(&content[..CHUNK_SIZE]);
+        expected.extend_from_slice(b"\r\na\r\nxxxxxxxxxx\r\n0\r\n\r\n");
+        assert_eq!(sent(Body::reader(io::Cursor::new(content))), expected);
+        assert_eq!(sent(Body::reader(io::empty())), b"0\r\n\r\n");
+    }
+
+    #[test]
+    fn sends_sized_bodies_and_file_ranges_unchanged() {
+        assert_eq!(sent(Body::sized_reader(&b"abcdef"[..], 3)), b"abc");
+        let path = temp_file(b"0123456789");
+        let received = sent(Body::file_range(File::open(&path).unwrap(), 3, 4));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(received, b"3456");
+    }
+
+    #[test]
+    fn fails_when_sized_body_is_shorter_than_announced() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut stream, _) = listener.accept().unwrap();
+        let error = Body::sized_reader(&b"ab"[..], 5)
+            .write_to(&mut stream)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        drop(client);
+    }
+}
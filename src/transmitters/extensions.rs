@@ -0,0 +1,89 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::Arc,
+};
+
+/// Type map holding at most one value of every type
+/// <br>
+/// Used for the shared application state of the server and for per-request values which
+/// middlewares pass on to handlers, such as the authenticated user.
+/// Values are reference counted, cloning the map does not clone the values.
+///
+/// # Example
+/// ``` rust
+/// struct User { name: String }
+///
+/// let mut extensions = Extensions::new();
+/// extensions.insert(User { name: "admin".to_string() });
+/// assert_eq!(extensions.get::<User>().unwrap().name, "admin");
+/// ```
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+#[allow(unused)]
+impl Extensions {
+    pub fn new() -> Extensions {
+        Extensions {
+            map: HashMap::new(),
+        }
+    }
+
+    /// Inserts a value, replacing the previous value of the same type
+    /// # Arguments
+    /// * `value` - The value to insert : T
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.map.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Gets the value of a type
+    /// # Returns
+    /// * `Option<&T>` - The value or `None` if no value of the type was inserted
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map.get(&TypeId::of::<T>())?.downcast_ref::<T>()
+    }
+
+    /// Removes the value of a type
+    /// # Returns
+    /// * `bool` - `true` if a value was removed
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> bool {
+        self.map.remove(&TypeId::of::<T>()).is_some()
+    }
+
+    /// Returns `true` if a value of the type was inserted
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::http_methods::HttpMethod, Request};
+
+    #[test]
+    fn keeps_one_value_per_type() {
+        let mut extensions = Extensions::new();
+        extensions.insert(1u32);
+        extensions.insert(2u32);
+        extensions.insert("user".to_string());
+        assert_eq!(extensions.get::<u32>(), Some(&2));
+        assert_eq!(extensions.get::<String>().map(String::as_str), Some("user"));
+        assert!(extensions.get::<u64>().is_none());
+        assert!(extensions.remove::<u32>());
+        assert!(!extensions.contains::<u32>());
+        assert!(!extensions.remove::<u32>());
+    }
+
+    #[test]
+    fn shares_application_state_with_requests() {
+        let mut state = Extensions::new();
+        state.insert(Arc::new(vec!["db".to_string()]));
+        let mut request = Request::test(HttpMethod::GET, "/", &[]);
+        request.app_state = Arc::new(state);
+        assert_eq!(request.state::<Vec<String>>().unwrap()[0], "db");
+        assert!(request.state::<u32>().is_none());
+    }
+}
@@ -1,2 +1,4 @@
+pub mod body;
+pub mod extensions;
 pub mod request;
 pub mod response;
@@ -1,16 +1,24 @@
 use crate::http::{http_cookies::*, http_headers::*, http_methods::*};
 use crate::server;
+use crate::sessions::session::Session;
+use crate::transmitters::extensions::Extensions;
 use crate::utils::logger::Logger;
-use std::fmt::Display;
+use std::{fmt::Display, sync::Arc};
 
 /// Represents a request
 /// # Arguments
 /// * `method` - The method of the request : HttpMethod
-/// * `path` - The path of the request : String
-/// * `path_variables` - The path variables of the request : Vec<String>
+/// * `path` - The path of the request without query string : String
+/// * `query` - The query string of the request, without the leading `?` : String
+/// * `path_variables` - The path variables of the request, set when the request is routed : Vec<String>
 /// * `headers` - The headers of the request : Vec<Header>
 /// * `cookies` - The cookies of the request : Vec<Cookie>
-/// * `data` - The data of the request : String
+/// * `data` - The body of the request as text, invalid UTF-8 is replaced with `U+FFFD` : String
+/// * `raw_data` - The body of the request as sent, after decoding its `Content-Encoding` : Vec<u8>
+/// * `session` - The session of the request, set when sessions are enabled : Option<Session>
+/// * `peer_addr` - The address of the client : String
+/// * `extensions` - Per-request values set by middlewares, e.g. the authenticated user : Extensions
+/// * `app_state` - The application state registered on the server : Arc<Extensions>
 ///
 /// # Example
 /// ```
@@ -23,13 +31,20 @@ use std::fmt::Display;
 ///     "Hello world".to_string(),
 /// );
 /// ```
+#[derive(Clone)]
 pub struct Request {
     pub method: HttpMethod,
     pub path: String,
+    pub query: String,
     pub path_variables: Vec<String>,
     pub headers: Vec<Header>,
     pub cookies: Vec<Cookie>,
     pub data: String,
+    pub raw_data: Vec<u8>,
+    pub session: Option<Session>,
+    pub peer_addr: String,
+    pub extensions: Extensions,
+    pub app_state: Arc<Extensions>,
 }
 
 #[allow(unused)]
@@ -45,14 +60,22 @@ impl Request {
         Request {
             method,
             path,
+            query: String::new(),
             path_variables,
             headers,
             cookies,
+            raw_data: data.clone().into_bytes(),
             data,
+            session: None,
+            peer_addr: String::new(),
+            extensions: Extensions::new(),
+            app_state: Arc::new(Extensions::new()),
         }
     }
 
     /// Parse a request from a string
+    /// <br>
+    /// Everything after the empty line ending the headers is kept unchanged as the body.
     /// # Arguments
     /// * `payload` - The payload of the request : String
     /// # Returns
@@ -67,51 +90,43 @@ impl Request {
             level: server::get_log_level(),
         };
         logger.debug(&["split", payload.as_str()]);
-        let mut lines = payload.lines();
+        let (head, data) = match (payload.find("\r\n\r\n"), payload.find("\n\n")) {
+            (Some(crlf), Some(lf)) if lf < crlf => (&payload[..lf], &payload[lf + 2..]),
+            (Some(crlf), _) => (&payload[..crlf], &payload[crlf + 4..]),
+            (None, Some(lf)) => (&payload[..lf], &payload[lf + 2..]),
+            (None, None) => (payload.as_str(), ""),
+        };
+        let mut lines = head.lines();
 
         let request_line = lines.next().unwrap_or_default();
         let mut parts = request_line.split_whitespace();
         let method = HttpMethod::get_method(parts.next().unwrap_or_default());
-        let path = parts.next().unwrap_or_default().to_string();
-
-        let path_variables = path
-            .split('/')
-            .filter(|segment| !segment.is_empty() && segment.starts_with(':'))
-            .map(|segment| segment.trim_start_matches(':').to_string())
-            .collect();
+        let target = parts.next().unwrap_or_default();
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let path = path.to_string();
+        let query = query.to_string();
+        let path_variables = Vec::new();
 
         let mut headers = Vec::new();
         let mut cookies = Vec::new();
-        let mut is_header_section = true;
-        let mut data = String::new();
 
         for line in lines {
-            if line.is_empty() {
-                is_header_section = false;
-                continue;
-            }
-
-            if is_header_section {
-                if let Some((key, value)) = line.split_once(": ") {
-                    let key = key.trim().to_string();
-                    let value = value.trim().to_string();
+            if let Some((key, value)) = line.split_once(": ") {
+                let key = key.trim().to_string();
+                let value = value.trim().to_string();
 
-                    if key.eq_ignore_ascii_case("Cookie") {
-                        for cookie in value.split("; ") {
-                            if let Some((cookie_key, cookie_value)) = cookie.split_once('=') {
-                                cookies.push(Cookie {
-                                    key: cookie_key.to_string(),
-                                    value: cookie_value.to_string(),
-                                });
-                            }
+                if key.eq_ignore_ascii_case("Cookie") {
+                    for cookie in value.split("; ") {
+                        if let Some((cookie_key, cookie_value)) = cookie.split_once('=') {
+                            cookies.push(Cookie::new(
+                                cookie_key.to_string(),
+                                cookie_value.to_string(),
+                            ));
                         }
-                    } else {
-                        headers.push(Header { key, value });
                     }
+                } else {
+                    headers.push(Header { key, value });
                 }
-            } else {
-                data.push_str(line);
-                data.push('\n');
             }
         }
 
@@ -119,10 +134,16 @@ impl Request {
         Request {
             method,
             path,
+            query,
             path_variables,
             headers,
             cookies,
-            data: data.trim_end().to_string(),
+            data: data.to_string(),
+            raw_data: data.as_bytes().to_vec(),
+            session: None,
+            peer_addr: String::new(),
+            extensions: Extensions::new(),
+            app_state: Arc::new(Extensions::new()),
         }
     }
     /// Create an empty request
@@ -161,7 +182,7 @@ impl Request {
     /// * `Option<Header>` - The header : Option<Header>
     pub fn get_header(&self, key: &str) -> Option<Header> {
         for header in &self.headers {
-            if header.key == key {
+            if header.key.eq_ignore_ascii_case(key) {
                 return Some(header.clone());
             }
         }
@@ -181,6 +202,20 @@ impl Request {
         None
     }
 
+    /// Get the application state of a type registered with `Server::with_state`
+    /// # Returns
+    /// * `Option<Arc<T>>` - The state : Option<Arc<T>>
+    /// # Example
+    /// ```
+    /// fn users(request: Request) -> Response {
+    ///     let db = request.state::<Database>().unwrap();
+    ///     ...
+    /// }
+    /// ```
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.app_state.get::<Arc<T>>().cloned()
+    }
+
     /// Set the method of the request
     /// # Arguments
     /// * `method` - The method of the request : HttpMethod
@@ -241,13 +276,45 @@ impl Request {
 
     /// Set the data of the request
     /// # Arguments
-    /// * `data` - The data of the request : String
+    /// * `data` - The body of the request as text, invalid UTF-8 is replaced with `U+FFFD` : String
+    /// * `raw_data` - The body of the request as sent, after decoding its `Content-Encoding` : Vec<u8>
     /// # Returns
     /// * `Request` - The request
     pub fn set_data(&mut self, data: String) -> &mut Self {
+        self.raw_data = data.clone().into_bytes();
         self.data = data;
         self
     }
+
+    /// Set the data of the request from raw bytes, `data` gets the text with invalid UTF-8 replaced
+    /// # Arguments
+    /// * `raw_data` - The data of the request : Vec<u8>
+    /// # Returns
+    /// * `Request` - The request
+    pub fn set_raw_data(&mut self, raw_data: Vec<u8>) -> &mut Self {
+        self.data = String::from_utf8_lossy(&raw_data).into_owned();
+        self.raw_data = raw_data;
+        self
+    }
+}
+
+#[cfg(test)]
+impl Request {
+    /// Builds a request as the server would parse it, for tests
+    /// # Arguments
+    /// * `method` - The method : HttpMethod
+    /// * `target` - The path with an optional query string : &str
+    /// * `headers` - The headers, a `Cookie` header is parsed into cookies : &[(&str, &str)]
+    /// # Returns
+    /// * `Request` - The request
+    pub(crate) fn test(method: HttpMethod, target: &str, headers: &[(&str, &str)]) -> Request {
+        let mut payload = format!("{} {} HTTP/1.1\r\n", method, target);
+        for (key, value) in headers {
+            payload.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        payload.push_str("\r\n");
+        Request::parse(payload)
+    }
 }
 
 impl Display for Request {
@@ -265,10 +332,10 @@ impl Display for Request {
             .collect::<Vec<_>>()
             .join("\n");
 
-        return write!(
+        write!(
             f,
             "{}\n{}\n{}\n{}\n{}",
             self.method, self.path, headers_str, cookies_str, self.data
-        );
+        )
     }
 }
@@ -1,7 +1,8 @@
 use std::fmt::Display;
 
-use crate::http::{
-    http_codes::*, http_content_types::ContentType, http_cookies::*, http_headers::*,
+use crate::{
+    http::{http_codes::*, http_content_types::ContentType, http_cookies::*, http_headers::*},
+    transmitters::{body::Body, extensions::Extensions},
 };
 
 /// Represents a response
@@ -10,7 +11,11 @@ use crate::http::{
 /// * `resp_type` - The type of the response
 /// * `headers` - The headers of the response
 /// * `cookies` - The cookies of the response
-/// * `data` - The data of the response
+/// * `data` - The data of the response, text or binary
+///
+/// Large or generated content can be streamed instead with `set_body`, the `body` is then sent in place of `data`.
+///
+/// Values for the server and middlewares, e.g. an `Error` to be rendered, are kept in `extensions`. They are never sent to the client.
 ///
 /// # Returns
 /// * `Response` - The response object
@@ -34,7 +39,9 @@ pub struct Response {
     pub content_type: ContentType,
     pub headers: Vec<Header>,
     pub cookies: Vec<Cookie>,
-    pub data: String,
+    pub data: Vec<u8>,
+    pub body: Option<Body>,
+    pub extensions: Extensions,
 }
 #[allow(unused)]
 impl Response {
@@ -43,14 +50,16 @@ impl Response {
         resp_type: ContentType,
         headers: Vec<Header>,
         cookies: Vec<Cookie>,
-        data: String,
+        data: impl Into<Vec<u8>>,
     ) -> Response {
         Response {
             status,
             content_type: resp_type,
             headers,
             cookies,
-            data,
+            data: data.into(),
+            body: None,
+            extensions: Extensions::new(),
         }
     }
 
@@ -62,7 +71,9 @@ impl Response {
             status: tuple.0,
             headers: tuple.2,
             cookies: tuple.3,
-            data: tuple.4,
+            data: tuple.4.into_bytes(),
+            body: None,
+            extensions: Extensions::new(),
         }
     }
     pub fn get_empty() -> Response {
@@ -135,13 +146,60 @@ impl Response {
     }
     /// Sets the data of the response
     /// # Arguments
-    /// * `data` - The data of the response : String
+    /// * `data` - The data of the response : impl Into<Vec<u8>>
     /// # Returns
     /// * `response` - The response object: Response
-    pub fn set_data(&mut self, data: String) -> &mut Self {
-        self.data = data;
+    pub fn set_data(&mut self, data: impl Into<Vec<u8>>) -> &mut Self {
+        self.data = data.into();
+        self.body = None;
         self
     }
+
+    /// Sets a streamed body, replacing the data of the response
+    /// # Arguments
+    /// * `body` - The body of the response : Body
+    /// # Returns
+    /// * `response` - The response object: Response
+    pub fn set_body(&mut self, body: Body) -> &mut Self {
+        self.data = Vec::new();
+        self.body = Some(body);
+        self
+    }
+
+    /// Adds a header name to the `Vary` header of the response, unless it is already listed
+    /// # Arguments
+    /// * `name` - The name of the request header the response depends on : &str
+    /// # Returns
+    /// * `response` - The response object: Response
+    pub fn add_vary(&mut self, name: &str) -> &mut Self {
+        match self
+            .headers
+            .iter_mut()
+            .find(|header| header.key.eq_ignore_ascii_case("Vary"))
+        {
+            Some(header) => {
+                let listed = header
+                    .value
+                    .split(',')
+                    .any(|item| item.trim().eq_ignore_ascii_case(name) || item.trim() == "*");
+                if !listed {
+                    header.value = format!("{}, {}", header.value, name);
+                }
+            }
+            None => self
+                .headers
+                .push(Header::new("Vary".to_string(), name.to_string())),
+        }
+        self
+    }
+
+    /// Gets the length of the content, `None` if a streamed body of unknown length is sent with chunked encoding
+    pub fn content_length(&self) -> Option<u64> {
+        match &self.body {
+            Some(body) => body.length(),
+            None => Some(self.data.len() as u64),
+        }
+    }
     /// Gets a header from the response
     /// # Arguments
     /// * `key` - The key of the header : &str
@@ -149,7 +207,7 @@ impl Response {
     /// * `Option<Header>` - The header: Option<Header>
     pub fn get_header(&self, key: &str) -> Option<Header> {
         for header in &self.headers {
-            if header.key == key {
+            if header.key.eq_ignore_ascii_case(key) {
                 return Some(header.clone());
             }
         }
@@ -170,47 +228,58 @@ impl Response {
     }
     /// Prepares the response:
     /// <br>
-    /// Packs the status, headers, cookies and data into a string and converts the response into a string to be sended
+    /// Packs the status, headers, cookies and data into the bytes to be sended. A streamed body is not included.
     /// # Returns
-    /// * `String` - The response: String
-    pub fn prepare(&self) -> String {
-        let cookies_str = self
-            .cookies
-            .iter()
-            .map(|c| c.to_string())
-            .collect::<Vec<_>>()
-            .join("\n");
+    /// * `Vec<u8>` - The response: Vec<u8>
+    pub fn prepare(&self) -> Vec<u8> {
+        let mut response = self.prepare_head().into_bytes();
+        if self.has_body() {
+            response.extend_from_slice(&self.data);
+        }
+        response
+    }
 
-        let mut pre_response_headers = self.headers.clone();
-        pre_response_headers.push(Header::new(
-            "Access-Control-Allow-Origin".to_string(),
-            "*".to_string(),
-        ));
-        pre_response_headers.push(Header::new(
-            "Access-Control-Allow-Credentials".to_string(),
-            "true".to_string(),
-        ));
-        pre_response_headers.push(Header::new(
-            "Access-Control-Allow-Methods".to_string(),
-            "POST, GET, OPTIONS, PUT, DELETE, PATCH".to_string(),
-        ));
-        pre_response_headers.push(Header::new(
-            "Access-Control-Allow-Headers".to_string(),
-            "Content-Type, Authorization".to_string(),
-        ));
+    /// Returns `false` if the status of the response forbids a body: `1xx`, `204 No Content` and `304 Not Modified`
+    pub fn has_body(&self) -> bool {
+        let status = self.status as u16;
+        status >= 200 && status != 204 && status != 304
+    }
+
+    /// Prepares the status line and headers of the response, e.g. to answer a `HEAD` request
+    /// <br>
+    /// A `Content-Type` header, e.g. with a multipart boundary, replaces the content type of the response.
+    /// # Returns
+    /// * `String` - The head of the response, ending with an empty line: String
+    pub fn prepare_head(&self) -> String {
+        let mut pre_response_headers = Vec::new();
+        if self.get_header("Content-Type").is_none() {
+            pre_response_headers.push(Header::new(
+                "Content-Type".to_string(),
+                self.content_type.to_string(),
+            ));
+        }
+        pre_response_headers.extend(self.headers.clone());
+        for cookie in &self.cookies {
+            pre_response_headers.push(Header::new(
+                "Set-Cookie".to_string(),
+                cookie.to_set_cookie(),
+            ));
+        }
+        if self.has_body() {
+            pre_response_headers.push(match self.content_length() {
+                Some(length) => Header::new("Content-Length".to_string(), length.to_string()),
+                None => Header::new("Transfer-Encoding".to_string(), "chunked".to_string()),
+            });
+        }
 
         let headers_str = pre_response_headers
             .iter()
-            .map(|c| c.to_string())
-            .collect::<Vec<_>>()
-            .join("\n");
+            .map(|c| c.to_string() + "\r\n")
+            .collect::<String>();
 
         let status_line = get_status_line(self.status);
 
-        format!(
-            "{}\n{}\n{}\n{}\n{}",
-            status_line, self.content_type, headers_str, cookies_str, self.data
-        )
+        format!("{}\r\n{}\r\n", status_line, headers_str)
     }
 }
 
@@ -221,7 +290,9 @@ impl Default for Response {
             content_type: ContentType::TextPlainCharsetUtf8,
             headers: Vec::new(),
             cookies: Vec::new(),
-            data: "".to_string(),
+            data: Vec::new(),
+            body: None,
+            extensions: Extensions::new(),
         }
     }
 }
@@ -245,7 +316,13 @@ impl Display for Response {
         write!(
             f,
             "{}\n{}\n{}\n{}",
-            status_line, headers_str, cookies_str, self.data
+            status_line,
+            headers_str,
+            cookies_str,
+            match self.body {
+                Some(_) => "<streamed body>".into(),
+                None => String::from_utf8_lossy(&self.data),
+            }
         )
     }
 }
@@ -0,0 +1,23 @@
+/// Escapes a string for use in HTML text and attribute values
+/// # Arguments
+/// * `value` - The value to escape : &str
+/// # Returns
+/// * `String` - The escaped value : String
+/// # Example
+/// ``` rust
+/// assert_eq!(escape_html("<a href=\"x\">"), "&lt;a href=&quot;x&quot;&gt;");
+/// ```
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -52,7 +52,7 @@ impl Logger {
     }
 
     fn get_time() -> String {
-        let dt: DateTime<Utc> = SystemTime::now().clone().into();
+        let dt: DateTime<Utc> = SystemTime::now().into();
         dt.format("%d-%m-%Y %H:%M:%S:%3f").to_string()
     }
 
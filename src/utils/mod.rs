@@ -1 +1,4 @@
+pub mod html;
 pub mod logger;
+pub mod panic;
+pub mod url;
@@ -0,0 +1,94 @@
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    cell::{Cell, RefCell},
+    panic::{self, AssertUnwindSafe},
+    sync::Once,
+};
+
+thread_local! {
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    static LAST_PANIC: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+static HOOK: Once = Once::new();
+
+/// A caught panic
+/// # Arguments
+/// * `message` - The panic message with its location : String
+/// * `backtrace` - The backtrace of the panicking thread : String
+pub struct CaughtPanic {
+    pub message: String,
+    pub backtrace: String,
+}
+
+/// Installs the panic hook recording the message and backtrace of panics raised inside `catch`
+/// <br>
+/// Panics raised outside of `catch` are passed on to the previous hook. Installing the hook more than once has no effect.
+pub fn install_hook() {
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CATCHING.with(|catching| catching.get()) {
+                let backtrace = Backtrace::force_capture().to_string();
+                LAST_PANIC.with(|last| *last.borrow_mut() = Some((info.to_string(), backtrace)));
+            } else {
+                previous(info);
+            }
+        }));
+    });
+}
+
+/// Returns the message of a panic payload
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
+
+/// Runs a function, catching any panic it raises
+/// # Arguments
+/// * `f` - The function to run : FnOnce() -> R
+/// # Returns
+/// * `Result<R, CaughtPanic>` - The result of the function or the caught panic
+/// # Example
+/// ``` rust
+/// match catch(|| route.call(request)) {
+///     Ok(response) => response,
+///     Err(panic) => logger.error(&panic.backtrace, &["Handler panicked:", &panic.message]),
+/// }
+/// ```
+pub fn catch<R>(f: impl FnOnce() -> R) -> Result<R, CaughtPanic> {
+    let was_catching = CATCHING.with(|catching| catching.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(was_catching));
+    result.map_err(|payload| {
+        let (message, backtrace) = LAST_PANIC
+            .with(|last| last.borrow_mut().take())
+            .unwrap_or_else(|| (payload_message(payload.as_ref()), String::new()));
+        CaughtPanic { message, backtrace }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catches_panics_with_message() {
+        install_hook();
+        assert_eq!(catch(|| 4).ok(), Some(4));
+        let panic = catch(|| panic!("handler failed")).err().unwrap();
+        assert!(panic.message.contains("handler failed"));
+        let panic = catch(|| std::panic::panic_any(7u8)).err().unwrap();
+        assert!(
+            panic.message.contains("src/utils/panic.rs"),
+            "{}",
+            panic.message
+        );
+    }
+}
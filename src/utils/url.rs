@@ -0,0 +1,53 @@
+/// Decodes a percent-encoded URL component
+/// <br>
+/// Invalid escape sequences are kept as they are and invalid UTF-8 is replaced.
+/// # Arguments
+/// * `value` - The encoded value : &str
+/// # Returns
+/// * `String` - The decoded value : String
+/// # Example
+/// ``` rust
+/// assert_eq!(percent_decode("a%20b"), "a b");
+/// ```
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(Ok(byte)) = value
+                .get(i + 1..i + 3)
+                .map(|hex| u8::from_str_radix(hex, 16))
+            {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Percent-encodes a URL path segment, only unreserved characters are kept
+/// # Arguments
+/// * `value` - The value to encode : &str
+/// # Returns
+/// * `String` - The encoded value : String
+/// # Example
+/// ``` rust
+/// assert_eq!(percent_encode("a b/c"), "a%20b%2Fc");
+/// ```
+pub fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}